@@ -0,0 +1,4 @@
+//! The reqwest snippet exported from `fixtures/export.yaml`, compiled as an example so that
+//! the generated code is checked. The tests of the export module keep it up to date.
+
+include!("../fixtures/export_reqwest.rs.in");
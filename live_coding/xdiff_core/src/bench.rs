@@ -0,0 +1,321 @@
+use crate::{
+    cli::OverrideArgs,
+    client::HttpClients,
+    profile::{
+        req::RequestProfile,
+        res::{ResponseExt, ResponseProfile},
+    },
+    utils::diff_text,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use console::style;
+use tokio::{
+    sync::Semaphore,
+    time::{interval, MissedTickBehavior},
+};
+
+/// The latency percentiles in the report of a bench.
+const PERCENTILES: [f64; 4] = [50.0, 90.0, 95.0, 99.0];
+
+/// How hard a bench loads the server.
+#[derive(Debug, Clone, Copy)]
+pub struct Load {
+    /// The requests started per second. Without a rate every request starts as soon as
+    /// another one finishes, and with one it is only reached if the concurrency allows.
+    pub rate: Option<f64>,
+    /// The maximum number of requests in flight.
+    pub concurrency: usize,
+    pub duration: Duration,
+}
+
+/// The results of a bench.
+#[derive(Debug, Default)]
+pub struct BenchReport {
+    /// The time from the first request to the end of the last one.
+    pub elapsed: Duration,
+    /// The latencies of the requests which got a response, sorted.
+    pub latencies: Vec<Duration>,
+    pub statuses: BTreeMap<u16, usize>,
+    /// The number of the requests which failed without a response, by error.
+    pub errors: BTreeMap<String, usize>,
+    /// Responses sampled evenly across the bench, in the order they were received.
+    pub samples: Vec<ResponseExt>,
+}
+
+/// Collects the results of the requests as they finish.
+#[derive(Debug)]
+struct Recorder {
+    report: BenchReport,
+    capacity: usize,
+    /// Every response whose number is a multiple of the stride is sampled. The stride is
+    /// doubled and every other sample dropped once there are too many.
+    stride: usize,
+    sampled: Vec<(usize, ResponseExt)>,
+    count: usize,
+}
+
+impl Recorder {
+    fn new(capacity: usize) -> Self {
+        Self {
+            report: BenchReport::default(),
+            capacity,
+            stride: 1,
+            sampled: vec![],
+            count: 0,
+        }
+    }
+
+    fn record(&mut self, latency: Duration, result: Result<ResponseExt>) {
+        let res = match result {
+            Ok(res) => res,
+            Err(e) => {
+                *self.report.errors.entry(format!("{:#}", e)).or_default() += 1;
+                return;
+            }
+        };
+        self.report.latencies.push(latency);
+        *self.report.statuses.entry(res.status.as_u16()).or_default() += 1;
+
+        let number = self.count;
+        self.count += 1;
+        if self.capacity == 0 || !number.is_multiple_of(self.stride) {
+            return;
+        }
+        self.sampled.push((number, res));
+        if self.sampled.len() > self.capacity {
+            self.stride *= 2;
+            let stride = self.stride;
+            self.sampled
+                .retain(|(number, _)| number.is_multiple_of(stride));
+        }
+    }
+
+    fn finish(mut self, elapsed: Duration) -> BenchReport {
+        self.report.elapsed = elapsed;
+        self.report.latencies.sort();
+        self.report.samples = self.sampled.into_iter().map(|(_, res)| res).collect();
+        self.report
+    }
+}
+
+/// Send the request repeatedly for the duration of the load, keeping up to `samples`
+/// of the responses. Its pre-requests are only sent once, before the bench starts, and
+/// the benched requests are never retried so that failures and latencies are reported as is.
+pub async fn bench(
+    req: &RequestProfile,
+    clients: &HttpClients,
+    args: &OverrideArgs,
+    load: Load,
+    samples: usize,
+) -> Result<BenchReport> {
+    let req = Arc::new(req.prepare(clients, args, &mut Default::default()).await?);
+    let clients = clients.without_retries();
+    let recorder = Arc::new(Mutex::new(Recorder::new(samples)));
+    let concurrency = load.concurrency.max(1);
+    let permits = Arc::new(Semaphore::new(concurrency));
+    let mut ticker = load.rate.map(|rate| {
+        let mut ticker = interval(Duration::from_secs_f64(1.0 / rate));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+
+    let start = Instant::now();
+    loop {
+        if let Some(ref mut ticker) = ticker {
+            ticker.tick().await;
+        }
+        let permit = permits.clone().acquire_owned().await?;
+        if start.elapsed() >= load.duration {
+            break;
+        }
+        let (req, clients, args, recorder) =
+            (req.clone(), clients.clone(), args.clone(), recorder.clone());
+        tokio::spawn(async move {
+            let sent = Instant::now();
+            let result = req.send(&clients, &args).await;
+            recorder.lock().unwrap().record(sent.elapsed(), result);
+            drop(permit);
+        });
+    }
+    // wait for the requests in flight
+    let _ = permits.acquire_many(concurrency as u32).await?;
+
+    let recorder = std::mem::replace(&mut *recorder.lock().unwrap(), Recorder::new(0));
+    Ok(recorder.finish(start.elapsed()))
+}
+
+impl BenchReport {
+    pub fn requests(&self) -> usize {
+        self.latencies.len() + self.errors.values().sum::<usize>()
+    }
+
+    /// The requests finished per second.
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed.is_zero() {
+            true => 0.0,
+            false => self.requests() as f64 / self.elapsed.as_secs_f64(),
+        }
+    }
+
+    /// The latency which the given percent of the requests are at most, by the nearest rank.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let rank = (percent / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies.get(rank.max(1) - 1).copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let total: Duration = self.latencies.iter().sum();
+        (!self.latencies.is_empty()).then(|| total / self.latencies.len() as u32)
+    }
+
+    pub fn render(&self) -> Result<String> {
+        let mut output = String::new();
+        let requests = self.requests();
+        writeln!(
+            &mut output,
+            "{:<10}{} in {:.1}s, {:.1}/s",
+            "requests",
+            requests,
+            self.elapsed.as_secs_f64(),
+            self.throughput()
+        )?;
+
+        if let (Some(min), Some(max), Some(mean)) =
+            (self.latencies.first(), self.latencies.last(), self.mean())
+        {
+            let mut latency = format!("min {}  mean {}", ms(*min), ms(mean));
+            for percent in PERCENTILES {
+                let value = self.percentile(percent).unwrap_or_default();
+                write!(&mut latency, "  p{} {}", percent, ms(value))?;
+            }
+            writeln!(
+                &mut output,
+                "{:<10}{}  max {}",
+                "latency",
+                latency,
+                ms(*max)
+            )?;
+        }
+
+        let share = |count: usize| count as f64 * 100.0 / requests as f64;
+        for (idx, (status, count)) in self.statuses.iter().enumerate() {
+            let label = if idx == 0 { "statuses" } else { "" };
+            let text = format!("{} {:>8} {:>6.1}%", status, count, share(*count));
+            let text = match status {
+                200..=299 => style(text).green(),
+                300..=399 => style(text).yellow(),
+                _ => style(text).red(),
+            };
+            writeln!(&mut output, "{:<10}{}", label, text)?;
+        }
+        for (idx, (error, count)) in self.errors.iter().enumerate() {
+            let label = if idx == 0 { "errors" } else { "" };
+            writeln!(
+                &mut output,
+                "{:<10}{} {:>6.1}%  {}",
+                label,
+                style(count).red(),
+                share(*count),
+                error
+            )?;
+        }
+        Ok(output)
+    }
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Diff the sampled responses against the baseline, showing every distinct diff once.
+pub fn diff_samples(
+    samples: &[ResponseExt],
+    baseline: &ResponseExt,
+    profile: &ResponseProfile,
+) -> Result<String> {
+    let expected = baseline.get_text(profile)?;
+    let mut diffs: Vec<(String, usize)> = vec![];
+    for sample in samples {
+        let text = sample.get_text(profile)?;
+        if text == expected {
+            continue;
+        }
+        let diff = diff_text(&expected, &text)?;
+        match diffs.iter_mut().find(|(d, _)| *d == diff) {
+            Some((_, count)) => *count += 1,
+            None => diffs.push((diff, 1)),
+        }
+    }
+
+    let different: usize = diffs.iter().map(|(_, count)| count).sum();
+    let mut output = format!(
+        "{} of {} sampled responses differ from the baseline\n",
+        different,
+        samples.len()
+    );
+    for (diff, count) in diffs {
+        writeln!(
+            &mut output,
+            "{}",
+            style(format!("== {} of the samples:", count)).bold()
+        )?;
+        output.push_str(&diff);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use reqwest::{header::HeaderMap, StatusCode, Version};
+
+    fn response(status: StatusCode, body: &str) -> ResponseExt {
+        ResponseExt {
+            version: Version::HTTP_11,
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+            binary: false,
+            metrics: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_record_bench() {
+        let mut recorder = Recorder::new(4);
+        for i in 0..100u64 {
+            let status = if i % 10 == 0 {
+                StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                StatusCode::OK
+            };
+            let res = response(status, &i.to_string());
+            recorder.record(Duration::from_millis(100 - i), Ok(res));
+        }
+        recorder.record(Duration::ZERO, Err(anyhow!("connection refused")));
+        let report = recorder.finish(Duration::from_secs(2));
+
+        assert_eq!(report.requests(), 101);
+        assert_eq!(report.throughput(), 50.5);
+        assert_eq!(report.percentile(50.0), Some(Duration::from_millis(50)));
+        assert_eq!(report.percentile(99.0), Some(Duration::from_millis(99)));
+        assert_eq!(report.statuses, BTreeMap::from([(200, 90), (503, 10)]));
+        assert_eq!(report.errors["connection refused"], 1);
+        let bodies: Vec<&str> = report.samples.iter().map(|r| r.body.as_str()).collect();
+        assert_eq!(bodies, ["0", "32", "64", "96"]);
+
+        let baseline = report.samples[0].clone();
+        let output = diff_samples(&report.samples, &baseline, &ResponseProfile::default()).unwrap();
+        let output = console::strip_ansi_codes(&output);
+        assert!(output.starts_with("3 of 4 sampled responses differ from the baseline\n"));
+        assert_eq!(output.matches("== 1 of the samples:").count(), 3);
+    }
+}
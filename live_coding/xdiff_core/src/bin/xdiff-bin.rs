@@ -1,13 +1,36 @@
-use anyhow::{anyhow, Ok, Result};
+use anyhow::{anyhow, Result};
 use clap::Parser;
 use dialoguer::{theme::ColorfulTheme, Input, MultiSelect};
-use std::io::Write;
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
 use xdiff_core::{
-    cli::{Action, Args, OverrideArgs, RunArgs},
+    bench::{self, diff_samples},
+    cli::{
+        Action, Args, BenchArgs, DetectArgs, ExportArgs, ImportArgs, OutputFormat, OverrideArgs,
+        RunAllArgs, RunArgs, SnapshotArgs, TuiArgs, WatchArgs,
+    },
+    client::HttpClients,
     config::xdiff::DiffConfig,
-    profile::{req::RequestProfile, res::ResponseProfile, xdiff::DiffProfile},
-    utils::highlight_text,
+    export::export_requests,
+    flaky::{add_skips, detect_flakiness},
+    import::{import_profiles, with_origin},
+    profile::{
+        req::RequestProfile,
+        res::ResponseProfile,
+        xdiff::{DiffProfile, DiffTarget, REQ2},
+    },
+    snapshot::SnapshotStore,
+    tui::{run_tui, App},
+    utils::{
+        highlight_text,
+        render::{exit_code, summary_table, DiffOutcome},
+    },
+    watch::{wait_for_change, Trigger, Watch},
     LoadConfig,
 };
 
@@ -19,7 +42,16 @@ async fn main() -> Result<()> {
 
     match args.action {
         Action::Run(arg) => run(arg).await?,
+        Action::RunAll(arg) => run_all(arg).await?,
+        Action::Record(arg) => record(arg).await?,
+        Action::Verify(arg) => verify(arg).await?,
         Action::Parse => parse().await?,
+        Action::Import(arg) => import(arg).await?,
+        Action::Export(arg) => export(arg).await?,
+        Action::Detect(arg) => detect(arg).await?,
+        Action::Watch(arg) => watch(arg).await?,
+        Action::Tui(arg) => tui(arg).await?,
+        Action::Bench(arg) => bench(arg).await?,
         _ => panic!("Not implemented action"),
     };
 
@@ -30,7 +62,214 @@ async fn run(arg: RunArgs) -> Result<()> {
     let config_file = arg
         .config
         .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
-    let config = DiffConfig::load_yaml(&config_file).await?;
+    let override_args: OverrideArgs = arg.override_args.into();
+    let vars = override_args.variables(arg.env);
+    let config = DiffConfig::load_yaml_with(&config_file, &vars).await?;
+    let profile = config.get_profile(&arg.profile).ok_or_else(|| {
+        anyhow!(
+            "Profile {} not found in config file {}",
+            arg.profile,
+            config_file
+        )
+    })?;
+
+    let start = Instant::now();
+    let result = profile
+        .diff(&config.clients(), override_args, arg.target.as_deref())
+        .await;
+    let outcomes = DiffOutcome::from_targets(&arg.profile, result, start.elapsed());
+
+    let output = arg.format.renderer().render(&outcomes)?;
+    write!(std::io::stdout().lock(), "{}", output)?;
+
+    exit_with(&outcomes)
+}
+
+async fn watch(arg: WatchArgs) -> Result<()> {
+    let run = arg.run;
+    let config_file = run
+        .config
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let override_args: OverrideArgs = run.override_args.into();
+    let vars = override_args.variables(run.env);
+    let interval = arg.interval.map(Duration::from_secs);
+
+    let start = Instant::now();
+    let mut watch = Watch::new(arg.history);
+    let mut trigger = Trigger::Start;
+    // the files of the last config which loaded, as a broken config has no includes to watch
+    let mut files = vec![PathBuf::from(&config_file)];
+    loop {
+        // the config is loaded again every time, so that edits to it are picked up
+        let config = DiffConfig::load_yaml_with(&config_file, &vars).await;
+        if let Ok(config) = &config {
+            files.truncate(1);
+            files.extend_from_slice(config.sources.included());
+        }
+        let outcomes = match config {
+            Ok(config) => match config.get_profile(&run.profile) {
+                Some(profile) => {
+                    let iteration = Instant::now();
+                    let result = profile
+                        .diff(
+                            &config.clients(),
+                            override_args.clone(),
+                            run.target.as_deref(),
+                        )
+                        .await;
+                    DiffOutcome::from_targets(&run.profile, result, iteration.elapsed())
+                }
+                None => vec![DiffOutcome::new(
+                    &run.profile,
+                    Err(anyhow!(
+                        "Profile {} not found in config file {}",
+                        run.profile,
+                        config_file
+                    )),
+                    Duration::ZERO,
+                )],
+            },
+            Err(e) => vec![DiffOutcome::new(&run.profile, Err(e), Duration::ZERO)],
+        };
+
+        let changes = watch.update(&outcomes, trigger, start.elapsed())?;
+        let mut stdout = std::io::stdout().lock();
+        match trigger {
+            Trigger::Start => write!(stdout, "{}", run.format.renderer().render(&outcomes)?)?,
+            _ if changes.is_empty() => {}
+            _ => write!(stdout, "{}", changes)?,
+        }
+        // the whole history only when something changed, the latest run otherwise
+        match trigger != Trigger::Start && changes.is_empty() {
+            true => writeln!(stdout, "{}", watch.history().back().unwrap())?,
+            false => writeln!(stdout, "\n{}", watch.render_history())?,
+        }
+        drop(stdout);
+
+        trigger = wait_for_change(&files, interval).await;
+    }
+}
+
+async fn tui(arg: TuiArgs) -> Result<()> {
+    let config_file = arg
+        .config
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let override_args: OverrideArgs = arg.override_args.into();
+    let vars = override_args.variables(arg.env);
+    let config = DiffConfig::load_yaml_with(&config_file, &vars).await?;
+
+    let mut app = App::new(config_file, config);
+    run_tui(&mut app, &override_args).await
+}
+
+async fn run_all(arg: RunAllArgs) -> Result<()> {
+    let (config, names, override_args) = select_profiles(&arg).await?;
+    let outcomes = config
+        .diff_profiles(&names, &override_args, arg.concurrency)
+        .await?;
+
+    report(arg.format, &outcomes)
+}
+
+async fn record(arg: SnapshotArgs) -> Result<()> {
+    let (config, names, override_args) = select_profiles(&arg.run).await?;
+    let store = SnapshotStore::new(arg.snapshot_dir);
+    let recorded = config
+        .record_profiles(&names, &override_args, arg.run.concurrency, &store)
+        .await?;
+
+    let mut failed = false;
+    let mut stderr = std::io::stderr().lock();
+    for (name, result) in recorded {
+        match result {
+            Ok(path) => writeln!(stderr, "{} -> {}", name, path.display())?,
+            Err(e) => {
+                failed = true;
+                writeln!(stderr, "{}: failed to record: {:#}", name, e)?;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(2);
+    }
+    Ok(())
+}
+
+async fn verify(arg: SnapshotArgs) -> Result<()> {
+    let (config, names, override_args) = select_profiles(&arg.run).await?;
+    let store = SnapshotStore::new(arg.snapshot_dir);
+    let outcomes = config
+        .verify_profiles(&names, &override_args, arg.run.concurrency, &store)
+        .await?;
+
+    report(arg.run.format, &outcomes)
+}
+
+async fn select_profiles(arg: &RunAllArgs) -> Result<(DiffConfig, Vec<String>, OverrideArgs)> {
+    let config_file = arg
+        .config
+        .clone()
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let override_args: OverrideArgs = arg.override_args.clone().into();
+    let vars = override_args.variables(arg.env.clone());
+    let config = DiffConfig::load_yaml_with(&config_file, &vars).await?;
+    let pattern = arg.profile.as_deref().unwrap_or("*");
+    let names = config.select_profiles(pattern, &arg.tag)?;
+    if names.is_empty() {
+        return Err(anyhow!(
+            "No profile matches {} in config file {}",
+            pattern,
+            config_file
+        ));
+    }
+    Ok((config, names, override_args))
+}
+
+fn report(format: OutputFormat, outcomes: &[DiffOutcome]) -> Result<()> {
+    let output = format.renderer().render(outcomes)?;
+    write!(std::io::stdout().lock(), "{}", output)?;
+    write!(std::io::stderr().lock(), "\n{}", summary_table(outcomes)?)?;
+
+    exit_with(outcomes)
+}
+
+fn exit_with(outcomes: &[DiffOutcome]) -> Result<()> {
+    let code = exit_code(outcomes);
+    if code != 0 {
+        std::io::stdout().flush()?;
+        std::process::exit(code);
+    }
+    Ok(())
+}
+
+async fn import(arg: ImportArgs) -> Result<()> {
+    let mut profiles = BTreeMap::new();
+    for (name, req1) in import_profiles(&arg).await? {
+        let req2 = match arg.target {
+            Some(ref target) => with_origin(&req1, target)?,
+            None => req1.clone(),
+        };
+        profiles.insert(
+            name,
+            DiffProfile::new(req1, req2, ResponseProfile::default()),
+        );
+    }
+    write!(
+        std::io::stdout().lock(),
+        "---\n{}",
+        serde_yaml::to_string(&profiles)?
+    )?;
+
+    Ok(())
+}
+
+async fn export(arg: ExportArgs) -> Result<()> {
+    let config_file = arg
+        .config
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let override_args: OverrideArgs = arg.override_args.into();
+    let vars = override_args.variables(arg.env);
+    let config = DiffConfig::load_yaml_with(&config_file, &vars).await?;
     let profile = config.get_profile(&arg.profile).ok_or_else(|| {
         anyhow!(
             "Profile {} not found in config file {}",
@@ -39,13 +278,113 @@ async fn run(arg: RunArgs) -> Result<()> {
         )
     })?;
 
-    let override_args = arg.override_args.into();
-    let output = profile.diff(override_args).await?;
+    let mut reqs = vec![];
+    for (idx, pre) in profile.pre.iter().enumerate() {
+        labeled_chain(&mut reqs, &format!("pre-request {}", idx + 1), pre);
+    }
+    labeled_chain(&mut reqs, "req1", &profile.req1);
+    for (name, target) in profile.targets() {
+        if let DiffTarget::Request(ref req) = target {
+            labeled_chain(&mut reqs, name, req);
+        }
+    }
+
+    let output = export_requests(&reqs, &override_args, &config.client, arg.format)?;
     write!(std::io::stdout().lock(), "{}", output)?;
 
     Ok(())
 }
 
+async fn detect(arg: DetectArgs) -> Result<()> {
+    let config_file = arg
+        .config
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let override_args: OverrideArgs = arg.override_args.into();
+    let vars = override_args.variables(arg.env);
+    let config = DiffConfig::load_yaml_with(&config_file, &vars).await?;
+    let profile = config.get_profile(&arg.profile).ok_or_else(|| {
+        anyhow!(
+            "Profile {} not found in config file {}",
+            arg.profile,
+            config_file
+        )
+    })?;
+
+    let flakiness =
+        detect_flakiness(profile, &config.clients(), &override_args, arg.repeat).await?;
+    if flakiness.text_body {
+        eprintln!("The body is not json and changes between calls, consider a replace transform.");
+    }
+    if flakiness.headers.is_empty() && flakiness.body.is_empty() {
+        eprintln!("No headers or body paths change between calls.");
+        return Ok(());
+    }
+
+    let headers: Vec<String> = flakiness.headers.into_iter().collect();
+    let body: Vec<String> = flakiness.body.into_iter().collect();
+    let items: Vec<String> = headers
+        .iter()
+        .map(|name| format!("header {}", name))
+        .chain(body.iter().map(|path| format!("body {}", path)))
+        .collect();
+    let selected = match arg.yes {
+        true => (0..items.len()).collect(),
+        false => MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select items to skip")
+            .items(&items)
+            .defaults(&vec![true; items.len()])
+            .interact()?,
+    };
+    let skip_headers: Vec<String> = selected
+        .iter()
+        .filter_map(|&idx| headers.get(idx).cloned())
+        .collect();
+    let skip_body: Vec<String> = selected
+        .iter()
+        .filter_map(|&idx| body.get(idx.checked_sub(headers.len())?).cloned())
+        .collect();
+
+    if arg.write {
+        // the profile could be defined in a file included by the config
+        let file = config
+            .sources
+            .file(&arg.profile)
+            .unwrap_or_else(|| Path::new(&config_file));
+        let content = tokio::fs::read_to_string(file).await?;
+        let content = add_skips(&content, &arg.profile, &skip_headers, &skip_body)?;
+        tokio::fs::write(file, content).await?;
+        eprintln!("Updated profile {} in {}", arg.profile, file.display());
+        return Ok(());
+    }
+
+    let res = ResponseProfile::new(skip_headers, skip_body);
+    let result = serde_yaml::to_string(&BTreeMap::from([("res", res)]))?;
+    write!(
+        std::io::stdout().lock(),
+        "{}",
+        highlight_text(&result, "yaml")?
+    )?;
+
+    Ok(())
+}
+
+/// Add the request and its pre-requests, labeled in the order to send.
+fn labeled_chain<'a>(
+    reqs: &mut Vec<(String, &'a RequestProfile)>,
+    label: &str,
+    req: &'a RequestProfile,
+) {
+    let chain = req.chain();
+    let pre = chain.len() - 1;
+    for (idx, req) in chain.into_iter().enumerate() {
+        if idx < pre {
+            reqs.push((format!("{} pre-request {}", label, idx + 1), req));
+        } else {
+            reqs.push((label.to_string(), req));
+        }
+    }
+}
+
 async fn parse() -> Result<()> {
     let theme = ColorfulTheme::default();
     let profile_name: String = Input::with_theme(&theme)
@@ -60,7 +399,10 @@ async fn parse() -> Result<()> {
     let req1: RequestProfile = url1.parse()?;
     let req2: RequestProfile = url2.parse()?;
 
-    let headers = req1.send(&OverrideArgs::default()).await?.get_header_keys();
+    let headers = req1
+        .send(&HttpClients::default(), &OverrideArgs::default())
+        .await?
+        .get_header_keys();
     let skip_headers = MultiSelect::with_theme(&theme)
         .with_prompt("Select items to skip")
         .items(&headers)
@@ -82,3 +424,45 @@ async fn parse() -> Result<()> {
 
     Ok(())
 }
+
+async fn bench(arg: BenchArgs) -> Result<()> {
+    let load = arg.load()?;
+    let config_file = arg
+        .config
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let override_args: OverrideArgs = arg.override_args.into();
+    let vars = override_args.variables(arg.env);
+    let config = DiffConfig::load_yaml_with(&config_file, &vars).await?;
+    let profile = config.get_profile(&arg.profile).ok_or_else(|| {
+        anyhow!(
+            "Profile {} not found in config file {}",
+            arg.profile,
+            config_file
+        )
+    })?;
+
+    let clients = config.clients();
+    let target = arg.target.as_deref().unwrap_or(REQ2);
+    let (res1, req) = profile
+        .prepare_target(&clients, &override_args, target)
+        .await?;
+    eprintln!(
+        "Sending {} {} for {}s...",
+        req.method,
+        req.url,
+        load.duration.as_secs()
+    );
+    let report = bench::bench(&req, &clients, &override_args, load, arg.sample).await?;
+
+    let mut stdout = std::io::stdout().lock();
+    write!(stdout, "{}", report.render()?)?;
+    if !report.samples.is_empty() {
+        writeln!(stdout)?;
+        write!(
+            stdout,
+            "{}",
+            diff_samples(&report.samples, &res1, &profile.res)?
+        )?;
+    }
+    Ok(())
+}
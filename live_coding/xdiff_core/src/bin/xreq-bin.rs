@@ -1,12 +1,20 @@
 use anyhow::{anyhow, Ok, Result};
 use clap::Parser;
+use console::{style, Style};
 use dialoguer::{theme::ColorfulTheme, Input, MultiSelect};
+use reqwest::StatusCode;
 use std::io::Write;
+use tokio::fs;
 
 use xdiff_core::{
-    cli::{Action, Args, OverrideArgs, RunArgs},
-    config::{xdiff::DiffConfig, xreq::RequestConfig},
-    profile::{req::RequestProfile, res::ResponseProfile, xdiff::DiffProfile},
+    bench::{self, diff_samples},
+    cli::{BenchArgs, ExportArgs, ImportArgs, OverrideArgs, XreqAction, XreqArgs, XreqRunArgs},
+    client::HttpClients,
+    config::xreq::RequestConfig,
+    export::export_requests,
+    import::import_profiles,
+    profile::{req::RequestProfile, res::ResponseProfile},
+    snapshot::load_snapshot,
     utils::highlight_text,
     LoadConfig,
 };
@@ -15,22 +23,27 @@ const DEFAULT_CONFIG_PATH: &str = "~/.xreq";
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let args = XreqArgs::parse();
 
     match args.action {
-        Action::Run(arg) => run(arg).await?,
-        Action::Parse => parse().await?,
+        XreqAction::Run(arg) => run(arg).await?,
+        XreqAction::Parse => parse().await?,
+        XreqAction::Import(arg) => import(arg).await?,
+        XreqAction::Export(arg) => export(arg).await?,
+        XreqAction::Bench(arg) => bench(arg).await?,
         _ => panic!("Not implemented action"),
     };
 
     Ok(())
 }
 
-async fn run(arg: RunArgs) -> Result<()> {
+async fn run(arg: XreqRunArgs) -> Result<()> {
     let config_file = arg
         .config
         .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
-    let config = RequestConfig::load_yaml(&config_file).await?;
+    let override_args: OverrideArgs = arg.override_args.into();
+    let vars = override_args.variables(arg.env);
+    let config = RequestConfig::load_yaml_with(&config_file, &vars).await?;
     let profile = config.get_profile(&arg.profile).ok_or_else(|| {
         anyhow!(
             "Profile {} not found in config file {}",
@@ -39,39 +52,212 @@ async fn run(arg: RunArgs) -> Result<()> {
         )
     })?;
 
-    let override_args = arg.override_args.into();
-    let res = profile.send(&override_args).await?;
-    write!(std::io::stdout().lock(), "{:?}", res)?;
+    let res = profile.send(&config.clients(), &override_args).await?;
+    let mut stdout = std::io::stdout().lock();
+
+    if !arg.body_only {
+        writeln!(
+            stdout,
+            "{}",
+            status_style(res.status).apply_to(res.status_line())
+        )?;
+        for (name, value) in res.visible_headers(&profile.skip_headers) {
+            writeln!(stdout, "{}: {}", style(name).cyan(), value)?;
+        }
+    }
+    if let Some(ref file) = arg.raw {
+        let body = res.body_bytes()?;
+        fs::write(file, &body).await?;
+        eprintln!("Saved {} bytes of body to {}", body.len(), file.display());
+    } else if !arg.headers_only {
+        if !arg.body_only {
+            writeln!(stdout)?;
+        }
+        match res.pretty_body() {
+            Some(body) => match res.syntax() {
+                Some(syntax) if console::colors_enabled() => {
+                    writeln!(stdout, "{}", highlight_text(&body, syntax)?.trim_end())?
+                }
+                _ => writeln!(stdout, "{}", body.trim_end())?,
+            },
+            None => writeln!(
+                stdout,
+                "{}",
+                style("<binary body, save it with --raw FILE>").dim()
+            )?,
+        }
+    }
+
+    if profile.expect.is_empty() && profile.schema.is_none() {
+        return Ok(());
+    }
+    let mut results: Vec<(String, Option<String>)> = profile
+        .expect
+        .evaluate(&res)
+        .into_iter()
+        .map(|result| (result.name, result.failure))
+        .collect();
+    if let Some(ref schema) = profile.schema {
+        let violations = schema.load()?.validate_response(&res);
+        if violations.is_empty() {
+            results.push((format!("schema {}", schema.path.display()), None));
+        }
+        for violation in violations {
+            results.push((
+                format!("schema {}", violation.path),
+                Some(violation.message),
+            ));
+        }
+    }
+    writeln!(stdout)?;
+    for (name, failure) in &results {
+        match failure {
+            None => writeln!(stdout, "{} {}", style("PASS").green(), name)?,
+            Some(failure) => writeln!(stdout, "{} {}: {}", style("FAIL").red(), name, failure)?,
+        }
+    }
+    let failed = results
+        .iter()
+        .filter(|(_, failure)| failure.is_some())
+        .count();
+    writeln!(
+        stdout,
+        "{} passed, {} failed",
+        results.len() - failed,
+        failed
+    )?;
+    stdout.flush()?;
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn status_style(status: StatusCode) -> Style {
+    match status.as_u16() {
+        200..=299 => Style::new().green().bold(),
+        300..=399 => Style::new().yellow().bold(),
+        _ => Style::new().red().bold(),
+    }
+}
+
+async fn import(arg: ImportArgs) -> Result<()> {
+    let profiles = import_profiles(&arg).await?;
+    write!(
+        std::io::stdout().lock(),
+        "---\n{}",
+        serde_yaml::to_string(&profiles)?
+    )?;
+
+    Ok(())
+}
+
+async fn export(arg: ExportArgs) -> Result<()> {
+    let config_file = arg
+        .config
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let override_args: OverrideArgs = arg.override_args.into();
+    let vars = override_args.variables(arg.env);
+    let config = RequestConfig::load_yaml_with(&config_file, &vars).await?;
+    let profile = config.get_profile(&arg.profile).ok_or_else(|| {
+        anyhow!(
+            "Profile {} not found in config file {}",
+            arg.profile,
+            config_file
+        )
+    })?;
+
+    let chain = profile.chain();
+    let pre = chain.len() - 1;
+    let reqs: Vec<(String, &RequestProfile)> = chain
+        .into_iter()
+        .enumerate()
+        .map(|(idx, req)| {
+            if idx < pre {
+                (format!("pre-request {}", idx + 1), req)
+            } else {
+                (arg.profile.clone(), req)
+            }
+        })
+        .collect();
+
+    let output = export_requests(&reqs, &override_args, &config.client, arg.format)?;
+    write!(std::io::stdout().lock(), "{}", output)?;
 
     Ok(())
 }
 
+async fn bench(arg: BenchArgs) -> Result<()> {
+    let load = arg.load()?;
+    let config_file = arg
+        .config
+        .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+    let override_args: OverrideArgs = arg.override_args.into();
+    let vars = override_args.variables(arg.env);
+    let config = RequestConfig::load_yaml_with(&config_file, &vars).await?;
+    let profile = config.get_profile(&arg.profile).ok_or_else(|| {
+        anyhow!(
+            "Profile {} not found in config file {}",
+            arg.profile,
+            config_file
+        )
+    })?;
+    let baseline = match arg.baseline {
+        Some(ref file) => Some(load_snapshot(file).await?),
+        None => None,
+    };
+
+    eprintln!(
+        "Sending {} {} for {}s...",
+        profile.method,
+        profile.url,
+        load.duration.as_secs()
+    );
+    let report = bench::bench(profile, &config.clients(), &override_args, load, arg.sample).await?;
+
+    let mut stdout = std::io::stdout().lock();
+    write!(stdout, "{}", report.render()?)?;
+    let (baseline, samples) = match (baseline, report.samples.split_first()) {
+        (Some(baseline), _) => (baseline, report.samples.as_slice()),
+        (None, Some((first, rest))) => (first.clone(), rest),
+        (None, None) => return Ok(()),
+    };
+    if !samples.is_empty() {
+        let res_profile = ResponseProfile::new(profile.skip_headers.clone(), vec![]);
+        writeln!(stdout)?;
+        write!(
+            stdout,
+            "{}",
+            diff_samples(samples, &baseline, &res_profile)?
+        )?;
+    }
+    Ok(())
+}
+
 async fn parse() -> Result<()> {
     let theme = ColorfulTheme::default();
     let profile_name: String = Input::with_theme(&theme)
         .with_prompt("Profile name")
         .interact_text()?;
-    let url1: String = Input::with_theme(&theme)
-        .with_prompt("Url1")
-        .interact_text()?;
-    let url2: String = Input::with_theme(&theme)
-        .with_prompt("Url2")
+    let url: String = Input::with_theme(&theme)
+        .with_prompt("Url")
         .interact_text()?;
-    let req1: RequestProfile = url1.parse()?;
-    let req2: RequestProfile = url2.parse()?;
+    let mut profile: RequestProfile = url.parse()?;
 
-    let headers = req1.send(&OverrideArgs::default()).await?.get_header_keys();
-    let skip_headers = MultiSelect::with_theme(&theme)
-        .with_prompt("Select items to skip")
+    let headers = profile
+        .send(&HttpClients::default(), &OverrideArgs::default())
+        .await?
+        .get_header_keys();
+    profile.skip_headers = MultiSelect::with_theme(&theme)
+        .with_prompt("Select headers to skip")
         .items(&headers)
         .interact()?
         .iter()
         .map(|&idx| headers.get(idx).unwrap().to_string())
         .collect();
 
-    let res = ResponseProfile::new(skip_headers, vec![]);
-    let profile = DiffProfile::new(req1, req2, res);
-    let config = DiffConfig::new(vec![(profile_name, profile)].into_iter().collect());
+    let config = RequestConfig::new(vec![(profile_name, profile)].into_iter().collect());
 
     let result = serde_yaml::to_string(&config)?;
     write!(
@@ -1,5 +1,14 @@
+use crate::bench::Load;
+use crate::config::Variables;
+use crate::utils::render::{
+    ColorRenderer, DiffRenderer, HtmlRenderer, JsonRenderer, JunitRenderer, MatrixRenderer,
+    SideBySideRenderer, UnifiedRenderer,
+};
+use std::{path::PathBuf, time::Duration};
+
 use anyhow::{anyhow, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use console::Term;
 
 /// Diff two http requests and compare the difference of the responses.
 #[derive(Debug, Clone, Parser)]
@@ -13,8 +22,57 @@ pub struct Args {
 pub enum Action {
     /// The default xdiff action.
     Run(RunArgs),
+    /// Run all profiles, or those matching a pattern or tag, and summarize the results.
+    ///
+    /// Exits with 0 if all responses are the same, 1 if any differ and 2 if any profile failed.
+    RunAll(RunAllArgs),
+    /// Record the response of req1 of the selected profiles as snapshots.
+    Record(SnapshotArgs),
+    /// Diff the response of req1 of the selected profiles against their recorded snapshots.
+    ///
+    /// Exits with the same codes as `run-all`.
+    Verify(SnapshotArgs),
     /// Parse URLs to generate a profile.
     Parse,
+    /// Import profiles from a curl command, a HAR file or an OpenAPI spec and print them as yaml.
+    Import(ImportArgs),
+    /// Export the requests of a profile as curl or HTTPie commands, or a reqwest snippet.
+    Export(ExportArgs),
+    /// Send the requests of a profile repeatedly and propose skips for the headers and
+    /// body paths which change between identical calls.
+    Detect(DetectArgs),
+    /// Run a profile again on an interval or whenever the config file changes,
+    /// and show what changed since the previous run.
+    Watch(WatchArgs),
+    /// Browse the profiles of a config in a terminal UI, run them, and add skip rules
+    /// from the lines of their diffs.
+    Tui(TuiArgs),
+    /// Send a request repeatedly at a rate or concurrency for a duration, and report the
+    /// latency percentiles, the throughput and the statuses of the responses.
+    Bench(BenchArgs),
+}
+
+/// Send an http request and print the response.
+#[derive(Debug, Clone, Parser)]
+pub struct XreqArgs {
+    #[clap(subcommand)]
+    pub action: XreqAction,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+#[non_exhaustive]
+pub enum XreqAction {
+    /// The default xreq action.
+    Run(XreqRunArgs),
+    /// Parse a URL to generate a profile.
+    Parse,
+    /// Import profiles from a curl command, a HAR file or an OpenAPI spec and print them as yaml.
+    Import(ImportArgs),
+    /// Export the requests of a profile as curl or HTTPie commands, or a reqwest snippet.
+    Export(ExportArgs),
+    /// Send a request repeatedly at a rate or concurrency for a duration, and report the
+    /// latency percentiles, the throughput and the statuses of the responses.
+    Bench(BenchArgs),
 }
 
 #[derive(Debug, Clone, Parser)]
@@ -32,8 +90,315 @@ pub struct RunArgs {
     /// - For query params, use `-o key=value`.
     /// - For headers, use `-o %key=value`.
     /// - For body, use `-o @key=value`.
+    /// - For config variables, use `-o '$key=value'`.
+    #[clap(short, long, value_parser = parse_key_val, number_of_values=1)]
+    pub override_args: Vec<KeyVal>,
+
+    /// The environment in the `envs` block of the config used to resolve variables.
+    #[clap(short, long, value_parser)]
+    pub env: Option<String>,
+
+    /// Only diff req1 against the named target of the profile, or `req2`.
+    #[clap(long, value_parser)]
+    pub target: Option<String>,
+
+    /// The output format of the diff.
+    #[clap(short, long, value_enum, default_value_t = OutputFormat::Color)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct XreqRunArgs {
+    /// The config file name.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// The profile name in config.
+    #[clap(short, long, value_parser)]
+    pub profile: String,
+
+    /// The override args from cli input, see `xdiff run`.
+    #[clap(short, long, value_parser = parse_key_val, number_of_values=1)]
+    pub override_args: Vec<KeyVal>,
+
+    /// The environment in the `envs` block of the config used to resolve variables.
+    #[clap(short, long, value_parser)]
+    pub env: Option<String>,
+
+    /// Only print the status line and the headers of the response.
+    #[clap(long, value_parser, conflicts_with = "body-only")]
+    pub headers_only: bool,
+
+    /// Only print the body of the response.
+    #[clap(long, value_parser)]
+    pub body_only: bool,
+
+    /// Write the raw bytes of the body to a file rather than printing it.
+    #[clap(long, value_parser, value_name = "FILE")]
+    pub raw: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct RunAllArgs {
+    /// The config file name.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Only run the profiles whose name matches the glob pattern.
+    #[clap(short, long, value_parser)]
+    pub profile: Option<String>,
+
+    /// Only run the profiles with any of the tags.
+    #[clap(short, long, value_parser, number_of_values = 1)]
+    pub tag: Vec<String>,
+
+    /// The maximum number of profiles to run at the same time.
+    #[clap(short = 'j', long, value_parser, default_value_t = 4)]
+    pub concurrency: usize,
+
+    /// The override args from cli input, see `run`.
+    #[clap(short, long, value_parser = parse_key_val, number_of_values=1)]
+    pub override_args: Vec<KeyVal>,
+
+    /// The environment in the `envs` block of the config used to resolve variables.
+    #[clap(short, long, value_parser)]
+    pub env: Option<String>,
+
+    /// The output format of the diffs.
+    #[clap(short, long, value_enum, default_value_t = OutputFormat::Color)]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct SnapshotArgs {
+    #[clap(flatten)]
+    pub run: RunAllArgs,
+
+    /// The directory of the snapshot files.
+    #[clap(short = 'd', long, value_parser, default_value = "snapshots")]
+    pub snapshot_dir: String,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ImportArgs {
+    /// The kind of the input.
+    #[clap(value_enum)]
+    pub source: ImportSource,
+
+    /// The input file, or `-` to read from stdin.
+    #[clap(value_parser, default_value = "-")]
+    pub input: String,
+
+    /// The profile name of a curl command, derived from its method and path by default.
+    #[clap(short, long, value_parser)]
+    pub name: Option<String>,
+
+    /// The server url of the OpenAPI operations, instead of the first one in the spec.
+    #[clap(short, long, value_parser)]
+    pub server: Option<String>,
+
+    /// For xdiff, the origin req2 is sent to, e.g. `http://localhost:8080`.
+    /// req2 is the same as req1 if not given.
+    #[clap(short, long, value_parser)]
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ExportArgs {
+    /// The config file name.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// The profile name in config.
+    #[clap(short, long, value_parser)]
+    pub profile: String,
+
+    /// The override args from cli input, see `run`.
     #[clap(short, long, value_parser = parse_key_val, number_of_values=1)]
     pub override_args: Vec<KeyVal>,
+
+    /// The environment in the `envs` block of the config used to resolve variables.
+    #[clap(short, long, value_parser)]
+    pub env: Option<String>,
+
+    /// The form to export the requests to.
+    #[clap(short, long, value_enum, default_value_t = ExportFormat::Curl)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct DetectArgs {
+    /// The config file name.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// The profile name in config.
+    #[clap(short, long, value_parser)]
+    pub profile: String,
+
+    /// How many times each request is sent.
+    #[clap(short = 'n', long, value_parser, default_value_t = 5)]
+    pub repeat: usize,
+
+    /// The override args from cli input, see `run`.
+    #[clap(short, long, value_parser = parse_key_val, number_of_values=1)]
+    pub override_args: Vec<KeyVal>,
+
+    /// The environment in the `envs` block of the config used to resolve variables.
+    #[clap(short, long, value_parser)]
+    pub env: Option<String>,
+
+    /// Add the selected skips to the profile in the config file rather than printing them.
+    #[clap(short, long, value_parser)]
+    pub write: bool,
+
+    /// Take all the proposed skips without asking.
+    #[clap(short, long, value_parser)]
+    pub yes: bool,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct WatchArgs {
+    #[clap(flatten)]
+    pub run: RunArgs,
+
+    /// Run again every number of seconds, besides whenever the config file changes.
+    #[clap(short, long, value_parser)]
+    pub interval: Option<u64>,
+
+    /// The number of the recent runs to summarize.
+    #[clap(long, value_parser, default_value_t = 10)]
+    pub history: usize,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct TuiArgs {
+    /// The config file name.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// The override args from cli input, see `run`.
+    #[clap(short, long, value_parser = parse_key_val, number_of_values=1)]
+    pub override_args: Vec<KeyVal>,
+
+    /// The environment in the `envs` block of the config used to resolve variables.
+    #[clap(short, long, value_parser)]
+    pub env: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct BenchArgs {
+    /// The config file name.
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// The profile name in config.
+    #[clap(short, long, value_parser)]
+    pub profile: String,
+
+    /// For xdiff, the target of the profile to send, `req2` by default.
+    /// The sampled responses are diffed against the response of req1.
+    #[clap(long, value_parser)]
+    pub target: Option<String>,
+
+    /// The requests started per second, as many as the concurrency allows if not given.
+    #[clap(short, long, value_parser)]
+    pub rate: Option<f64>,
+
+    /// The maximum number of requests in flight.
+    #[clap(short = 'C', long, value_parser, default_value_t = 10)]
+    pub concurrency: usize,
+
+    /// How long to send the requests for, in seconds.
+    #[clap(short, long, value_parser, default_value_t = 10)]
+    pub duration: u64,
+
+    /// The number of responses sampled across the bench to diff against the baseline.
+    #[clap(short, long, value_parser, default_value_t = 0)]
+    pub sample: usize,
+
+    /// For xreq, a snapshot of the expected response to diff the samples against.
+    /// The first sample is the baseline if not given.
+    #[clap(short, long, value_parser)]
+    pub baseline: Option<PathBuf>,
+
+    /// The override args from cli input, see `run`.
+    #[clap(short, long, value_parser = parse_key_val, number_of_values=1)]
+    pub override_args: Vec<KeyVal>,
+
+    /// The environment in the `envs` block of the config used to resolve variables.
+    #[clap(short, long, value_parser)]
+    pub env: Option<String>,
+}
+
+impl BenchArgs {
+    pub fn load(&self) -> Result<Load> {
+        if self
+            .rate
+            .is_some_and(|rate| rate <= 0.0 || !rate.is_finite())
+        {
+            return Err(anyhow!("rate must be a positive number"));
+        }
+        Ok(Load {
+            rate: self.rate,
+            concurrency: self.concurrency,
+            duration: Duration::from_secs(self.duration),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A curl command line.
+    Curl,
+    /// An HTTPie command line.
+    Httpie,
+    /// A Rust program sending the requests with reqwest.
+    Reqwest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportSource {
+    /// A curl command line.
+    Curl,
+    /// A HAR file exported by browsers.
+    Har,
+    /// An OpenAPI 3 spec in json or yaml.
+    Openapi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored inline diff for terminals.
+    Color,
+    /// Plain unified diff without escape sequences.
+    Unified,
+    /// Json report of the hunks and changed json paths.
+    Json,
+    /// JUnit xml report for CI.
+    Junit,
+    /// Side-by-side view fitting the terminal width.
+    SideBySide,
+    /// Self-contained html report, e.g. for CI artifacts.
+    Html,
+    /// Matrix of the targets which differ from req1, and on which paths.
+    Matrix,
+}
+
+impl OutputFormat {
+    pub fn renderer(&self) -> Box<dyn DiffRenderer> {
+        match self {
+            OutputFormat::Color => Box::new(ColorRenderer),
+            OutputFormat::Unified => Box::new(UnifiedRenderer),
+            OutputFormat::Json => Box::new(JsonRenderer),
+            OutputFormat::Junit => Box::new(JunitRenderer),
+            OutputFormat::SideBySide => Box::new(SideBySideRenderer {
+                width: Term::stdout().size().1 as usize,
+            }),
+            OutputFormat::Html => Box::new(HtmlRenderer),
+            OutputFormat::Matrix => Box::new(MatrixRenderer),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -41,6 +406,7 @@ pub enum KeyValType {
     Query,
     Header,
     Body,
+    Var,
 }
 
 #[derive(Debug, Clone)]
@@ -63,6 +429,7 @@ fn parse_key_val<'a>(s: &'a str) -> Result<KeyVal> {
     let (key_type, key) = match key.chars().next() {
         Some('%') => (KeyValType::Header, &key[1..]),
         Some('@') => (KeyValType::Body, &key[1..]),
+        Some('$') => (KeyValType::Var, &key[1..]),
         Some(v) if v.is_ascii_alphabetic() => (KeyValType::Query, key),
         _ => return Err(anyhow!("Invalid key value pair")),
     };
@@ -74,11 +441,19 @@ fn parse_key_val<'a>(s: &'a str) -> Result<KeyVal> {
     })
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct OverrideArgs {
     pub headers: Vec<(String, String)>,
     pub body: Vec<(String, String)>,
     pub query: Vec<(String, String)>,
+    pub vars: Vec<(String, String)>,
+}
+
+impl OverrideArgs {
+    /// The variables to resolve the config with.
+    pub fn variables(&self, env: Option<String>) -> Variables {
+        Variables::new(env, self.vars.iter().cloned())
+    }
 }
 
 impl From<Vec<KeyVal>> for OverrideArgs {
@@ -86,12 +461,14 @@ impl From<Vec<KeyVal>> for OverrideArgs {
         let mut headers = vec![];
         let mut body = vec![];
         let mut query = vec![];
+        let mut vars = vec![];
 
         for arg in args {
             match arg.key_type {
-                KeyValType::Query => headers.push((arg.key, arg.value)),
-                KeyValType::Header => body.push((arg.key, arg.value)),
-                KeyValType::Body => query.push((arg.key, arg.value)),
+                KeyValType::Query => query.push((arg.key, arg.value)),
+                KeyValType::Header => headers.push((arg.key, arg.value)),
+                KeyValType::Body => body.push((arg.key, arg.value)),
+                KeyValType::Var => vars.push((arg.key, arg.value)),
             }
         }
 
@@ -99,6 +476,53 @@ impl From<Vec<KeyVal>> for OverrideArgs {
             headers,
             body,
             query,
+            vars,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_args() {
+        let args: Vec<KeyVal> = ["page=2", "%x-token=abc", "@title=a b", "$host=localhost"]
+            .into_iter()
+            .map(|s| parse_key_val(s).unwrap())
+            .collect();
+        let args: OverrideArgs = args.into();
+
+        let pair = |k: &str, v: &str| vec![(k.to_string(), v.to_string())];
+        assert_eq!(args.query, pair("page", "2"));
+        assert_eq!(args.headers, pair("x-token", "abc"));
+        assert_eq!(args.body, pair("title", "a b"));
+        assert_eq!(args.vars, pair("host", "localhost"));
+
+        assert!(parse_key_val("page").is_err());
+        assert!(parse_key_val("1=2").is_err());
+    }
+
+    #[test]
+    fn test_run_args() {
+        let xdiff =
+            |args: &[&str]| Args::try_parse_from([&["xdiff", "run", "-p", "a"], args].concat());
+        let xreq =
+            |args: &[&str]| XreqArgs::try_parse_from([&["xreq", "run", "-p", "a"], args].concat());
+
+        assert!(xdiff(&["--target", "b", "-f", "json"]).is_ok());
+        assert!(xdiff(&["--headers-only"]).is_err());
+        assert!(xdiff(&["--raw", "body.bin"]).is_err());
+
+        let Ok(XreqArgs {
+            action: XreqAction::Run(args),
+        }) = xreq(&["--raw", "body.bin"])
+        else {
+            panic!("xreq run should parse");
+        };
+        assert_eq!(args.raw, Some(PathBuf::from("body.bin")));
+        assert!(xreq(&["--headers-only", "--body-only"]).is_err());
+        assert!(xreq(&["--target", "b"]).is_err());
+        assert!(xreq(&["-f", "json"]).is_err());
+    }
+}
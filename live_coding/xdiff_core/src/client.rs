@@ -0,0 +1,377 @@
+use crate::{profile::auth::TokenCache, utils::resolve_path};
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    redirect::Policy,
+    Certificate, Client, Proxy, Request, Response,
+};
+use serde::{Deserialize, Serialize};
+use tokio::net::lookup_host;
+
+tokio::task_local! {
+    static DNS: Cell<Option<Duration>>;
+}
+
+/// The settings of the http client, in the top-level `client:` block of a config
+/// or in the `client:` block of a request profile to override the top-level one.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ClientConfig {
+    /// The timeout to connect to the server, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub connect_timeout_ms: Option<u64>,
+
+    /// The timeout of the whole request including reading the body, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub timeout_ms: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub retry: Option<RetryConfig>,
+
+    /// The proxy url for all requests, e.g. `http://localhost:8080`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub proxy: Option<String>,
+
+    /// Extra root certificates in PEM files.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub ca_certs: Vec<PathBuf>,
+
+    /// Accept invalid certificates, only for development.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub insecure: Option<bool>,
+
+    /// The maximum number of redirects to follow, 0 to not follow redirects.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max_redirects: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct RetryConfig {
+    /// How many times a failed request is retried.
+    pub max_retries: u32,
+
+    /// The delay before the first retry in milliseconds, doubled on every retry.
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+
+    /// The response statuses to retry on. Connection errors and timeouts are always retried.
+    #[serde(default = "default_retry_statuses")]
+    pub statuses: Vec<u16>,
+}
+
+fn default_backoff_ms() -> u64 {
+    100
+}
+
+fn default_retry_statuses() -> Vec<u16> {
+    vec![502, 503, 504]
+}
+
+impl ClientConfig {
+    /// Merge the overrides of a profile into this config.
+    pub fn merge(&self, overrides: &ClientConfig) -> ClientConfig {
+        ClientConfig {
+            connect_timeout_ms: overrides.connect_timeout_ms.or(self.connect_timeout_ms),
+            timeout_ms: overrides.timeout_ms.or(self.timeout_ms),
+            retry: overrides.retry.clone().or_else(|| self.retry.clone()),
+            proxy: overrides.proxy.clone().or_else(|| self.proxy.clone()),
+            ca_certs: self
+                .ca_certs
+                .iter()
+                .chain(&overrides.ca_certs)
+                .cloned()
+                .collect(),
+            insecure: overrides.insecure.or(self.insecure),
+            max_redirects: overrides.max_redirects.or(self.max_redirects),
+        }
+    }
+
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        for cert in &mut self.ca_certs {
+            resolve_path(cert, dir);
+        }
+    }
+
+    pub fn build(&self) -> Result<HttpClient> {
+        let mut builder = Client::builder();
+        if let Some(ms) = self.connect_timeout_ms {
+            builder = builder.connect_timeout(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.timeout_ms {
+            builder = builder.timeout(Duration::from_millis(ms));
+        }
+        match self.proxy {
+            Some(ref proxy) => {
+                builder =
+                    builder.proxy(Proxy::all(proxy).context(format!("invalid proxy {}", proxy))?)
+            }
+            // the resolver would only see the host of the proxy
+            None => builder = builder.dns_resolver(Arc::new(TimingResolver)),
+        }
+        for path in &self.ca_certs {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("failed to read ca cert {}", path.display()))?;
+            let cert = Certificate::from_pem(&pem)
+                .with_context(|| format!("invalid ca cert {}", path.display()))?;
+            builder = builder.add_root_certificate(cert);
+        }
+        if self.insecure == Some(true) {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        match self.max_redirects {
+            Some(0) => builder = builder.redirect(Policy::none()),
+            Some(max) => builder = builder.redirect(Policy::limited(max)),
+            None => {}
+        }
+
+        Ok(HttpClient {
+            client: builder.build()?,
+            retry: self.retry.clone(),
+        })
+    }
+}
+
+/// A http client with its retry policy.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    pub client: Client,
+    pub retry: Option<RetryConfig>,
+}
+
+impl HttpClient {
+    /// Execute the request, retrying with exponential backoff according to the retry policy.
+    pub async fn execute(&self, req: Request) -> Result<Response> {
+        Ok(self.execute_timed(req).await?.0)
+    }
+
+    /// Execute the request like `execute`, along with the timings of the attempt
+    /// which got the response, so that the backoff of the retries is not counted.
+    pub async fn execute_timed(&self, req: Request) -> Result<(Response, AttemptTimings)> {
+        let attempt = |req: Request| async move {
+            DNS.with(|dns| dns.take());
+            let start = Instant::now();
+            let res = self.client.execute(req).await;
+            let timings = AttemptTimings {
+                start,
+                ttfb: start.elapsed(),
+                dns: DNS.with(|dns| dns.get()),
+            };
+            res.map(|res| (res, timings))
+        };
+
+        DNS.scope(Cell::new(None), async {
+            let Some(ref retry) = self.retry else {
+                return Ok(attempt(req).await?);
+            };
+
+            let mut backoff = Duration::from_millis(retry.backoff_ms);
+            for _ in 0..retry.max_retries {
+                let Some(cloned) = req.try_clone() else {
+                    break;
+                };
+                match attempt(cloned).await {
+                    Ok((res, timings)) if !retry.statuses.contains(&res.status().as_u16()) => {
+                        return Ok((res, timings))
+                    }
+                    Err(e) if !(e.is_connect() || e.is_timeout()) => return Err(e.into()),
+                    _ => {
+                        tokio::time::sleep(backoff).await;
+                        backoff *= 2;
+                    }
+                }
+            }
+            Ok(attempt(req).await?)
+        })
+        .await
+    }
+}
+
+/// When the attempt which got a response started and how long it took to get its headers.
+/// The time to resolve the host is only known if the attempt opened a new connection rather
+/// than reusing one of the pool. reqwest has no hook into its connector, so the time to
+/// connect is not measured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttemptTimings {
+    pub start: Instant,
+    pub ttfb: Duration,
+    pub dns: Option<Duration>,
+}
+
+/// The system resolver, timing the lookup for the attempt being sent.
+#[derive(Debug)]
+struct TimingResolver;
+
+impl Resolve for TimingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let start = Instant::now();
+            let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0)).await?.collect();
+            let _ = DNS.try_with(|dns| dns.set(Some(start.elapsed())));
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The http clients of a config. A client is built once for every distinct
+/// client config and shared by all the requests using it.
+#[derive(Debug, Clone, Default)]
+pub struct HttpClients {
+    config: ClientConfig,
+    cache: Arc<Mutex<HashMap<ClientConfig, HttpClient>>>,
+    pub(crate) tokens: TokenCache,
+    /// Leave out the retry policy of the config and of the overrides.
+    no_retry: bool,
+}
+
+impl HttpClients {
+    pub fn new(config: ClientConfig) -> Self {
+        Self {
+            config,
+            cache: Arc::default(),
+            tokens: TokenCache::default(),
+            no_retry: false,
+        }
+    }
+
+    /// The same clients, but sending every request only once whatever the retry policy,
+    /// e.g. to measure the server as it is.
+    pub fn without_retries(&self) -> Self {
+        Self {
+            no_retry: true,
+            ..self.clone()
+        }
+    }
+
+    /// Get the client for the overrides of a profile, or the default one.
+    pub fn get(&self, overrides: Option<&ClientConfig>) -> Result<HttpClient> {
+        let mut config = match overrides {
+            Some(overrides) => self.config.merge(overrides),
+            None => self.config.clone(),
+        };
+        if self.no_retry {
+            config.retry = None;
+        }
+
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(client) = cache.get(&config) {
+            return Ok(client.clone());
+        }
+        let client = config.build()?;
+        cache.insert(config, client.clone());
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_client_config() {
+        let base: ClientConfig = serde_yaml::from_str(
+            r#"
+timeout_ms: 5000
+proxy: http://localhost:8080
+ca_certs: [base.pem]
+retry:
+  max_retries: 3
+"#,
+        )
+        .unwrap();
+        let overrides: ClientConfig = serde_yaml::from_str(
+            r#"
+timeout_ms: 1000
+insecure: true
+ca_certs: [dev.pem]
+"#,
+        )
+        .unwrap();
+
+        let merged = base.merge(&overrides);
+        assert_eq!(merged.timeout_ms, Some(1000));
+        assert_eq!(merged.proxy.as_deref(), Some("http://localhost:8080"));
+        assert_eq!(merged.insecure, Some(true));
+        assert_eq!(
+            merged.ca_certs,
+            vec![PathBuf::from("base.pem"), "dev.pem".into()]
+        );
+
+        let retry = merged.retry.unwrap();
+        assert_eq!(retry.max_retries, 3);
+        assert_eq!(retry.backoff_ms, 100);
+        assert_eq!(retry.statuses, vec![502, 503, 504]);
+    }
+
+    #[tokio::test]
+    async fn test_retries_are_not_timed() {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            for status in ["503 Service Unavailable", "200 OK"] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf).await.unwrap();
+                let res = format!(
+                    "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                stream.write_all(res.as_bytes()).await.unwrap();
+            }
+        });
+
+        let config: ClientConfig =
+            serde_yaml::from_str("retry: { max_retries: 1, backoff_ms: 500 }").unwrap();
+        let client = config.build().unwrap();
+        let req = client
+            .client
+            .get(format!("http://localhost:{}/", port))
+            .build()
+            .unwrap();
+        let start = Instant::now();
+        let (res, timings) = client.execute_timed(req).await.unwrap();
+
+        assert_eq!(res.status(), 200);
+        assert!(start.elapsed() >= Duration::from_millis(500));
+        assert!(timings.ttfb < Duration::from_millis(500));
+        assert!(timings.dns.is_some());
+    }
+
+    #[test]
+    fn test_clients_are_reused() {
+        let clients = HttpClients::default();
+        let overrides = ClientConfig {
+            timeout_ms: Some(1000),
+            ..Default::default()
+        };
+
+        clients.get(None).unwrap();
+        clients.get(None).unwrap();
+        clients.get(Some(&overrides)).unwrap();
+        assert_eq!(clients.cache.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_clients_without_retries() {
+        let retry: ClientConfig = serde_yaml::from_str("retry: { max_retries: 3 }").unwrap();
+        let clients = HttpClients::new(retry.clone());
+        assert!(clients.get(None).unwrap().retry.is_some());
+
+        let clients = clients.without_retries();
+        assert!(clients.get(None).unwrap().retry.is_none());
+        assert!(clients.get(Some(&retry)).unwrap().retry.is_none());
+    }
+}
@@ -1,9 +1,21 @@
+pub mod compose;
+pub mod vars;
 pub mod xdiff;
 pub mod xreq;
 
+pub use compose::Sources;
+pub use vars::Variables;
+
+use crate::client::ClientConfig;
+use compose::compose;
+use std::{collections::HashMap, path::Path};
+
 use anyhow::Result;
 use async_trait::async_trait;
-use serde::de::DeserializeOwned;
+use serde::{
+    de::{self, DeserializeOwned},
+    Deserialize, Deserializer,
+};
 use tokio::fs;
 
 #[async_trait]
@@ -13,17 +25,63 @@ where
 {
     /// Load config from yaml file
     async fn load_yaml(path: &str) -> Result<Self> {
+        Self::load_yaml_with(path, &Variables::default()).await
+    }
+    /// Load config from yaml file, resolving the `{{ var }}` placeholders with the variables
+    async fn load_yaml_with(path: &str, vars: &Variables) -> Result<Self> {
         let content = fs::read_to_string(path).await?;
-        Self::from_yaml(&content)
+        Self::from_value_with(serde_yaml::from_str(&content)?, Some(Path::new(path)), vars)
     }
     /// Load config from yaml string
     fn from_yaml(content: &str) -> Result<Self> {
-        let config: Self = serde_yaml::from_str(content)?;
+        Self::from_yaml_with(content, &Variables::default())
+    }
+    /// Load config from yaml string, resolving the `{{ var }}` placeholders with the variables
+    fn from_yaml_with(content: &str, vars: &Variables) -> Result<Self> {
+        Self::from_value_with(serde_yaml::from_str(content)?, None, vars)
+    }
+    /// Load config from the yaml of a file, with the files it includes relative to it
+    fn from_value_with(
+        value: serde_yaml::Value,
+        file: Option<&Path>,
+        vars: &Variables,
+    ) -> Result<Self> {
+        let (mut value, sources) = compose(value, file)?;
+        vars.interpolate(&mut value)?;
+        let mut config: Self = serde_yaml::from_value(value)?;
+        config.set_sources(sources);
+        if let Some(file) = file {
+            config.resolve_paths(file);
+        }
         config.validate()?;
         Ok(config)
     }
+    /// Keep the files the profiles are defined in, to locate them in errors
+    fn set_sources(&mut self, sources: Sources);
+    /// Make the relative paths of the profiles, e.g. of the schemas and the secret files,
+    /// relative to the file each profile is defined in, and those of the client to the config
+    fn resolve_paths(&mut self, file: &Path);
 }
 
 pub trait ValidateConfig {
     fn validate(&self) -> Result<()>;
 }
+
+/// Read the top-level `client:` block and the profiles of a config. The profiles are not
+/// `#[serde(flatten)]`, as it loses the yaml tags of the request bodies.
+fn deserialize_profiles<'de, D, P>(
+    deserializer: D,
+) -> Result<(ClientConfig, HashMap<String, P>), D::Error>
+where
+    D: Deserializer<'de>,
+    P: DeserializeOwned,
+{
+    let mut map = serde_yaml::Mapping::deserialize(deserializer)?;
+    let client = match map.remove("client") {
+        Some(client) => serde_yaml::from_value(client).map_err(de::Error::custom)?,
+        None => ClientConfig::default(),
+    };
+    let profiles =
+        serde_yaml::from_value(serde_yaml::Value::Mapping(map)).map_err(de::Error::custom)?;
+    Ok((client, profiles))
+}
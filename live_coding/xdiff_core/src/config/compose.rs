@@ -0,0 +1,419 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde_yaml::{Mapping, Value};
+
+/// The top-level keys of a config which are not profiles.
+const RESERVED: [&str; 5] = ["include", "defaults", "vars", "envs", "client"];
+
+/// The lists which are added to when merged, rather than replaced.
+const APPENDED: [&str; 3] = ["skip_headers", "skip_body", "tags"];
+
+/// The files the profiles of a config are defined in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sources {
+    profiles: HashMap<String, PathBuf>,
+    included: Vec<PathBuf>,
+}
+
+impl Sources {
+    /// The file the profile is defined in, if it is loaded from a file.
+    pub fn file(&self, profile: &str) -> Option<&Path> {
+        self.profiles.get(profile).map(PathBuf::as_path)
+    }
+
+    /// All the files included by the config, directly or not, e.g. to watch them for changes.
+    pub fn included(&self) -> &[PathBuf] {
+        &self.included
+    }
+
+    /// The profile name followed by its file, for error messages.
+    pub fn describe(&self, profile: &str) -> String {
+        match self.file(profile) {
+            Some(file) => format!("{} in {}", profile, file.display()),
+            None => profile.to_string(),
+        }
+    }
+}
+
+/// Build a single config out of the `include:`, `extends:` and `defaults:` of a config file:
+///
+/// ```yaml
+/// include: [common.yaml, teams/*.yaml]
+/// defaults:
+///   res: { skip_headers: [date] }
+/// .base:
+///   req1: { headers: { accept: application/json } }
+/// todo:
+///   extends: .base
+///   req1: { url: https://example.com/todos/1 }
+/// ```
+///
+/// The included files are relative to the including file, and its own profiles override theirs.
+/// The other files of a profile, e.g. its schemas, are relative to the file it is defined in.
+/// A profile is merged over the profile it extends and then over the defaults: mappings are
+/// merged by key, `skip_headers`, `skip_body` and `tags` are appended to and any other value,
+/// lists included, is replaced. Profiles whose name starts with `.` could only be extended and
+/// are left out of the config.
+pub fn compose(value: Value, file: Option<&Path>) -> Result<(Value, Sources)> {
+    let (mut config, sources) = with_includes(value, file, &mut vec![])?;
+
+    let names: Vec<String> = profile_names(&config).collect();
+    let mut resolved = Mapping::new();
+    for name in &names {
+        let profile = resolve_extends(&config, &sources, name, &mut vec![])?;
+        resolved.insert(name.as_str().into(), profile);
+    }
+
+    let defaults = config.remove("defaults");
+    for name in names {
+        let mut profile = resolved.remove(name.as_str()).unwrap();
+        if let Some(ref defaults) = defaults {
+            let mut merged = defaults.clone();
+            merge(&mut merged, profile);
+            profile = merged;
+        }
+        match name.starts_with('.') {
+            true => config.remove(name.as_str()),
+            false => config.insert(name.into(), profile),
+        };
+    }
+    Ok((Value::Mapping(config), sources))
+}
+
+fn profile_names(config: &Mapping) -> impl Iterator<Item = String> + '_ {
+    config
+        .keys()
+        .filter_map(Value::as_str)
+        .filter(|key| !RESERVED.contains(key))
+        .map(str::to_string)
+}
+
+/// Merge the included files under the config, recording the file of every profile.
+fn with_includes(
+    value: Value,
+    file: Option<&Path>,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(Mapping, Sources)> {
+    let mut config = match value {
+        Value::Mapping(config) => config,
+        Value::Null => Mapping::new(),
+        _ => return Err(anyhow!("config must be a mapping")),
+    };
+    let dir = file.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+
+    let mut merged = Mapping::new();
+    let mut sources = Sources::default();
+    for path in included_files(config.remove("include"), dir)? {
+        let canonical = path.canonicalize()?;
+        if stack.contains(&canonical) {
+            return Err(anyhow!("{} includes itself", path.display()));
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read included file {}", path.display()))?;
+        let value = serde_yaml::from_str(&content)
+            .with_context(|| format!("invalid yaml in {}", path.display()))?;
+
+        stack.push(canonical);
+        let (included, included_sources) = with_includes(value, Some(&path), stack)?;
+        stack.pop();
+        merge_config(&mut merged, included);
+        sources.profiles.extend(included_sources.profiles);
+        sources.included.push(path);
+        sources.included.extend(included_sources.included);
+    }
+
+    if let Some(file) = file {
+        for name in profile_names(&config) {
+            sources.profiles.insert(name, file.to_path_buf());
+        }
+    }
+    merge_config(&mut merged, config);
+    Ok((merged, sources))
+}
+
+/// The files of the `include:` entries, which could be globs.
+fn included_files(include: Option<Value>, dir: &Path) -> Result<Vec<PathBuf>> {
+    let patterns = match include {
+        None => vec![],
+        Some(Value::String(pattern)) => vec![pattern],
+        Some(Value::Sequence(patterns)) => patterns
+            .into_iter()
+            .map(|p| match p {
+                Value::String(p) => Ok(p),
+                p => Err(anyhow!("invalid include {:?}", p)),
+            })
+            .collect::<Result<_>>()?,
+        Some(include) => return Err(anyhow!("invalid include {:?}", include)),
+    };
+
+    let mut files = vec![];
+    for pattern in patterns {
+        let path = dir.join(&pattern);
+        let mut matched: Vec<PathBuf> = glob::glob(&path.to_string_lossy())
+            .with_context(|| format!("invalid include {}", pattern))?
+            .collect::<Result<_, _>>()?;
+        if matched.is_empty() {
+            return Err(anyhow!("included file {} not found", path.display()));
+        }
+        matched.sort();
+        files.extend(matched);
+    }
+    Ok(files)
+}
+
+/// Merge the settings of a config over another, and replace its profiles.
+fn merge_config(config: &mut Mapping, other: Mapping) {
+    for (key, value) in other {
+        let reserved = key.as_str().is_some_and(|key| RESERVED.contains(&key));
+        match config.get_mut(&key) {
+            Some(existing) if reserved => merge(existing, value),
+            _ => {
+                config.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The profile merged over the profiles it extends.
+fn resolve_extends(
+    config: &Mapping,
+    sources: &Sources,
+    name: &str,
+    stack: &mut Vec<String>,
+) -> Result<Value> {
+    let mut profile = config[name].clone();
+    let base = match profile.as_mapping_mut().and_then(|p| p.remove("extends")) {
+        None => return Ok(profile),
+        Some(Value::String(base)) => base,
+        Some(_) => {
+            return Err(anyhow!(
+                "extends of profile {} must be a profile name",
+                sources.describe(name)
+            ))
+        }
+    };
+    if stack.iter().any(|n| n == name) {
+        return Err(anyhow!("profile {} extends itself", sources.describe(name)));
+    }
+    if RESERVED.contains(&base.as_str()) || !config.contains_key(base.as_str()) {
+        return Err(anyhow!(
+            "profile {} extends unknown profile {}",
+            sources.describe(name),
+            base
+        ));
+    }
+
+    stack.push(name.to_string());
+    let mut merged = resolve_extends(config, sources, &base, stack)?;
+    stack.pop();
+    merge(&mut merged, profile);
+    Ok(merged)
+}
+
+/// Merge a value over another: mappings by key, the `APPENDED` lists are appended to,
+/// other values replaced.
+fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Mapping(base), Value::Mapping(value)) => {
+            for (k, v) in value {
+                let appended = k.as_str().is_some_and(|k| APPENDED.contains(&k));
+                match (base.get_mut(&k), v) {
+                    (Some(Value::Sequence(existing)), Value::Sequence(items)) if appended => {
+                        for item in items {
+                            if !existing.contains(&item) {
+                                existing.push(item);
+                            }
+                        }
+                    }
+                    (Some(existing), v) => merge(existing, v),
+                    (None, v) => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("teams")).unwrap();
+        fs::write(
+            dir.join("common.yaml"),
+            r#"
+vars: { host: example.com, scheme: http }
+defaults:
+  res: { skip_headers: [date] }
+.base:
+  req1: { headers: { accept: application/json, x-team: none } }
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("teams/todo.yaml"),
+            r#"
+todo:
+  extends: .base
+  req1: { url: "https://{{ host }}/todos/1", headers: { x-team: todo } }
+  res: { skip_headers: [etag] }
+"#,
+        )
+        .unwrap();
+        let file = dir.join("xdiff.yaml");
+        let config = r#"
+include: [common.yaml, teams/*.yaml]
+vars: { scheme: https }
+"#;
+        let (config, sources) =
+            compose(serde_yaml::from_str(config).unwrap(), Some(&file)).unwrap();
+
+        assert_eq!(
+            serde_yaml::to_string(&config).unwrap(),
+            r#"vars:
+  host: example.com
+  scheme: https
+todo:
+  res:
+    skip_headers:
+    - date
+    - etag
+  req1:
+    headers:
+      accept: application/json
+      x-team: todo
+    url: https://{{ host }}/todos/1
+"#
+        );
+        assert_eq!(
+            sources.file("todo"),
+            Some(dir.join("teams/todo.yaml").as_path())
+        );
+        assert_eq!(
+            sources.describe("todo"),
+            format!("todo in {}", dir.join("teams/todo.yaml").display())
+        );
+        assert_eq!(
+            sources.included(),
+            [dir.join("common.yaml"), dir.join("teams/todo.yaml")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_paths() {
+        use crate::{
+            config::{xdiff::DiffConfig, LoadConfig},
+            profile::{auth::Auth, auth::Secret, body::Body, xdiff::DiffTarget},
+        };
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("teams/schemas")).unwrap();
+        fs::write(dir.join("teams/schemas/todo.json"), "{}").unwrap();
+        fs::write(
+            dir.join("teams/todo.yaml"),
+            r#"
+todo:
+  req1:
+    url: https://example.com/todos/1
+    schema: schemas/todo.json
+    auth: { bearer: { file: secrets/token } }
+  req2: { snapshot: snapshots/todo.yaml }
+"#,
+        )
+        .unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        let absolute = dir.join("teams/schemas/todo.json");
+        let config = format!(
+            r#"
+include: teams/*.yaml
+client: {{ ca_certs: [certs/ca.pem] }}
+upload:
+  req1:
+    method: POST
+    url: https://example.com/upload
+    body: !multipart [{{ name: a, file: a.txt }}, {{ name: b, file: {} }}]
+  req2: {{ url: https://example.com/upload }}
+"#,
+            absolute.display()
+        );
+        fs::write(dir.join("xdiff.yaml"), config).unwrap();
+        let file = dir.join("xdiff.yaml");
+        let config = DiffConfig::load_yaml(file.to_str().unwrap()).await.unwrap();
+
+        assert_eq!(config.client.ca_certs, [dir.join("certs/ca.pem")]);
+        let todo = &config.profiles["todo"];
+        assert_eq!(
+            todo.req1.schema.as_ref().unwrap().path,
+            dir.join("teams/schemas/todo.json")
+        );
+        assert!(matches!(
+            todo.req1.auth,
+            Some(Auth::Bearer(Secret::File { ref file })) if *file == dir.join("teams/secrets/token")
+        ));
+        assert!(matches!(
+            todo.req2,
+            Some(DiffTarget::Snapshot { ref snapshot }) if *snapshot == dir.join("teams/snapshots/todo.yaml")
+        ));
+        let Some(Body::Multipart(ref parts)) = config.profiles["upload"].req1.body else {
+            panic!("upload should have a multipart body");
+        };
+        let files: Vec<_> = parts.iter().filter_map(|p| p.file.clone()).collect();
+        assert_eq!(files, [dir.join("a.txt"), absolute]);
+    }
+
+    #[test]
+    fn test_merge_lists() {
+        let config = r#"
+defaults:
+  res: { skip_headers: [date] }
+.base:
+  pre: [{ url: "https://example.com/login" }]
+  req1:
+    url: https://example.com/todos
+    params: { ids: [1, 2] }
+    expect: { status: [2xx] }
+  req2: { url: "https://example.com/v2/todos" }
+  tags: [api]
+todo:
+  extends: .base
+  pre: []
+  req1:
+    params: { ids: [3] }
+    expect: { status: [404] }
+  res: { skip_headers: [etag] }
+  tags: [todo]
+"#;
+        let (config, _) = compose(serde_yaml::from_str(config).unwrap(), None).unwrap();
+        let todo = &config["todo"];
+
+        let yaml = |value: &Value| serde_yaml::to_string(value).unwrap();
+        assert_eq!(yaml(&todo["pre"]), "[]\n");
+        assert_eq!(yaml(&todo["req1"]["params"]["ids"]), "- 3\n");
+        assert_eq!(yaml(&todo["req1"]["expect"]["status"]), "- 404\n");
+        assert_eq!(yaml(&todo["res"]["skip_headers"]), "- date\n- etag\n");
+        assert_eq!(yaml(&todo["tags"]), "- api\n- todo\n");
+    }
+
+    #[test]
+    fn test_compose_errors() {
+        let compose_str = |config: &str| compose(serde_yaml::from_str(config).unwrap(), None);
+
+        let err = compose_str("a: { extends: b }").unwrap_err();
+        assert_eq!(err.to_string(), "profile a extends unknown profile b");
+        let err = compose_str("a: { extends: b }\nb: { extends: a }").unwrap_err();
+        assert_eq!(err.to_string(), "profile a extends itself");
+        assert!(compose_str("include: missing.yaml").is_err());
+    }
+}
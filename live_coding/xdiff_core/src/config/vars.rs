@@ -0,0 +1,212 @@
+use crate::utils::template::render_template;
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Context, Result};
+use serde_yaml::Value;
+
+/// Resolve the `{{ var }}` placeholders of a config file.
+///
+/// A variable is looked up in the cli overrides first, then in the environment
+/// selected from the `envs:` block, then in the top-level `vars:` block, and
+/// finally in the process environment variables.
+///
+/// Names declared in any `capture:` block are left as is, they are resolved
+/// from the responses when the requests are sent.
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    /// The name of the environment in the `envs:` block.
+    pub env: Option<String>,
+    /// The values given from the cli.
+    pub overrides: HashMap<String, String>,
+}
+
+impl Variables {
+    pub fn new(env: Option<String>, overrides: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            env,
+            overrides: overrides.into_iter().collect(),
+        }
+    }
+
+    /// Take the `vars:` and `envs:` blocks out of the config and interpolate the remaining string values.
+    pub fn interpolate(&self, config: &mut Value) -> Result<()> {
+        let Value::Mapping(map) = config else {
+            return Ok(());
+        };
+        let vars = match map.remove("vars") {
+            Some(vars) => to_vars(&vars).context("invalid vars")?,
+            None => HashMap::new(),
+        };
+        let envs = map.remove("envs");
+        let env_vars = match self.env {
+            Some(ref name) => {
+                let env = envs
+                    .as_ref()
+                    .and_then(|envs| envs.get(name.as_str()))
+                    .ok_or_else(|| anyhow!("environment {} not found in envs", name))?;
+                to_vars(env).context(format!("invalid environment {}", name))?
+            }
+            None => HashMap::new(),
+        };
+
+        let lookup = |name: &str| {
+            self.overrides
+                .get(name)
+                .or_else(|| env_vars.get(name))
+                .or_else(|| vars.get(name))
+                .cloned()
+                .or_else(|| std::env::var(name).ok())
+        };
+        let mut captured = HashSet::new();
+        collect_captures(config, &mut captured);
+
+        let mut undefined = vec![];
+        interpolate_value(config, "", &lookup, &mut undefined);
+        undefined.retain(|(name, _)| !captured.contains(name));
+        let undefined: Vec<String> = undefined
+            .into_iter()
+            .map(|(name, path)| format!("{} (in {})", name, path))
+            .collect();
+
+        if !undefined.is_empty() {
+            return Err(anyhow!("undefined variables: {}", undefined.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+fn collect_captures(value: &Value, captured: &mut HashSet<String>) {
+    match value {
+        Value::Sequence(seq) => seq.iter().for_each(|v| collect_captures(v, captured)),
+        Value::Mapping(map) => {
+            for (k, v) in map {
+                if k.as_str() == Some("capture") {
+                    if let Some(names) = v.as_mapping() {
+                        captured.extend(names.keys().filter_map(scalar_to_string));
+                    }
+                }
+                collect_captures(v, captured);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn to_vars(value: &Value) -> Result<HashMap<String, String>> {
+    let map = value
+        .as_mapping()
+        .ok_or_else(|| anyhow!("variables must be a mapping"))?;
+    map.iter()
+        .map(|(k, v)| {
+            let key =
+                scalar_to_string(k).ok_or_else(|| anyhow!("invalid variable name {:?}", k))?;
+            let value = scalar_to_string(v)
+                .ok_or_else(|| anyhow!("variable {} must be a string, number or bool", key))?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn interpolate_value(
+    value: &mut Value,
+    path: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    undefined: &mut Vec<(String, String)>,
+) {
+    match value {
+        Value::String(s) => {
+            let (output, missing) = render_template(s, lookup);
+            for name in missing {
+                undefined.push((name, path.to_string()));
+            }
+            *s = output;
+        }
+        Value::Sequence(seq) => {
+            for (idx, v) in seq.iter_mut().enumerate() {
+                interpolate_value(v, &format!("{}[{}]", path, idx), lookup, undefined);
+            }
+        }
+        Value::Mapping(map) => {
+            for (k, v) in map.iter_mut() {
+                let key = scalar_to_string(k).unwrap_or_default();
+                let path = if path.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                interpolate_value(v, &path, lookup, undefined);
+            }
+        }
+        Value::Tagged(tagged) => interpolate_value(&mut tagged.value, path, lookup, undefined),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+vars:
+  host: localhost
+  port: 8080
+envs:
+  prod:
+    host: example.com
+todo:
+  pre:
+    - url: http://{{ host }}/login
+      capture:
+        token:
+          body: $.token
+  url: http://{{ host }}:{{ port }}/todos/{{ id }}
+  headers:
+    authorization: Bearer {{ token }}
+"#;
+
+    fn interpolate(vars: &Variables) -> Result<Value> {
+        let mut config: Value = serde_yaml::from_str(CONFIG)?;
+        vars.interpolate(&mut config)?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_interpolate_precedence() {
+        let vars = Variables::new(None, [("id".to_string(), "1".to_string())]);
+        let config = interpolate(&vars).unwrap();
+        assert_eq!(config["todo"]["url"], "http://localhost:8080/todos/1");
+        assert_eq!(
+            config["todo"]["headers"]["authorization"],
+            "Bearer {{ token }}"
+        );
+        assert!(config.get("vars").is_none() && config.get("envs").is_none());
+
+        let vars = Variables::new(
+            Some("prod".into()),
+            [
+                ("id".to_string(), "2".to_string()),
+                ("port".to_string(), "443".to_string()),
+            ],
+        );
+        let config = interpolate(&vars).unwrap();
+        assert_eq!(config["todo"]["url"], "http://example.com:443/todos/2");
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        let err = interpolate(&Variables::default()).unwrap_err();
+        assert_eq!(err.to_string(), "undefined variables: id (in todo.url)");
+
+        let vars = Variables::new(Some("staging".into()), []);
+        assert!(interpolate(&vars).is_err());
+    }
+}
@@ -1,34 +1,209 @@
-use super::{LoadConfig, ValidateConfig};
-use crate::profile::xdiff::DiffProfile;
-use std::collections::HashMap;
+use super::{deserialize_profiles, LoadConfig, Sources, ValidateConfig};
+use crate::{
+    cli::OverrideArgs,
+    client::{ClientConfig, HttpClients},
+    profile::{is_default, xdiff::DiffProfile},
+    snapshot::SnapshotStore,
+    utils::render::DiffOutcome,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Ok, Result};
-use serde::{Deserialize, Serialize};
+use glob::Pattern;
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio::sync::Semaphore;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 pub struct DiffConfig {
+    /// The http client settings shared by all the profiles.
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub client: ClientConfig,
+    /// The files the profiles are defined in, when loaded from a file.
+    #[serde(skip)]
+    pub sources: Sources,
     #[serde(flatten)]
     pub profiles: HashMap<String, DiffProfile>,
 }
 
 impl DiffConfig {
     pub fn new(profiles: HashMap<String, DiffProfile>) -> Self {
-        Self { profiles }
+        Self {
+            client: ClientConfig::default(),
+            sources: Sources::default(),
+            profiles,
+        }
+    }
+
+    /// The http clients built from the client settings, to be shared by all the requests.
+    pub fn clients(&self) -> HttpClients {
+        HttpClients::new(self.client.clone())
     }
 
     pub fn get_profile(&self, name: &str) -> Option<&DiffProfile> {
         self.profiles.get(name)
     }
+
+    /// Get the sorted names of the profiles matching the glob pattern,
+    /// and having any of the tags if tags are given.
+    pub fn select_profiles(&self, pattern: &str, tags: &[String]) -> Result<Vec<String>> {
+        let pattern = Pattern::new(pattern).context(format!("invalid pattern: {}", pattern))?;
+        let mut names: Vec<String> = self
+            .profiles
+            .iter()
+            .filter(|(name, profile)| {
+                pattern.matches(name)
+                    && (tags.is_empty() || profile.tags.iter().any(|t| tags.contains(t)))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Diff the named profiles with at most `concurrency` of them running at the same time.
+    /// The outcomes are returned in the order of the names, and of the targets of every profile.
+    pub async fn diff_profiles(
+        &self,
+        names: &[String],
+        args: &OverrideArgs,
+        concurrency: usize,
+    ) -> Result<Vec<DiffOutcome>> {
+        let clients = self.clients();
+        let outcomes = self
+            .run_profiles(names, concurrency, |_, profile| {
+                let (clients, args) = (clients.clone(), args.clone());
+                async move { profile.diff(&clients, args, None).await }
+            })
+            .await?;
+        Ok(outcomes
+            .into_iter()
+            .flat_map(|(name, result, elapsed)| DiffOutcome::from_targets(&name, result, elapsed))
+            .collect())
+    }
+
+    /// Diff the live response of req1 of the named profiles against their recorded snapshots.
+    pub async fn verify_profiles(
+        &self,
+        names: &[String],
+        args: &OverrideArgs,
+        concurrency: usize,
+        store: &SnapshotStore,
+    ) -> Result<Vec<DiffOutcome>> {
+        let clients = self.clients();
+        let outcomes = self
+            .run_profiles(names, concurrency, |name, profile| {
+                let (clients, args, store) = (clients.clone(), args.clone(), store.clone());
+                async move {
+                    let snapshot = store.load(&name).await?;
+                    profile.verify(&clients, args, &snapshot).await
+                }
+            })
+            .await?;
+        Ok(outcomes
+            .into_iter()
+            .map(|(name, result, elapsed)| DiffOutcome::new(name, result, elapsed))
+            .collect())
+    }
+
+    /// Record the response of req1 of the named profiles into the store.
+    /// Returns the written snapshot file of every profile.
+    pub async fn record_profiles(
+        &self,
+        names: &[String],
+        args: &OverrideArgs,
+        concurrency: usize,
+        store: &SnapshotStore,
+    ) -> Result<Vec<(String, Result<PathBuf>)>> {
+        let clients = self.clients();
+        let outcomes = self
+            .run_profiles(names, concurrency, |name, profile| {
+                let (clients, args, store) = (clients.clone(), args.clone(), store.clone());
+                async move {
+                    let res = profile.send_req1(&clients, &args).await?;
+                    store.save(&name, &res).await
+                }
+            })
+            .await?;
+        Ok(outcomes
+            .into_iter()
+            .map(|(name, result, _)| (name, result))
+            .collect())
+    }
+
+    async fn run_profiles<T, F, Fut>(
+        &self,
+        names: &[String],
+        concurrency: usize,
+        f: F,
+    ) -> Result<Vec<(String, Result<T>, Duration)>>
+    where
+        T: Send + 'static,
+        F: Fn(String, DiffProfile) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut tasks = vec![];
+        for name in names {
+            let profile = self
+                .get_profile(name)
+                .with_context(|| format!("profile {} not found", name))?
+                .clone();
+            let fut = f(name.clone(), profile);
+            let (name, semaphore) = (name.clone(), semaphore.clone());
+
+            tasks.push(tokio::spawn(async move {
+                let _permit = semaphore.acquire().await.unwrap();
+                let start = Instant::now();
+                let result = fut.await;
+                (name, result, start.elapsed())
+            }));
+        }
+
+        let mut outcomes = vec![];
+        for task in tasks {
+            outcomes.push(task.await?);
+        }
+        Ok(outcomes)
+    }
+}
+
+impl<'de> Deserialize<'de> for DiffConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (client, profiles) = deserialize_profiles(deserializer)?;
+        Result::Ok(Self {
+            client,
+            sources: Sources::default(),
+            profiles,
+        })
+    }
 }
 
-impl LoadConfig for DiffConfig {}
+impl LoadConfig for DiffConfig {
+    fn set_sources(&mut self, sources: Sources) {
+        self.sources = sources;
+    }
+
+    fn resolve_paths(&mut self, file: &Path) {
+        let dir = |file: &Path| file.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        self.client.resolve_paths(&dir(file));
+        for (name, profile) in &mut self.profiles {
+            profile.resolve_paths(&dir(self.sources.file(name).unwrap_or(file)));
+        }
+    }
+}
 
 impl ValidateConfig for DiffConfig {
     fn validate(&self) -> Result<()> {
         for (name, profile) in &self.profiles {
-            profile
-                .validate()
-                .context(format!("falied to validate profile: {}", name))?;
+            profile.validate().with_context(|| {
+                format!("failed to validate profile {}", self.sources.describe(name))
+            })?;
         }
         Ok(())
     }
@@ -1,19 +1,37 @@
-use super::{LoadConfig, ValidateConfig};
-use crate::profile::req::RequestProfile;
-use std::collections::HashMap;
+use super::{deserialize_profiles, LoadConfig, Sources, ValidateConfig};
+use crate::{
+    client::{ClientConfig, HttpClients},
+    profile::{is_default, req::RequestProfile},
+};
+use std::{collections::HashMap, path::Path};
 
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 pub struct RequestConfig {
+    /// The http client settings shared by all the profiles.
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub client: ClientConfig,
+    /// The files the profiles are defined in, when loaded from a file.
+    #[serde(skip)]
+    pub sources: Sources,
     #[serde(flatten)]
     pub profiles: HashMap<String, RequestProfile>,
 }
 
 impl RequestConfig {
     pub fn new(profiles: HashMap<String, RequestProfile>) -> Self {
-        Self { profiles }
+        Self {
+            client: ClientConfig::default(),
+            sources: Sources::default(),
+            profiles,
+        }
+    }
+
+    /// The http clients built from the client settings, to be shared by all the requests.
+    pub fn clients(&self) -> HttpClients {
+        HttpClients::new(self.client.clone())
     }
 
     pub fn get_profile(&self, name: &str) -> Option<&RequestProfile> {
@@ -21,14 +39,37 @@ impl RequestConfig {
     }
 }
 
-impl LoadConfig for RequestConfig {}
+impl<'de> Deserialize<'de> for RequestConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (client, profiles) = deserialize_profiles(deserializer)?;
+        Ok(Self {
+            client,
+            sources: Sources::default(),
+            profiles,
+        })
+    }
+}
+
+impl LoadConfig for RequestConfig {
+    fn set_sources(&mut self, sources: Sources) {
+        self.sources = sources;
+    }
+
+    fn resolve_paths(&mut self, file: &Path) {
+        let dir = |file: &Path| file.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        self.client.resolve_paths(&dir(file));
+        for (name, profile) in &mut self.profiles {
+            profile.resolve_paths(&dir(self.sources.file(name).unwrap_or(file)));
+        }
+    }
+}
 
 impl ValidateConfig for RequestConfig {
     fn validate(&self) -> Result<()> {
         for (name, profile) in &self.profiles {
-            profile
-                .validate()
-                .context(format!("falied to validate profile: {}", name))?;
+            profile.validate().with_context(|| {
+                format!("failed to validate profile {}", self.sources.describe(name))
+            })?;
         }
         Ok(())
     }
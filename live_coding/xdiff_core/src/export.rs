@@ -0,0 +1,800 @@
+use crate::cli::{ExportFormat, OverrideArgs};
+use crate::client::ClientConfig;
+use crate::profile::{
+    auth::{Auth, Secret},
+    body::{encode_form, Body, Part},
+    req::{Capture, RequestProfile},
+};
+use crate::utils::{json_path::JsonPath, template::render_template};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+};
+
+use anyhow::Result;
+use http::header;
+use serde_json::Value;
+use url::form_urlencoded;
+
+/// A request as it is sent, after the override args are applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedRequest {
+    pub method: String,
+    /// The url including the query string.
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Body>,
+    /// The auth of the request, exported with its secrets read from the env vars or the files
+    /// when the command is run.
+    pub auth: Option<Auth>,
+    /// The values captured from the response for the `{{ var }}` placeholders of the later
+    /// requests, exported as variables.
+    pub capture: BTreeMap<String, Capture>,
+    /// The client settings of the config merged with the ones of the request.
+    /// The retries are not exported.
+    pub client: ClientConfig,
+}
+
+impl ExportedRequest {
+    pub fn new(req: &RequestProfile, args: &OverrideArgs, client: &ClientConfig) -> Result<Self> {
+        let (headers, query, body) = req.generate(args)?;
+
+        // the url is not parsed, so that the `{{ var }}` placeholders of captured values are kept
+        let mut url = req.url.clone();
+        if let Value::Object(query) = query {
+            if !query.is_empty() {
+                let mut pairs = form_urlencoded::Serializer::new(String::new());
+                for (k, v) in &query {
+                    match v {
+                        Value::String(s) => pairs.append_pair(k, s),
+                        v => pairs.append_pair(k, &v.to_string()),
+                    };
+                }
+                url.push(if url.contains('?') { '&' } else { '?' });
+                url.push_str(&pairs.finish());
+            }
+        }
+
+        // the boundary of a multipart body is added to its content type by the tools
+        let multipart = matches!(body, Some(Body::Multipart(_)));
+        let headers = headers
+            .iter()
+            .filter(|(k, _)| !(multipart && *k == header::CONTENT_TYPE))
+            .map(|(k, v)| Ok((k.to_string(), v.to_str()?.to_string())))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            method: req.method.to_string(),
+            url,
+            headers,
+            body,
+            auth: req.auth.clone(),
+            capture: req.capture.clone(),
+            client: match req.client {
+                Some(ref overrides) => client.merge(overrides),
+                None => client.clone(),
+            },
+        })
+    }
+
+    pub fn to_curl(&self) -> String {
+        let captures =
+            self.shell_captures(|flags| self.curl(flags), " -s", " -s -o /dev/null -D -");
+        self.with_auth_note("#", self.curl("") + &captures)
+    }
+
+    pub fn to_httpie(&self) -> String {
+        let captures = self.shell_captures(|flags| self.httpie(flags), " --print=b", " --print=h");
+        self.with_auth_note("#", self.httpie("") + &captures)
+    }
+
+    /// The curl command, with the flags after `curl`.
+    fn curl(&self, flags: &str) -> String {
+        let mut parts = vec![format!(
+            "curl{} -X {} {}",
+            flags,
+            self.method,
+            quote(&self.url)
+        )];
+        let client = &self.client;
+        if client.insecure == Some(true) {
+            parts.push("-k".to_string());
+        }
+        if let Some(ms) = client.connect_timeout_ms {
+            parts.push(format!("--connect-timeout {}", seconds(ms)));
+        }
+        if let Some(ms) = client.timeout_ms {
+            parts.push(format!("-m {}", seconds(ms)));
+        }
+        if let Some(ref proxy) = client.proxy {
+            parts.push(format!("-x {}", quote(proxy)));
+        }
+        for cert in &client.ca_certs {
+            parts.push(format!("--cacert {}", quote(&cert.display().to_string())));
+        }
+        match client.max_redirects {
+            Some(0) => {}
+            Some(max) => parts.push(format!("-L --max-redirs {}", max)),
+            None => parts.push("-L".to_string()),
+        }
+        for (k, v) in &self.headers {
+            parts.push(format!("-H {}", quote(&format!("{}: {}", k, v))));
+        }
+        match self.auth {
+            Some(Auth::Basic {
+                ref username,
+                ref password,
+            }) => parts.push(format!("-u {}", basic_credentials(username, password))),
+            Some(Auth::Bearer(ref token)) => parts.push(format!(
+                "-H \"authorization: Bearer {}\"",
+                shell_secret(token)
+            )),
+            _ => {}
+        }
+        match self.body {
+            Some(Body::Multipart(ref form)) => {
+                for part in form {
+                    match part.file {
+                        Some(_) => parts.push(format!("-F {}", quote(&file_field(part, "=@")))),
+                        None => parts.push(format!(
+                            "--form-string {}",
+                            quote(&format!(
+                                "{}={}",
+                                part.name,
+                                part.value.as_deref().unwrap_or_default()
+                            ))
+                        )),
+                    }
+                }
+            }
+            Some(Body::Base64(ref data)) => {
+                parts.push("--data-binary @-".to_string());
+                parts[0] = format!("echo {} | base64 -d | {}", quote(data), parts[0]);
+            }
+            Some(ref body) => parts.push(format!("--data-raw {}", quote(&body_text(body)))),
+            None => {}
+        }
+        parts.join(" \\\n  ")
+    }
+
+    /// The HTTPie command, with the flags after `http`.
+    fn httpie(&self, flags: &str) -> String {
+        let mut parts = vec![format!("http{}", flags)];
+        match self.body {
+            Some(Body::Multipart(_)) => parts.push("--multipart".to_string()),
+            Some(Body::Base64(ref data)) => {
+                parts[0] = format!("echo {} | base64 -d | {}", quote(data), parts[0]);
+            }
+            Some(ref body) => parts.push(format!("--raw {}", quote(&body_text(body)))),
+            None => {}
+        }
+        let client = &self.client;
+        if client.insecure == Some(true) {
+            parts.push("--verify=no".to_string());
+        }
+        for cert in &client.ca_certs {
+            parts.push(format!("--verify={}", quote(&cert.display().to_string())));
+        }
+        if let Some(ms) = client.timeout_ms.or(client.connect_timeout_ms) {
+            parts.push(format!("--timeout={}", seconds(ms)));
+        }
+        if let Some(ref proxy) = client.proxy {
+            for scheme in ["http", "https"] {
+                parts.push(format!(
+                    "--proxy={}",
+                    quote(&format!("{}:{}", scheme, proxy))
+                ));
+            }
+        }
+        match client.max_redirects {
+            Some(0) => {}
+            Some(max) => parts.push(format!("--follow --max-redirects={}", max)),
+            None => parts.push("--follow".to_string()),
+        }
+        if let Some(Auth::Basic {
+            ref username,
+            ref password,
+        }) = self.auth
+        {
+            parts.push(format!("-a {}", basic_credentials(username, password)));
+        }
+        parts.push(format!("{} {}", self.method, quote(&self.url)));
+        for (k, v) in &self.headers {
+            parts.push(quote(&format!("{}:{}", k, v)));
+        }
+        if let Some(Auth::Bearer(ref token)) = self.auth {
+            parts.push(format!("\"authorization:Bearer {}\"", shell_secret(token)));
+        }
+        if let Some(Body::Multipart(ref form)) = self.body {
+            for part in form {
+                match part.file {
+                    Some(_) => parts.push(quote(&file_field(part, "@"))),
+                    None => parts.push(quote(&format!(
+                        "{}={}",
+                        part.name,
+                        part.value.as_deref().unwrap_or_default()
+                    ))),
+                }
+            }
+        }
+        parts.join(" \\\n  ")
+    }
+
+    /// The commented out lines sending the request again to capture the values of the later
+    /// requests into shell variables, the body through `jq` and the headers through `grep`.
+    fn shell_captures(
+        &self,
+        command: impl Fn(&str) -> String,
+        body_flags: &str,
+        header_flags: &str,
+    ) -> String {
+        let mut lines = String::new();
+        for (name, capture) in &self.capture {
+            let (flags, filter) = match capture {
+                Capture::Body(path) => {
+                    match path.parse::<JsonPath>().ok().and_then(|p| p.to_jq()) {
+                        Some(jq) => (body_flags, format!("jq -r {}", quote(&jq))),
+                        None => {
+                            write!(lines, "\n# {} is captured from {} of the body", name, path)
+                                .unwrap();
+                            continue;
+                        }
+                    }
+                }
+                Capture::Header(header) => (
+                    header_flags,
+                    format!(
+                        "grep -i {} | cut -d' ' -f2- | tr -d '\\r'",
+                        quote(&format!("^{}:", header))
+                    ),
+                ),
+            };
+            let capture = format!("{}=$({} | {})", variable(name), command(flags), filter);
+            for line in capture.lines() {
+                write!(lines, "\n# {}", line).unwrap();
+            }
+        }
+        lines
+    }
+
+    /// The names of the `{{ var }}` placeholders in the request.
+    fn placeholders(&self) -> Vec<String> {
+        let mut texts = vec![self.url.clone()];
+        texts.extend(self.headers.iter().map(|(_, v)| v.clone()));
+        match self.body {
+            Some(Body::Multipart(ref form)) => {
+                texts.extend(form.iter().filter_map(|part| part.value.clone()))
+            }
+            Some(Body::Base64(_)) | None => {}
+            Some(ref body) => texts.push(body_text(body)),
+        }
+        texts
+            .iter()
+            .flat_map(|text| render_template(text, |_| None).1)
+            .collect()
+    }
+
+    /// The statements to send the request with the client bound to `client`,
+    /// binding the response to `var`.
+    fn to_reqwest(&self, client: &str, var: &str) -> String {
+        let mut code = format!(
+            "    let {} = {}\n        .request(reqwest::Method::from_bytes(b\"{}\")?, {})\n",
+            var,
+            client,
+            self.method,
+            rust_str(&self.url)
+        );
+        for (k, v) in &self.headers {
+            writeln!(code, "        .header({:?}, {})", k, rust_str(v)).unwrap();
+        }
+        match self.auth {
+            Some(Auth::Basic {
+                ref username,
+                ref password,
+            }) => {
+                let password = password
+                    .as_ref()
+                    .map_or("None::<String>".to_string(), |password| {
+                        format!("Some({})", rust_secret(password))
+                    });
+                writeln!(code, "        .basic_auth({:?}, {})", username, password).unwrap();
+            }
+            Some(Auth::Bearer(ref token)) => {
+                writeln!(code, "        .bearer_auth({})", rust_secret(token)).unwrap()
+            }
+            _ => {}
+        }
+        match self.body {
+            Some(Body::Multipart(ref form)) => {
+                code.push_str("        // requires the `multipart` feature of reqwest\n");
+                code.push_str("        .multipart(\n            reqwest::multipart::Form::new()");
+                for part in form {
+                    match part.file {
+                        Some(ref file) => {
+                            let mut file_part = format!(
+                                "reqwest::multipart::Part::bytes(std::fs::read({:?})?)",
+                                file.display().to_string()
+                            );
+                            if let Some(filename) = part.filename() {
+                                write!(file_part, ".file_name({:?})", filename).unwrap();
+                            }
+                            if let Some(ref content_type) = part.content_type {
+                                write!(file_part, ".mime_str({:?})?", content_type).unwrap();
+                            }
+                            write!(
+                                code,
+                                "\n                .part({:?}, {})",
+                                part.name, file_part
+                            )
+                            .unwrap();
+                        }
+                        None => write!(
+                            code,
+                            "\n                .text({:?}, {})",
+                            part.name,
+                            rust_str(part.value.as_deref().unwrap_or_default())
+                        )
+                        .unwrap(),
+                    }
+                }
+                code.push_str(",\n        )\n");
+            }
+            Some(Body::Base64(ref data)) => {
+                let bytes = base64::decode(data).unwrap_or_default();
+                writeln!(code, "        .body(vec!{:?})", bytes).unwrap();
+            }
+            Some(ref body) => {
+                writeln!(code, "        .body({})", rust_str(&body_text(body))).unwrap()
+            }
+            None => {}
+        }
+        code.push_str("        .send()\n        .await?;\n");
+        let mut captured_paths = vec![];
+        for (name, capture) in &self.capture {
+            match capture {
+                Capture::Header(header) => writeln!(
+                    code,
+                    "    let {} = {}.headers().get({:?}).ok_or({:?})?.to_str()?.to_string();",
+                    variable(name),
+                    var,
+                    header,
+                    format!("header {} not found to capture {}", header, name)
+                )
+                .unwrap(),
+                Capture::Body(path) => captured_paths.push((name, path)),
+            }
+        }
+        writeln!(code, "    println!(\"{{:?}}\", {});", var).unwrap();
+        if captured_paths.is_empty() {
+            writeln!(code, "    println!(\"{{}}\", {}.text().await?);", var).unwrap();
+        } else {
+            writeln!(code, "    let {0}_text = {0}.text().await?;", var).unwrap();
+            writeln!(code, "    println!(\"{{}}\", {}_text);", var).unwrap();
+            writeln!(
+                code,
+                "    let {0}_json: serde_json::Value = serde_json::from_str(&{0}_text)?;",
+                var
+            )
+            .unwrap();
+        }
+        for (name, path) in captured_paths {
+            match path.parse::<JsonPath>().ok().and_then(|p| p.to_pointer()) {
+                Some(pointer) => writeln!(
+                    code,
+                    "    let {} = {}_json.pointer({:?}).map(|v| v.as_str().map_or_else(|| v.to_string(), String::from)).ok_or({:?})?;",
+                    variable(name),
+                    var,
+                    pointer,
+                    format!("{} not found to capture {}", path, name)
+                ),
+                None => writeln!(
+                    code,
+                    "    let {}: String = todo!({:?});",
+                    variable(name),
+                    format!("capture {} of the body", path)
+                ),
+            }
+            .unwrap();
+        }
+        self.with_auth_note("    //", code)
+    }
+
+    /// Put a comment over the exported request if its auth could not be exported: the
+    /// signatures and the OAuth2 tokens are computed when the request is sent.
+    fn with_auth_note(&self, comment: &str, code: String) -> String {
+        let scheme = match self.auth {
+            Some(Auth::OAuth2(_)) => "oauth2",
+            Some(Auth::Hmac(_)) => "hmac",
+            Some(Auth::Sigv4(_)) => "sigv4",
+            _ => return code,
+        };
+        format!(
+            "{} signed with {}, not reproducible\n{}",
+            comment, scheme, code
+        )
+    }
+}
+
+/// The expression building a reqwest client with the settings.
+fn reqwest_client(client: &ClientConfig) -> String {
+    if *client == ClientConfig::default() {
+        return "reqwest::Client::new()".to_string();
+    }
+    let mut code = "reqwest::Client::builder()".to_string();
+    let mut call = |line: String| write!(code, "\n        .{}", line).unwrap();
+    if client.insecure == Some(true) {
+        call("danger_accept_invalid_certs(true)".to_string());
+    }
+    if let Some(ms) = client.connect_timeout_ms {
+        call(format!(
+            "connect_timeout(std::time::Duration::from_millis({}))",
+            ms
+        ));
+    }
+    if let Some(ms) = client.timeout_ms {
+        call(format!("timeout(std::time::Duration::from_millis({}))", ms));
+    }
+    if let Some(ref proxy) = client.proxy {
+        call(format!("proxy(reqwest::Proxy::all({:?})?)", proxy));
+    }
+    for cert in &client.ca_certs {
+        call(format!(
+            "add_root_certificate(reqwest::Certificate::from_pem(&std::fs::read({:?})?)?)",
+            cert.display().to_string()
+        ));
+    }
+    match client.max_redirects {
+        Some(0) => call("redirect(reqwest::redirect::Policy::none())".to_string()),
+        Some(max) => call(format!(
+            "redirect(reqwest::redirect::Policy::limited({}))",
+            max
+        )),
+        None => {}
+    }
+    call("build()?".to_string());
+    code
+}
+
+/// Export the labeled requests in the order to send them, with the client settings
+/// of the config.
+pub fn export_requests(
+    reqs: &[(String, &RequestProfile)],
+    args: &OverrideArgs,
+    client: &ClientConfig,
+    format: ExportFormat,
+) -> Result<String> {
+    let mut exported = vec![];
+    let (mut captured, mut missing) = (HashSet::new(), vec![]);
+    for (label, req) in reqs {
+        let req = ExportedRequest::new(req, args, client)?;
+        for name in req.placeholders() {
+            if !captured.contains(&name) && !missing.contains(&name) {
+                missing.push(name);
+            }
+        }
+        captured.extend(req.capture.keys().cloned());
+        exported.push((label, req));
+    }
+
+    let mut output = String::new();
+    if !missing.is_empty() {
+        writeln!(
+            output,
+            "{} warning: not captured by any previous request: {}",
+            match format {
+                ExportFormat::Reqwest => "//",
+                _ => "#",
+            },
+            missing.join(", ")
+        )?;
+    }
+    match format {
+        ExportFormat::Curl | ExportFormat::Httpie => {
+            for (idx, (label, req)) in exported.iter().enumerate() {
+                if idx > 0 {
+                    output.push('\n');
+                }
+                let command = match format {
+                    ExportFormat::Curl => req.to_curl(),
+                    _ => req.to_httpie(),
+                };
+                writeln!(output, "# {}\n{}", label, command)?;
+            }
+        }
+        ExportFormat::Reqwest => {
+            output.push_str(
+                "#[tokio::main]\nasync fn main() -> Result<(), Box<dyn std::error::Error>> {\n",
+            );
+            writeln!(output, "    let client = {};", reqwest_client(client))?;
+            for (idx, (label, req)) in exported.iter().enumerate() {
+                writeln!(output, "\n    // {}", label)?;
+                // a request overriding the client settings gets a client of its own
+                let client = match req.client == *client {
+                    true => "client".to_string(),
+                    false => {
+                        let var = format!("client{}", idx + 1);
+                        writeln!(output, "    let {} = {};", var, reqwest_client(&req.client))?;
+                        var
+                    }
+                };
+                output.push_str(&req.to_reqwest(&client, &format!("res{}", idx + 1)));
+            }
+            output.push_str("\n    Ok(())\n}\n");
+        }
+    }
+    Ok(output)
+}
+
+/// The body of the textual encodings as it is sent.
+fn body_text(body: &Body) -> String {
+    match body {
+        Body::Json(value) => value.to_string(),
+        Body::Form(fields) => encode_form(fields),
+        Body::Text(text) => text.clone(),
+        Body::Multipart(_) | Body::Base64(_) => unreachable!("not a textual body"),
+    }
+}
+
+/// A file part in the form of curl (`name=@file;type=...`) or HTTPie (`name@file;type=...`).
+fn file_field(part: &Part, sep: &str) -> String {
+    let file = part.file.as_deref().map(|f| f.display().to_string());
+    let mut field = format!("{}{}{}", part.name, sep, file.unwrap_or_default());
+    if let Some(ref content_type) = part.content_type {
+        write!(field, ";type={}", content_type).unwrap();
+    }
+    // HTTPie always sends the name of the file
+    if let (Some(ref filename), "=@") = (&part.filename, sep) {
+        write!(field, ";filename={}", filename).unwrap();
+    }
+    field
+}
+
+/// The `user:password` of the basic auth, double quoted for the password to be read when
+/// the command is run.
+fn basic_credentials(username: &str, password: &Option<Secret>) -> String {
+    let password = password.as_ref().map(shell_secret).unwrap_or_default();
+    format!("\"{}:{}\"", escape_double_quoted(username), password)
+}
+
+/// A secret to put in double quotes, referencing its env var or its file rather than its value.
+fn shell_secret(secret: &Secret) -> String {
+    match secret {
+        Secret::Env { env } => format!("${}", env),
+        Secret::File { file } => format!("$(cat {})", quote(&file.display().to_string())),
+        Secret::Value(value) => escape_double_quoted(value),
+    }
+}
+
+/// The expression reading a secret in the reqwest snippet.
+fn rust_secret(secret: &Secret) -> String {
+    match secret {
+        Secret::Env { env } => format!("std::env::var({:?})?", env),
+        Secret::File { file } => format!(
+            "std::fs::read_to_string({:?})?.trim_end()",
+            file.display().to_string()
+        ),
+        Secret::Value(value) => format!("{:?}", value),
+    }
+}
+
+fn escape_double_quoted(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn seconds(ms: u64) -> String {
+    (ms as f64 / 1000.0).to_string()
+}
+
+/// Quote a word for the shell, with its `{{ var }}` placeholders as shell variables.
+fn quote(s: &str) -> String {
+    if !s.contains("{{") {
+        return shell_words::quote(s).into_owned();
+    }
+    let (word, _) = render_template(&escape_double_quoted(s), |name| {
+        Some(format!("${{{}}}", variable(name)))
+    });
+    format!("\"{}\"", word)
+}
+
+/// A string literal for the reqwest snippet, formatted with the captured variables if it has
+/// `{{ var }}` placeholders.
+fn rust_str(s: &str) -> String {
+    if !s.contains("{{") {
+        return format!("{:?}", s);
+    }
+    // the variables are marked to escape the braces of the text around them
+    let (marked, _) = render_template(s, |name| Some(format!("\0{}\0", variable(name))));
+    if let Some(var) = marked.strip_prefix('\0').and_then(|m| m.strip_suffix('\0')) {
+        if !var.contains('\0') {
+            return format!("{}.clone()", var);
+        }
+    }
+    let mut template = String::new();
+    for (idx, text) in marked.split('\0').enumerate() {
+        match idx % 2 {
+            0 => template.push_str(&text.replace('{', "{{").replace('}', "}}")),
+            _ => write!(template, "{{{}}}", text).unwrap(),
+        }
+    }
+    format!("format!({:?})", template)
+}
+
+/// The variable holding a captured value in the shell or in the reqwest snippet.
+fn variable(name: &str) -> String {
+    let mut var: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !var.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        var.insert(0, '_');
+    }
+    var
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::xreq::RequestConfig, LoadConfig};
+
+    fn exported() -> ExportedRequest {
+        let req: RequestProfile = serde_yaml::from_str(
+            r#"
+method: POST
+url: https://example.com/todos
+params:
+  a: 1
+headers:
+  x-token: it's
+body:
+  title: hello
+client:
+  insecure: true
+  max_redirects: 3
+"#,
+        )
+        .unwrap();
+        let args = OverrideArgs {
+            query: vec![("b".into(), "2".into())],
+            body: vec![("done".into(), "true".into())],
+            ..Default::default()
+        };
+        let client = ClientConfig {
+            timeout_ms: Some(2500),
+            ..Default::default()
+        };
+        ExportedRequest::new(&req, &args, &client).unwrap()
+    }
+
+    #[test]
+    fn test_export_curl() {
+        assert_eq!(
+            exported().to_curl(),
+            r#"curl -X POST 'https://example.com/todos?a=1&b=2' \
+  -k \
+  -m 2.5 \
+  -L --max-redirs 3 \
+  -H 'x-token: it'\''s' \
+  -H 'content-type: application/json' \
+  --data-raw '{"done":true,"title":"hello"}'"#
+        );
+    }
+
+    #[test]
+    fn test_export_httpie() {
+        assert_eq!(
+            exported().to_httpie(),
+            r#"http \
+  --raw '{"done":true,"title":"hello"}' \
+  --verify=no \
+  --timeout=2.5 \
+  --follow --max-redirects=3 \
+  POST 'https://example.com/todos?a=1&b=2' \
+  'x-token:it'\''s' \
+  content-type:application/json"#
+        );
+    }
+
+    #[test]
+    fn test_export_auth() {
+        let with_auth = |auth: &str| {
+            let mut req = exported();
+            req.headers.clear();
+            req.body = None;
+            let auth = serde_yaml::Deserializer::from_str(auth);
+            req.auth = Some(serde_yaml::with::singleton_map_recursive::deserialize(auth).unwrap());
+            req
+        };
+
+        let basic = with_auth("basic: { username: admin, password: { env: ADMIN_PASSWORD } }");
+        assert!(basic.to_curl().contains("\n  -u \"admin:$ADMIN_PASSWORD\""));
+        assert!(basic
+            .to_httpie()
+            .contains("\n  -a \"admin:$ADMIN_PASSWORD\""));
+        assert!(basic
+            .to_reqwest("client", "res")
+            .contains(".basic_auth(\"admin\", Some(std::env::var(\"ADMIN_PASSWORD\")?))\n"));
+
+        let bearer = with_auth("bearer: { file: secrets/token }");
+        assert!(bearer
+            .to_curl()
+            .ends_with("\n  -H \"authorization: Bearer $(cat secrets/token)\""));
+        assert!(bearer
+            .to_httpie()
+            .ends_with("\n  \"authorization:Bearer $(cat secrets/token)\""));
+        assert!(bearer
+            .to_reqwest("client", "res")
+            .contains(".bearer_auth(std::fs::read_to_string(\"secrets/token\")?.trim_end())\n"));
+
+        let hmac = with_auth("hmac: { key_id: ci, secret: { env: HMAC_SECRET } }");
+        assert!(hmac
+            .to_curl()
+            .starts_with("# signed with hmac, not reproducible\ncurl -X POST"));
+        assert!(!hmac.to_curl().contains("HMAC_SECRET"));
+        assert!(hmac
+            .to_reqwest("client", "res")
+            .starts_with("    // signed with hmac, not reproducible\n    let res = client"));
+    }
+
+    #[test]
+    fn test_export_chain() {
+        let config = RequestConfig::from_yaml(include_str!("../fixtures/export.yaml")).unwrap();
+        let mut reqs: Vec<(String, &RequestProfile)> = config.profiles["upload"]
+            .chain()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, req)| (format!("request {}", idx + 1), req))
+            .collect();
+        let export = |reqs: &[(String, &RequestProfile)], format| {
+            export_requests(reqs, &OverrideArgs::default(), &config.client, format).unwrap()
+        };
+
+        let output = export(&reqs, ExportFormat::Curl);
+        assert!(output
+            .contains("\n#   --data-raw '{\"user\":\"alice\"}' | jq -r .token)\n\n# request 2\n"));
+        assert!(output.contains("\n# token=$(curl -s -X POST https://example.com/login \\\n"));
+        assert!(output.contains(
+            "\n# request_id=$(curl -s -o /dev/null -D - -X POST https://example.com/login \\\n"
+        ));
+        assert!(output.contains("\n  -H \"authorization: Bearer ${token}\" \\\n"));
+        assert!(output.contains("\n  -H \"x-request-id: ${request_id}\" \\\n"));
+
+        let output = export(&reqs, ExportFormat::Httpie);
+        assert!(output.contains("\n# token=$(http --print=b \\\n"));
+        assert!(output.contains("\n  \"authorization:Bearer ${token}\" \\\n"));
+
+        // without the login, nothing captures the variables
+        reqs.remove(0);
+        let output = export(&reqs, ExportFormat::Curl);
+        assert!(output.starts_with(
+            "# warning: not captured by any previous request: token, request_id\n# request 2\n"
+        ));
+    }
+
+    #[test]
+    fn test_export_reqwest() {
+        // the snippet is included by an example, so that it is compiled along with the crate
+        let config = RequestConfig::from_yaml(include_str!("../fixtures/export.yaml")).unwrap();
+        let reqs: Vec<(String, &RequestProfile)> = config.profiles["upload"]
+            .chain()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, req)| (format!("request {}", idx + 1), req))
+            .collect();
+        let output = export_requests(
+            &reqs,
+            &OverrideArgs::default(),
+            &config.client,
+            ExportFormat::Reqwest,
+        )
+        .unwrap();
+
+        assert_eq!(output, include_str!("../fixtures/export_reqwest.rs.in"));
+    }
+}
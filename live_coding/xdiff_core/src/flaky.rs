@@ -0,0 +1,478 @@
+use crate::{
+    cli::OverrideArgs,
+    client::HttpClients,
+    profile::{
+        res::{ResponseExt, ResponseProfile},
+        xdiff::{DiffProfile, DiffTarget},
+    },
+    utils::json_diff::diff_json,
+};
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde_yaml::{Mapping, Value};
+
+/// The headers and body paths which vary between the responses of identical requests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Flakiness {
+    pub headers: BTreeSet<String>,
+    /// Json paths with the array indices generalized, e.g. `$.items[*].updated_at`.
+    pub body: BTreeSet<String>,
+    /// Whether a body which isn't json varies, which could be masked with a transform.
+    pub text_body: bool,
+}
+
+impl Flakiness {
+    /// Find what varies among the responses, apart from what the profile skips already.
+    pub fn detect(responses: &[ResponseExt], profile: &ResponseProfile) -> Result<Self> {
+        let mut flakiness = Self::default();
+        let Some((first, rest)) = responses.split_first() else {
+            return Ok(flakiness);
+        };
+
+        let names: BTreeSet<String> = responses
+            .iter()
+            .flat_map(|res| res.get_header_keys())
+            .filter(|name| !profile.skip_headers.contains(name))
+            .collect();
+        for name in names {
+            let values = |res: &ResponseExt| -> Vec<Vec<u8>> {
+                res.headers
+                    .get_all(name.as_str())
+                    .iter()
+                    .map(|v| v.as_bytes().to_vec())
+                    .collect()
+            };
+            if rest.iter().any(|res| values(res) != values(first)) {
+                flakiness.headers.insert(name);
+            }
+        }
+
+        let index = Regex::new(r"\[\d+\]").unwrap();
+        let parts = first.get_parts(profile)?;
+        for res in rest {
+            let other = res.get_parts(profile)?;
+            match (&parts.json, &other.json) {
+                (Some(json1), Some(json2)) => {
+                    for change in diff_json(json1, json2) {
+                        let path = index.replace_all(change.path(), "[*]").into_owned();
+                        flakiness.body.insert(path);
+                    }
+                }
+                _ => flakiness.text_body |= parts.body != other.body,
+            }
+        }
+        Ok(flakiness)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty() && self.body.is_empty() && !self.text_body
+    }
+
+    pub fn merge(&mut self, other: Flakiness) {
+        self.headers.extend(other.headers);
+        self.body.extend(other.body);
+        self.text_body |= other.text_body;
+    }
+}
+
+/// Send req1 and every target request of the profile `times` times, after its pre-requests,
+/// and find what varies between the responses of each of them.
+pub async fn detect_flakiness(
+    profile: &DiffProfile,
+    clients: &HttpClients,
+    args: &OverrideArgs,
+    times: usize,
+) -> Result<Flakiness> {
+    let captures = profile.send_pre(clients, args).await?;
+    let mut reqs = vec![("req1", &profile.req1)];
+    for (name, target) in profile.targets() {
+        if let DiffTarget::Request(req) = target {
+            reqs.push((name, &**req));
+        }
+    }
+
+    let mut flakiness = Flakiness::default();
+    for (name, req) in reqs {
+        let mut responses = vec![];
+        for _ in 0..times.max(2) {
+            let res = req
+                .send_with(clients, args, &mut captures.clone())
+                .await
+                .with_context(|| format!("failed to send {}", name))?;
+            responses.push(res);
+        }
+        flakiness.merge(Flakiness::detect(&responses, &profile.res)?);
+    }
+    Ok(flakiness)
+}
+
+/// Add the headers and json paths to the skips of the profile in the yaml of a config,
+/// keeping the entries which are there already.
+///
+/// Only the `res.skip_headers` and `res.skip_body` lines of the profile are changed, so the
+/// comments and layout of the rest of the file are kept. A profile whose `res` is written in a
+/// way which couldn't be edited line by line, e.g. `res: { skip_body: [] }`, is left to the user
+/// with the yaml to add in the error.
+pub fn add_skips(
+    content: &str,
+    profile: &str,
+    headers: &[String],
+    body: &[String],
+) -> Result<String> {
+    let config: Value = serde_yaml::from_str(content)?;
+    let mut expected = config.clone();
+    let res = expected
+        .get_mut(profile)
+        .and_then(Value::as_mapping_mut)
+        .ok_or_else(|| anyhow!("Profile {} not found", profile))?
+        .entry("res".into())
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    let res = res
+        .as_mapping_mut()
+        .ok_or_else(|| anyhow!("res of profile {} is not a mapping", profile))?;
+
+    let mut added = vec![];
+    for (key, items) in [("skip_headers", headers), ("skip_body", body)] {
+        if items.is_empty() {
+            continue;
+        }
+        let list = res
+            .entry(key.into())
+            .or_insert_with(|| Value::Sequence(vec![]))
+            .as_sequence_mut()
+            .ok_or_else(|| anyhow!("{} of profile {} is not a list", key, profile))?;
+        let mut new = vec![];
+        for item in items {
+            let item = Value::String(item.clone());
+            if !list.contains(&item) {
+                list.push(item.clone());
+                new.push(item);
+            }
+        }
+        if !new.is_empty() {
+            added.push((key, new));
+        }
+    }
+    if added.is_empty() {
+        return Ok(content.to_string());
+    }
+
+    let edited = YamlLines::new(content)
+        .add_skips(profile, &added)
+        .map(YamlLines::into_string)
+        .filter(|edited| serde_yaml::from_str::<Value>(edited).ok().as_ref() == Some(&expected));
+    edited.ok_or_else(|| {
+        let res: Mapping = added
+            .into_iter()
+            .map(|(key, items)| (key.into(), items.into()))
+            .collect();
+        let snippet = serde_yaml::to_string(&Mapping::from_iter([("res".into(), res.into())]))
+            .unwrap_or_default();
+        anyhow!(
+            "could not edit profile {} in place, add these skips to it by hand:\n{}",
+            profile,
+            snippet
+        )
+    })
+}
+
+/// The lines of a yaml file, edited without reformatting the rest of the file.
+struct YamlLines {
+    lines: Vec<String>,
+    newline: &'static str,
+    trailing: bool,
+}
+
+impl YamlLines {
+    fn new(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(str::to_string).collect(),
+            newline: if content.contains("\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            },
+            trailing: content.ends_with('\n'),
+        }
+    }
+
+    fn into_string(self) -> String {
+        let mut content = self.lines.join(self.newline);
+        if self.trailing {
+            content.push_str(self.newline);
+        }
+        content
+    }
+
+    /// Append the items to the skip lists under `res:` of the top-level profile.
+    fn add_skips(mut self, profile: &str, added: &[(&str, Vec<Value>)]) -> Option<Self> {
+        let start = self.find_key(0, self.lines.len(), 0, profile)?;
+        let end = self.block_end(start, 0);
+        let indent = self.child_indent(start, end)?;
+        let res = match self.find_key(start + 1, end, indent, "res") {
+            Some(res) => res,
+            None => {
+                self.lines
+                    .insert(end, format!("{}res:", " ".repeat(indent)));
+                end
+            }
+        };
+        let mut end = self.block_end(res, indent);
+        // a new `res:` is indented by the step the profile is indented by
+        let indent = self.child_indent(res, end).unwrap_or(indent * 2);
+
+        for (key, items) in added {
+            let Some(line) = self.find_key(res + 1, end, indent, key) else {
+                let mut lines = vec![format!("{}{}:", " ".repeat(indent), key)];
+                lines.extend(items.iter().map(|item| block_item(indent, item)));
+                let count = lines.len();
+                self.lines.splice(end..end, lines);
+                end += count;
+                continue;
+            };
+            let text = self.lines[line].clone();
+            let (_, value) = split_key(&text[indent..])?;
+            let value = value.trim();
+            if value.starts_with('[') {
+                // a flow list on the line of the key, e.g. `skip_headers: [date]`
+                let open = offset_in(&text, value) + 1;
+                let close = offset_in(&text, value) + value.rfind(']')?;
+                let mut inner = text[open..close].trim_end().to_string();
+                for item in items {
+                    if !inner.trim().is_empty() {
+                        inner.push_str(", ");
+                    }
+                    inner.push_str(&serde_json::to_string(item.as_str()?).ok()?);
+                }
+                self.lines[line] = format!("{}{}{}", &text[..open], inner, &text[close..]);
+            } else if value.is_empty() || value.starts_with('#') {
+                // a block list under the key, which might be indented as the key is
+                let mut last = line;
+                let mut item_indent = indent;
+                for idx in line + 1..end {
+                    match indent_of(&self.lines[idx]) {
+                        None => continue,
+                        Some(n) if n > indent || self.lines[idx][n..].starts_with("- ") => {
+                            if last == line {
+                                item_indent = n;
+                            }
+                            last = idx;
+                        }
+                        Some(_) => break,
+                    }
+                }
+                let lines: Vec<String> = items
+                    .iter()
+                    .map(|item| block_item(item_indent, item))
+                    .collect();
+                end += lines.len();
+                self.lines.splice(last + 1..last + 1, lines);
+            } else {
+                return None;
+            }
+        }
+        Some(self)
+    }
+
+    /// The line of the `key:` at the indent in the range, which has no inline flow value.
+    fn find_key(&self, from: usize, to: usize, indent: usize, key: &str) -> Option<usize> {
+        (from..to).find(|&idx| {
+            let line = &self.lines[idx];
+            indent_of(line) == Some(indent)
+                && split_key(&line[indent..]).map(|(found, _)| found) == Some(key)
+        })
+    }
+
+    /// The line after the last line of the block which starts at the line.
+    fn block_end(&self, start: usize, indent: usize) -> usize {
+        let mut end = start + 1;
+        for idx in start + 1..self.lines.len() {
+            match indent_of(&self.lines[idx]) {
+                None => continue,
+                Some(n) if n > indent => end = idx + 1,
+                Some(_) => break,
+            }
+        }
+        end
+    }
+
+    fn child_indent(&self, start: usize, end: usize) -> Option<usize> {
+        self.lines[start + 1..end]
+            .iter()
+            .find_map(|line| indent_of(line))
+    }
+}
+
+/// The indent of a line, or none for a blank or comment line.
+fn indent_of(line: &str) -> Option<usize> {
+    let trimmed = line.trim_start_matches(' ');
+    match trimmed.trim().is_empty() || trimmed.starts_with('#') {
+        true => None,
+        false => Some(line.len() - trimmed.len()),
+    }
+}
+
+/// The key of a `key: value` line, unquoting quoted keys, and the rest of the line.
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let (key, rest) = match line.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let close = line[1..].find(quote)? + 1;
+            (&line[1..close], &line[close + 1..])
+        }
+        _ => line.split_at(line.find(':')?),
+    };
+    let rest = rest.strip_prefix(':')?;
+    (rest.is_empty() || rest.starts_with(' ')).then_some((key, rest))
+}
+
+/// The offset of a slice of a line in the line.
+fn offset_in(line: &str, part: &str) -> usize {
+    part.as_ptr() as usize - line.as_ptr() as usize
+}
+
+fn block_item(indent: usize, item: &Value) -> String {
+    let item = serde_yaml::to_string(item).unwrap_or_default();
+    format!("{}- {}", " ".repeat(indent), item.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::{HeaderMap, StatusCode, Version};
+
+    fn response(id: &str, body: &str) -> ResponseExt {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().unwrap());
+        headers.insert("x-request-id", id.parse().unwrap());
+        headers.insert("date", id.parse().unwrap());
+        ResponseExt {
+            version: Version::HTTP_11,
+            status: StatusCode::OK,
+            headers,
+            body: body.to_string(),
+            binary: false,
+            metrics: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_detect_flakiness() {
+        let responses = [
+            response(
+                "1",
+                r#"{"id": 1, "items": [{"ts": 1}, {"ts": 2}], "meta": {"ts": 1}}"#,
+            ),
+            response(
+                "2",
+                r#"{"id": 1, "items": [{"ts": 3}, {"ts": 4}], "meta": {"ts": 1}}"#,
+            ),
+            response(
+                "3",
+                r#"{"id": 1, "items": [{"ts": 1}, {"ts": 2}], "meta": {"ts": 2}}"#,
+            ),
+        ];
+        let profile = ResponseProfile::new(vec!["date".into()], vec![]);
+        let flakiness = Flakiness::detect(&responses, &profile).unwrap();
+
+        assert_eq!(
+            flakiness.headers.into_iter().collect::<Vec<_>>(),
+            vec!["x-request-id"]
+        );
+        assert_eq!(
+            flakiness.body.into_iter().collect::<Vec<_>>(),
+            vec!["$.items[*].ts", "$.meta.ts"]
+        );
+        assert!(!flakiness.text_body);
+    }
+
+    #[test]
+    fn test_add_skips() {
+        let content = r#"
+todo:
+  req1: { url: "https://example.com/todos/1" }
+  req2: { url: "https://example.com/todos/2" }
+  res:
+    skip_headers: [date]
+posts:
+  req1: { url: "https://example.com/posts/1" }
+  req2: { url: "https://example.com/posts/2" }
+"#;
+        let headers = vec!["date".to_string(), "x-request-id".to_string()];
+        let body = vec!["$.meta.ts".to_string()];
+        let updated = add_skips(content, "todo", &headers, &body).unwrap();
+        let updated = add_skips(&updated, "posts", &[], &body).unwrap();
+        let config: Value = serde_yaml::from_str(&updated).unwrap();
+
+        let skips = |profile: &str, key: &str| serde_yaml::to_string(&config[profile]["res"][key]);
+        assert_eq!(
+            skips("todo", "skip_headers").unwrap(),
+            "- date\n- x-request-id\n"
+        );
+        assert_eq!(skips("todo", "skip_body").unwrap(), "- $.meta.ts\n");
+        assert_eq!(skips("posts", "skip_body").unwrap(), "- $.meta.ts\n");
+        assert!(add_skips(content, "missing", &headers, &body).is_err());
+    }
+
+    #[test]
+    fn test_add_skips_keeps_the_file() {
+        let content = r#"# todos
+todo:
+  # the first todo
+  req1: { url: "https://example.com/todos/1" }
+  req2: { url: "https://example.com/todos/2" }
+  res:
+    skip_headers:  # always differ
+    - date
+
+    skip_body: [$.id] # ids
+posts:
+    req1:
+        url: https://example.com/posts/1
+    req2:
+        url: https://example.com/posts/2
+
+# the end
+"#;
+        let headers = vec!["date".to_string(), "x-request-id".to_string()];
+        let body = vec!["$.items[*].ts".to_string()];
+        let updated = add_skips(content, "todo", &headers, &body).unwrap();
+        let updated = add_skips(&updated, "posts", &[], &body).unwrap();
+        assert_eq!(
+            updated,
+            r#"# todos
+todo:
+  # the first todo
+  req1: { url: "https://example.com/todos/1" }
+  req2: { url: "https://example.com/todos/2" }
+  res:
+    skip_headers:  # always differ
+    - date
+    - x-request-id
+
+    skip_body: [$.id, "$.items[*].ts"] # ids
+posts:
+    req1:
+        url: https://example.com/posts/1
+    req2:
+        url: https://example.com/posts/2
+    res:
+        skip_body:
+        - $.items[*].ts
+
+# the end
+"#
+        );
+        assert_eq!(
+            add_skips(&updated, "todo", &headers, &body).unwrap(),
+            updated
+        );
+
+        let content = "todo: { req1: { url: https://a.com }, req2: { url: https://b.com } }\n";
+        let err = add_skips(content, "todo", &headers, &[]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("res:\n  skip_headers:\n  - date\n"));
+    }
+}
@@ -0,0 +1,134 @@
+pub mod curl;
+pub mod har;
+pub mod openapi;
+
+use crate::cli::{ImportArgs, ImportSource};
+use crate::profile::{body::Body, req::RequestProfile};
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+use http::header::{self, HeaderMap, HeaderValue};
+use serde_json::{Map, Value};
+use tokio::io::AsyncReadExt;
+use url::{form_urlencoded, Url};
+
+/// Import the request profiles from the input of the args, keyed by profile name.
+pub async fn import_profiles(args: &ImportArgs) -> Result<BTreeMap<String, RequestProfile>> {
+    let content = read_input(&args.input).await?;
+    let imported = match args.source {
+        ImportSource::Curl => {
+            let req = curl::parse_curl(&content)?;
+            let name = match args.name {
+                Some(ref name) => name.clone(),
+                None => profile_name(req.method.as_str(), req.parse_url()?.path()),
+            };
+            vec![(name, req)]
+        }
+        ImportSource::Har => har::parse_har(&content)?,
+        ImportSource::Openapi => openapi::parse_openapi(&content, args.server.as_deref())?,
+    };
+
+    let mut profiles = BTreeMap::new();
+    for (name, req) in imported {
+        let mut unique = name.clone();
+        let mut n = 1;
+        while profiles.contains_key(&unique) {
+            n += 1;
+            unique = format!("{}-{}", name, n);
+        }
+        profiles.insert(unique, req);
+    }
+    Ok(profiles)
+}
+
+/// A copy of the request sent to another origin, e.g. `http://localhost:8080`.
+/// Only the origin of the url is replaced, so that its `{{ var }}` placeholders are kept.
+pub fn with_origin(req: &RequestProfile, origin: &str) -> Result<RequestProfile> {
+    let origin = Url::parse(origin).with_context(|| format!("invalid origin {}", origin))?;
+    if !origin.has_host() {
+        return Err(anyhow!("invalid origin {}", origin));
+    }
+    let (_, rest) = req
+        .url
+        .split_once("://")
+        .ok_or_else(|| anyhow!("invalid url {}", req.url))?;
+    let path = rest.find(['/', '?', '#']).map_or("", |idx| &rest[idx..]);
+    let mut req = req.clone();
+    req.url = format!(
+        "{}{}",
+        origin.origin().ascii_serialization(),
+        match path {
+            "" => "/",
+            path => path,
+        }
+    );
+    Ok(req)
+}
+
+async fn read_input(input: &str) -> Result<String> {
+    let mut content = String::new();
+    if input == "-" {
+        tokio::io::stdin().read_to_string(&mut content).await?;
+    } else {
+        content = tokio::fs::read_to_string(input)
+            .await
+            .with_context(|| format!("failed to read {}", input))?;
+    }
+    Ok(content)
+}
+
+/// A profile name from the method and path, e.g. `get-todos-1` for `GET /todos/1`.
+pub fn profile_name(method: &str, path: &str) -> String {
+    let mut name = method.to_lowercase();
+    for part in path.split(|c: char| !c.is_ascii_alphanumeric()) {
+        if !part.is_empty() {
+            name.push('-');
+            name.push_str(&part.to_lowercase());
+        }
+    }
+    name
+}
+
+/// Move the query string of the url into params, unless a param is repeated.
+fn take_query(url: &mut Url) -> Option<Value> {
+    let params = parse_form(url.query()?)?;
+    url.set_query(None);
+    (!params.is_empty()).then_some(Value::Object(params))
+}
+
+fn parse_form(text: &str) -> Option<Map<String, Value>> {
+    form_fields(form_urlencoded::parse(text.as_bytes()).into_owned())
+}
+
+/// The fields of a form, or none if a field is repeated as a mapping couldn't keep both.
+fn form_fields(pairs: impl IntoIterator<Item = (String, String)>) -> Option<Map<String, Value>> {
+    let mut fields = Map::new();
+    for (k, v) in pairs {
+        if fields.insert(k, Value::String(v)).is_some() {
+            return None;
+        }
+    }
+    Some(fields)
+}
+
+/// Parse a raw body by its content type, which defaults to a form as curl does.
+fn parse_body(headers: &mut HeaderMap, text: &str) -> Result<Body> {
+    if !headers.contains_key(header::CONTENT_TYPE) {
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+    }
+    let content_type = crate::profile::req::get_content_type(headers);
+    match content_type.as_deref() {
+        Some("application/json") => Ok(Body::Json(
+            serde_json::from_str(text).context("invalid json body")?,
+        )),
+        // a form with a repeated field is kept as it is sent
+        Some("application/x-www-form-urlencoded") => Ok(parse_form(text)
+            .map(Body::Form)
+            .unwrap_or_else(|| Body::Text(text.to_string()))),
+        Some("multipart/form-data") => Err(anyhow!("raw multipart bodies are not supported")),
+        _ => Ok(Body::Text(text.to_string())),
+    }
+}
@@ -0,0 +1,394 @@
+use super::{parse_body, take_query};
+use crate::{
+    client::ClientConfig,
+    profile::{
+        body::{Body, Part},
+        req::RequestProfile,
+    },
+};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use reqwest::Method;
+use url::{form_urlencoded, Url};
+
+/// Options of curl which are accepted and make no difference to the request.
+const IGNORED_FLAGS: &[&str] = &[
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-L",
+    "--location",
+    "-i",
+    "--include",
+    "-v",
+    "--verbose",
+    "-f",
+    "--fail",
+    "--compressed",
+];
+
+/// Options of curl which take a value and make no difference to the request.
+const IGNORED_OPTIONS: &[&str] = &["-o", "--output", "-w", "--write-out"];
+
+/// Options of curl which could be attached to their value, e.g. `-XPOST`.
+const SHORT_OPTIONS: &[&str] = &[
+    "-X", "-H", "-d", "-F", "-u", "-A", "-b", "-e", "-m", "-o", "-w",
+];
+
+/// Parse a curl command line into a request profile.
+///
+/// Supports `-X`, `-H`, `-d` and its `--data-*` variants, `--data-urlencode`, `--json`, `-F`, `-G`,
+/// `-u`, `-A`, `-b`, `-e`, and `-k` / `-m` / `--connect-timeout` for the client settings.
+/// Short flags could be combined as in `-sSL`, and data given as `@file` is read from the file.
+pub fn parse_curl(command: &str) -> Result<RequestProfile> {
+    let command = command.replace("\\\r\n", " ").replace("\\\n", " ");
+    let mut words = shell_words::split(&command)
+        .context("invalid curl command")?
+        .into_iter();
+    if words.next().as_deref() != Some("curl") {
+        return Err(anyhow!("not a curl command: {}", command.trim()));
+    }
+
+    let mut method = None;
+    let mut url = None;
+    let mut headers = HeaderMap::new();
+    let mut data = vec![];
+    let mut parts = vec![];
+    let mut get = false;
+    let mut client = ClientConfig::default();
+    let mut flags = vec![];
+
+    while let Some(word) = flags.pop().or_else(|| words.next()) {
+        if let Some(mut split) = split_flags(&word) {
+            split.reverse();
+            flags = split;
+            continue;
+        }
+        let (option, inline) = split_option(&word);
+        let mut value = || {
+            inline
+                .map(|v| v.to_string())
+                .or_else(|| words.next())
+                .ok_or_else(|| anyhow!("missing value of {}", option))
+        };
+        match option {
+            "-X" | "--request" => method = Some(Method::from_str(&value()?.to_uppercase())?),
+            "-H" | "--header" => {
+                let header = value()?;
+                let (k, v) = header
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("invalid header {:?}", header))?;
+                headers.append(
+                    HeaderName::from_str(k.trim())?,
+                    HeaderValue::from_str(v.trim())?,
+                );
+            }
+            "-d" | "--data" | "--data-ascii" => data.push(read_data(&value()?, true)?),
+            "--data-binary" => data.push(read_data(&value()?, false)?),
+            "--data-raw" => data.push(value()?),
+            "--data-urlencode" => data.push(encode_data(&value()?)?),
+            "--json" => {
+                for name in [header::CONTENT_TYPE, header::ACCEPT] {
+                    if !headers.contains_key(&name) {
+                        headers.insert(name, HeaderValue::from_static("application/json"));
+                    }
+                }
+                data.push(read_data(&value()?, false)?);
+            }
+            "-F" | "--form" => parts.push(parse_part(&value()?)?),
+            "--form-string" => {
+                let field = value()?;
+                let (name, value) = field
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("invalid form field {:?}", field))?;
+                parts.push(Part::text(name, value));
+            }
+            "-G" | "--get" => get = true,
+            "-u" | "--user" => {
+                let credentials = base64::encode(value()?);
+                headers.insert(
+                    header::AUTHORIZATION,
+                    HeaderValue::from_str(&format!("Basic {}", credentials))?,
+                );
+            }
+            "-A" | "--user-agent" => {
+                headers.insert(header::USER_AGENT, HeaderValue::from_str(&value()?)?);
+            }
+            "-b" | "--cookie" => {
+                headers.insert(header::COOKIE, HeaderValue::from_str(&value()?)?);
+            }
+            "-e" | "--referer" => {
+                headers.insert(header::REFERER, HeaderValue::from_str(&value()?)?);
+            }
+            "-k" | "--insecure" => client.insecure = Some(true),
+            "-m" | "--max-time" => client.timeout_ms = Some(seconds_to_ms(&value()?)?),
+            "--connect-timeout" => client.connect_timeout_ms = Some(seconds_to_ms(&value()?)?),
+            "--url" => url = Some(value()?),
+            o if IGNORED_OPTIONS.contains(&o) => {
+                value()?;
+            }
+            o if IGNORED_FLAGS.contains(&o) => {}
+            o if o.starts_with('-') => return Err(anyhow!("unsupported curl option {}", o)),
+            _ => url = Some(word.clone()),
+        }
+    }
+
+    let url = url.ok_or_else(|| anyhow!("missing url in curl command"))?;
+    let mut url = Url::parse(&url).with_context(|| format!("invalid url {}", url))?;
+    let data = data.join("&");
+    // the data of -G is added to the query string as curl does
+    if get && !data.is_empty() {
+        let query = match url.query() {
+            Some(query) if !query.is_empty() => format!("{}&{}", query, data),
+            _ => data.clone(),
+        };
+        url.set_query(Some(&query));
+    }
+    let params = take_query(&mut url);
+
+    let mut body = None;
+    if !parts.is_empty() {
+        if !data.is_empty() {
+            return Err(anyhow!("-F could not be used together with -d"));
+        }
+        body = Some(Body::Multipart(parts));
+    } else if !get && !data.is_empty() {
+        body = Some(parse_body(&mut headers, &data)?);
+    }
+
+    let method = method.unwrap_or(if body.is_some() {
+        Method::POST
+    } else {
+        Method::GET
+    });
+    let mut req = RequestProfile::new(method, url, params, headers, body);
+    if client != ClientConfig::default() {
+        req.client = Some(client);
+    }
+    req.validate()?;
+    Ok(req)
+}
+
+/// Split combined short flags into single ones, e.g. `-sSL` into `-s`, `-S` and `-L`.
+/// An option taking a value ends the flags, e.g. `-sXPOST` is `-s` and `-XPOST`.
+fn split_flags(word: &str) -> Option<Vec<String>> {
+    let flags = word.strip_prefix('-').filter(|w| !w.starts_with('-'))?;
+    let first = format!("-{}", flags.chars().next()?);
+    if flags.len() == first.len() - 1 || SHORT_OPTIONS.contains(&first.as_str()) {
+        return None;
+    }
+    let mut split = vec![];
+    for (idx, c) in flags.char_indices() {
+        let flag = format!("-{}", c);
+        if SHORT_OPTIONS.contains(&flag.as_str()) {
+            split.push(format!("{}{}", flag, &flags[idx + c.len_utf8()..]));
+            break;
+        }
+        split.push(flag);
+    }
+    Some(split)
+}
+
+/// The data of `-d`, read from the file if given as `@file`. curl strips the line breaks
+/// of a file unless it is sent with `--data-binary`.
+fn read_data(data: &str, strip_newlines: bool) -> Result<String> {
+    let Some(path) = data.strip_prefix('@') else {
+        return Ok(data.to_string());
+    };
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read data file {}", path))?;
+    Ok(match strip_newlines {
+        true => content.replace(['\r', '\n'], ""),
+        false => content,
+    })
+}
+
+/// Split `--option=value` and `-Xvalue` into the option and its value.
+fn split_option(word: &str) -> (&str, Option<&str>) {
+    if word.starts_with("--") {
+        if let Some((option, value)) = word.split_once('=') {
+            return (option, Some(value));
+        }
+    } else if let Some(option) = word.get(..2) {
+        if word.len() > 2 && SHORT_OPTIONS.contains(&option) {
+            return (option, Some(&word[2..]));
+        }
+    }
+    (word, None)
+}
+
+/// Parse a `-F` field: `name=value`, or `name=@file` with optional `;type=` and `;filename=`.
+fn parse_part(field: &str) -> Result<Part> {
+    let (name, value) = field
+        .split_once('=')
+        .ok_or_else(|| anyhow!("invalid form field {:?}", field))?;
+    let Some(file) = value.strip_prefix('@') else {
+        return Ok(Part::text(name, value));
+    };
+
+    let mut attrs = file.split(';');
+    let mut part = Part::text(name, "");
+    part.value = None;
+    part.file = attrs.next().map(Into::into);
+    for attr in attrs {
+        match attr.split_once('=') {
+            Some(("type", v)) => part.content_type = Some(v.to_string()),
+            Some(("filename", v)) => part.filename = Some(v.trim_matches('"').to_string()),
+            _ => return Err(anyhow!("unsupported form field attribute {:?}", attr)),
+        }
+    }
+    Ok(part)
+}
+
+/// Encode the value of `--data-urlencode` as curl does for `name=content`, `content`,
+/// `@file` and `name@file`.
+fn encode_data(data: &str) -> Result<String> {
+    let encode = |s: &str| form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+    let read = |path: &str| {
+        std::fs::read_to_string(path).with_context(|| format!("failed to read data file {}", path))
+    };
+    Ok(match (data.split_once('='), data.split_once('@')) {
+        (Some(("", content)), _) => encode(content),
+        (Some((name, content)), _) => format!("{}={}", name, encode(content)),
+        (None, Some(("", path))) => encode(&read(path)?),
+        (None, Some((name, path))) => format!("{}={}", name, encode(&read(path)?)),
+        (None, None) => encode(data),
+    })
+}
+
+fn seconds_to_ms(s: &str) -> Result<u64> {
+    let secs: f64 = s
+        .parse()
+        .with_context(|| format!("invalid seconds {:?}", s))?;
+    Ok((secs * 1000.0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_curl() {
+        let req = parse_curl(
+            r#"curl -XPUT 'https://example.com/api/items/1?lang=en' \
+  -H 'Content-Type: application/json' -H "x-token: abc" \
+  --data-raw '{"name": "a b", "count": 2}' --compressed -k"#,
+        )
+        .unwrap();
+        assert_eq!(req.method, Method::PUT);
+        assert_eq!(req.url.as_str(), "https://example.com/api/items/1");
+        assert_eq!(req.params, Some(json!({"lang": "en"})));
+        assert_eq!(req.headers["x-token"], "abc");
+        assert_eq!(
+            req.body,
+            Some(Body::Json(json!({"name": "a b", "count": 2})))
+        );
+        assert_eq!(req.client.unwrap().insecure, Some(true));
+
+        let req = parse_curl("curl -sSLkXDELETE https://example.com/api/items/1").unwrap();
+        assert_eq!(req.method, Method::DELETE);
+        assert_eq!(req.client.unwrap().insecure, Some(true));
+
+        let req = parse_curl("curl -sSL -X PATCH https://example.com -sd a=1").unwrap();
+        assert_eq!(req.method, Method::PATCH);
+        assert_eq!(
+            req.body,
+            Some(Body::Form(json!({"a": "1"}).as_object().unwrap().clone()))
+        );
+    }
+
+    #[test]
+    fn test_parse_curl_data_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("item.json");
+        std::fs::write(&file, "{\"name\": \"a\",\n \"count\": 2}\n").unwrap();
+
+        let req = parse_curl(&format!(
+            "curl -sS https://example.com/items -H 'content-type: application/json' -d @{}",
+            file.display()
+        ))
+        .unwrap();
+        assert_eq!(req.method, Method::POST);
+        assert_eq!(req.body, Some(Body::Json(json!({"name": "a", "count": 2}))));
+
+        let text = dir.path().join("note.txt");
+        std::fs::write(&text, "a b\n").unwrap();
+        let req = parse_curl(&format!(
+            "curl https://example.com/notes -H 'content-type: text/plain' --data-binary @{0} \
+              --data-raw @{0}",
+            text.display()
+        ))
+        .unwrap();
+        assert_eq!(
+            req.body,
+            Some(Body::Text(format!("a b\n&@{}", text.display())))
+        );
+
+        let req = parse_curl(&format!(
+            "curl https://example.com/notes --data-urlencode note@{}",
+            text.display()
+        ))
+        .unwrap();
+        assert_eq!(
+            req.body,
+            Some(Body::Form(
+                json!({"note": "a b\n"}).as_object().unwrap().clone()
+            ))
+        );
+
+        assert!(parse_curl("curl https://example.com -d @missing.json").is_err());
+    }
+
+    #[test]
+    fn test_parse_curl_form_data() {
+        let req = parse_curl(
+            "curl https://example.com/search -d a=1 --data-urlencode 'q=hello world&more'",
+        )
+        .unwrap();
+        assert_eq!(req.method, Method::POST);
+        assert_eq!(
+            req.headers[header::CONTENT_TYPE],
+            "application/x-www-form-urlencoded"
+        );
+        assert_eq!(
+            req.body,
+            Some(Body::Form(
+                json!({"a": "1", "q": "hello world&more"})
+                    .as_object()
+                    .unwrap()
+                    .clone()
+            ))
+        );
+
+        let req = parse_curl("curl -G https://example.com/search -d a=1 -d b=2").unwrap();
+        assert_eq!(req.method, Method::GET);
+        assert_eq!(req.params, Some(json!({"a": "1", "b": "2"})));
+        assert_eq!(req.body, None);
+
+        // a repeated field is kept as it is sent rather than dropped
+        let req = parse_curl("curl https://example.com/search -d a=1 -d a=2").unwrap();
+        assert_eq!(req.body, Some(Body::Text("a=1&a=2".into())));
+        let req = parse_curl("curl -G 'https://example.com/search?a=1' -d a=2").unwrap();
+        assert_eq!(req.url, "https://example.com/search?a=1&a=2");
+        assert_eq!(req.params, None);
+
+        let req = parse_curl(
+            "curl https://example.com/upload -F title=report -F 'doc=@Cargo.toml;type=text/toml'",
+        )
+        .unwrap();
+        let Some(Body::Multipart(parts)) = req.body else {
+            panic!("expect a multipart body");
+        };
+        assert_eq!(parts[0], Part::text("title", "report"));
+        assert_eq!(parts[1].file, Some("Cargo.toml".into()));
+        assert_eq!(parts[1].content_type.as_deref(), Some("text/toml"));
+
+        assert!(parse_curl("curl -sS --unknown https://example.com").is_err());
+        assert!(parse_curl("curl -sZ https://example.com").is_err());
+        assert!(parse_curl("wget https://example.com").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use super::{form_fields, parse_body, profile_name, take_query};
+use crate::profile::{body::Body, req::RequestProfile};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use reqwest::Method;
+use serde::Deserialize;
+use url::{form_urlencoded, Url};
+
+#[derive(Debug, Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarEntry {
+    request: HarRequest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<HarPair>,
+    post_data: Option<HarPostData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarPair {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarPostData {
+    #[serde(default)]
+    mime_type: String,
+    text: Option<String>,
+    #[serde(default)]
+    params: Vec<HarPair>,
+}
+
+/// Headers which are derived from the request by the client.
+const SKIP_HEADERS: &[&str] = &["host", "content-length", "connection"];
+
+/// Parse the requests of a HAR export into profiles named after their method and path.
+pub fn parse_har(content: &str) -> Result<Vec<(String, RequestProfile)>> {
+    let har: Har = serde_json::from_str(content).context("invalid HAR file")?;
+    har.log
+        .entries
+        .into_iter()
+        .enumerate()
+        .map(|(idx, entry)| {
+            let req = parse_request(entry.request)
+                .with_context(|| format!("failed to import HAR entry {}", idx))?;
+            Ok((
+                profile_name(req.method.as_str(), req.parse_url()?.path()),
+                req,
+            ))
+        })
+        .collect()
+}
+
+fn parse_request(har: HarRequest) -> Result<RequestProfile> {
+    let method = Method::from_str(&har.method)?;
+    let mut url = Url::parse(&har.url).with_context(|| format!("invalid url {}", har.url))?;
+    let params = take_query(&mut url);
+
+    let mut headers = HeaderMap::new();
+    for HarPair { name, value } in har.headers {
+        let name = name.to_lowercase();
+        // skip the HTTP/2 pseudo headers, e.g. `:authority`
+        if name.starts_with(':') || SKIP_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        headers.append(HeaderName::from_str(&name)?, HeaderValue::from_str(&value)?);
+    }
+
+    let body = match har.post_data {
+        Some(data) => {
+            if !headers.contains_key(header::CONTENT_TYPE) && !data.mime_type.is_empty() {
+                headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_str(&data.mime_type)?,
+                );
+            }
+            match data.text {
+                Some(text) if !text.is_empty() => Some(parse_body(&mut headers, &text)?),
+                _ if !data.params.is_empty() => {
+                    let pairs: Vec<(String, String)> =
+                        data.params.into_iter().map(|p| (p.name, p.value)).collect();
+                    // a form with a repeated field is kept as it is sent
+                    Some(
+                        form_fields(pairs.clone())
+                            .map(Body::Form)
+                            .unwrap_or_else(|| {
+                                Body::Text(
+                                    form_urlencoded::Serializer::new(String::new())
+                                        .extend_pairs(pairs)
+                                        .finish(),
+                                )
+                            }),
+                    )
+                }
+                _ => None,
+            }
+        }
+        None => None,
+    };
+
+    let req = RequestProfile::new(method, url, params, headers, body);
+    req.validate()?;
+    Ok(req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_har() {
+        let har = json!({"log": {"version": "1.2", "entries": [
+            {"request": {
+                "method": "GET",
+                "url": "https://example.com/todos/1?full=true",
+                "headers": [
+                    {"name": ":authority", "value": "example.com"},
+                    {"name": "Accept", "value": "application/json"},
+                ],
+            }},
+            {"request": {
+                "method": "POST",
+                "url": "https://example.com/todos",
+                "headers": [{"name": "Content-Length", "value": "15"}],
+                "postData": {"mimeType": "application/json", "text": "{\"title\": \"a\"}"},
+            }},
+        ]}});
+
+        let profiles = parse_har(&har.to_string()).unwrap();
+        let names: Vec<&str> = profiles.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["get-todos-1", "post-todos"]);
+
+        let get = &profiles[0].1;
+        assert_eq!(get.params, Some(json!({"full": "true"})));
+        assert_eq!(get.headers.len(), 1);
+
+        let post = &profiles[1].1;
+        assert_eq!(post.headers[header::CONTENT_TYPE], "application/json");
+        assert!(!post.headers.contains_key(header::CONTENT_LENGTH));
+        assert_eq!(post.body, Some(Body::Json(json!({"title": "a"}))));
+    }
+}
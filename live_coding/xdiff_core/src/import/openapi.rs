@@ -0,0 +1,280 @@
+use super::profile_name;
+use crate::profile::{body::Body, req::RequestProfile};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use indexmap::IndexMap;
+use openapiv3::{
+    MediaType, OpenAPI, Operation, Parameter, ParameterData, ParameterSchemaOrContent, ReferenceOr,
+    RequestBody,
+};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::Method;
+use serde_json::{Map, Value};
+
+/// The characters encoded in a segment of a path, as the url parser does and `/`.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Parse the operations of an OpenAPI 3 spec in json or yaml into profiles named
+/// after their operation id.
+///
+/// Parameters and bodies are filled with their examples or defaults. Required
+/// parameters without any are left as `{{ name }}` variables.
+pub fn parse_openapi(content: &str, server: Option<&str>) -> Result<Vec<(String, RequestProfile)>> {
+    let spec: OpenAPI = serde_yaml::from_str(content).context("invalid OpenAPI spec")?;
+    let server = match server {
+        Some(server) => server.to_string(),
+        None => default_server(&spec)?,
+    };
+    let resolver = Resolver::new(&spec);
+
+    let mut profiles = vec![];
+    for (path, item) in spec.paths.iter() {
+        let item = item
+            .as_item()
+            .ok_or_else(|| anyhow!("references of path items are not supported: {}", path))?;
+        for (method, op) in item.iter() {
+            let name = op
+                .operation_id
+                .clone()
+                .unwrap_or_else(|| profile_name(method, path));
+            let parameters = item.parameters.iter().chain(&op.parameters);
+            let req = parse_operation(&resolver, &server, path, method, op, parameters)
+                .with_context(|| format!("failed to import operation {}", name))?;
+            profiles.push((name, req));
+        }
+    }
+    Ok(profiles)
+}
+
+fn default_server(spec: &OpenAPI) -> Result<String> {
+    let server = spec
+        .servers
+        .first()
+        .ok_or_else(|| anyhow!("no servers in the spec, please give one with --server"))?;
+    let mut url = server.url.clone();
+    for (name, var) in server.variables.iter().flatten() {
+        url = url.replace(&format!("{{{}}}", name), &var.default);
+    }
+    Ok(url)
+}
+
+fn parse_operation<'a>(
+    resolver: &Resolver,
+    server: &str,
+    path: &str,
+    method: &str,
+    op: &Operation,
+    parameters: impl Iterator<Item = &'a ReferenceOr<Parameter>>,
+) -> Result<RequestProfile> {
+    let mut path = path.to_string();
+    let mut params = Map::new();
+    let mut headers = HeaderMap::new();
+
+    for param in parameters {
+        let param = resolver.parameter(param)?;
+        let data = param.parameter_data_ref();
+        let Some(value) = example_value(data) else {
+            continue;
+        };
+        match param {
+            Parameter::Path { .. } => {
+                // the url is not parsed, so that the variables are kept as they are
+                let segment = match value {
+                    Value::String(s) if s == variable(&data.name) => s,
+                    value => utf8_percent_encode(&to_text(&value), PATH_SEGMENT).to_string(),
+                };
+                path = path.replace(&format!("{{{}}}", data.name), &segment);
+            }
+            Parameter::Query { .. } => {
+                params.insert(data.name.clone(), value);
+            }
+            Parameter::Header { .. } => {
+                headers.insert(
+                    HeaderName::from_str(&data.name)?,
+                    HeaderValue::from_str(&to_text(&value))?,
+                );
+            }
+            Parameter::Cookie { .. } => {}
+        }
+    }
+
+    let mut body = None;
+    if let Some(ref request_body) = op.request_body {
+        let request_body = resolver.request_body(request_body)?;
+        let media = request_body
+            .content
+            .get_key_value("application/json")
+            .or_else(|| request_body.content.first());
+        if let Some((content_type, media)) = media {
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(content_type)?);
+            body = media_example(media).map(|example| match (content_type.as_str(), example) {
+                ("application/x-www-form-urlencoded", Value::Object(fields)) => Body::Form(fields),
+                (_, Value::String(text)) if !content_type.contains("json") => Body::Text(text),
+                (_, example) => Body::Json(example),
+            });
+        }
+    }
+
+    let url = format!("{}{}", server.trim_end_matches('/'), path);
+    let method = Method::from_str(&method.to_uppercase())?;
+    let params = (!params.is_empty()).then_some(Value::Object(params));
+
+    let req = RequestProfile::new(method, url, params, headers, body);
+    req.validate()?;
+    Ok(req)
+}
+
+/// The example or default of a parameter, or a variable if it is required.
+fn example_value(data: &ParameterData) -> Option<Value> {
+    let schema = match data.format {
+        ParameterSchemaOrContent::Schema(ReferenceOr::Item(ref schema)) => Some(schema),
+        _ => None,
+    };
+    data.example
+        .clone()
+        .or_else(|| {
+            data.examples
+                .values()
+                .find_map(|e| e.as_item()?.value.clone())
+        })
+        .or_else(|| schema.and_then(|s| s.schema_data.example.clone()))
+        .or_else(|| schema.and_then(|s| s.schema_data.default.clone()))
+        .or_else(|| data.required.then(|| Value::String(variable(&data.name))))
+}
+
+fn variable(name: &str) -> String {
+    format!("{{{{ {} }}}}", name)
+}
+
+fn media_example(media: &MediaType) -> Option<Value> {
+    media
+        .example
+        .clone()
+        .or_else(|| {
+            media
+                .examples
+                .values()
+                .find_map(|e| e.as_item()?.value.clone())
+        })
+        .or_else(|| match media.schema {
+            Some(ReferenceOr::Item(ref schema)) => schema.schema_data.example.clone(),
+            _ => None,
+        })
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// Resolves the `#/components/...` references of a spec.
+struct Resolver<'a> {
+    parameters: Option<&'a IndexMap<String, ReferenceOr<Parameter>>>,
+    request_bodies: Option<&'a IndexMap<String, ReferenceOr<RequestBody>>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(spec: &'a OpenAPI) -> Self {
+        Self {
+            parameters: spec.components.as_ref().map(|c| &c.parameters),
+            request_bodies: spec.components.as_ref().map(|c| &c.request_bodies),
+        }
+    }
+
+    fn parameter(&self, param: &'a ReferenceOr<Parameter>) -> Result<&'a Parameter> {
+        resolve(param, self.parameters, "#/components/parameters/")
+    }
+
+    fn request_body(&self, body: &'a ReferenceOr<RequestBody>) -> Result<&'a RequestBody> {
+        resolve(body, self.request_bodies, "#/components/requestBodies/")
+    }
+}
+
+fn resolve<'a, T>(
+    item: &'a ReferenceOr<T>,
+    components: Option<&'a IndexMap<String, ReferenceOr<T>>>,
+    prefix: &str,
+) -> Result<&'a T> {
+    match item {
+        ReferenceOr::Item(item) => Ok(item),
+        ReferenceOr::Reference { reference } => reference
+            .strip_prefix(prefix)
+            .and_then(|name| components?.get(name)?.as_item())
+            .ok_or_else(|| anyhow!("unresolved reference {}", reference)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SPEC: &str = r##"
+openapi: 3.0.0
+info: { title: pets, version: "1.0" }
+servers:
+  - url: "{scheme}://example.com/v1"
+    variables:
+      scheme: { default: https }
+paths:
+  /pets:
+    get:
+      operationId: listPets
+      responses: { "200": { description: ok } }
+      parameters:
+        - { name: limit, in: query, schema: { type: integer, default: 20 } }
+        - { name: tag, in: query, schema: { type: string } }
+        - $ref: "#/components/parameters/Tenant"
+    post:
+      responses: { "201": { description: created } }
+      requestBody:
+        content:
+          application/json:
+            example: { name: kitty }
+  /pets/{petId}:
+    get:
+      operationId: showPet
+      responses: { "200": { description: ok } }
+      parameters:
+        - { name: petId, in: path, required: true, schema: { type: string } }
+components:
+  parameters:
+    Tenant: { name: x-tenant, in: header, example: acme, schema: { type: string } }
+"##;
+
+    #[test]
+    fn test_parse_openapi() {
+        let profiles = parse_openapi(SPEC, None).unwrap();
+        let names: Vec<&str> = profiles.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["listPets", "post-pets", "showPet"]);
+
+        let list = &profiles[0].1;
+        assert_eq!(list.url.as_str(), "https://example.com/v1/pets");
+        assert_eq!(list.params, Some(json!({"limit": 20})));
+        assert_eq!(list.headers["x-tenant"], "acme");
+
+        let create = &profiles[1].1;
+        assert_eq!(create.method, Method::POST);
+        assert_eq!(create.body, Some(Body::Json(json!({"name": "kitty"}))));
+
+        let (_, show) = &parse_openapi(SPEC, Some("http://localhost:8080")).unwrap()[2];
+        assert_eq!(show.url.as_str(), "http://localhost:8080/pets/{{ petId }}");
+        let target = crate::import::with_origin(show, "https://staging.example.com").unwrap();
+        assert_eq!(target.url, "https://staging.example.com/pets/{{ petId }}");
+    }
+}
@@ -1,6 +1,15 @@
+pub mod bench;
 pub mod cli;
+pub mod client;
 pub mod config;
+pub mod export;
+pub mod flaky;
+pub mod import;
+pub mod normalize;
 pub mod profile;
+pub mod snapshot;
+pub mod tui;
 pub mod utils;
+pub mod watch;
 
 pub use config::{LoadConfig, ValidateConfig};
@@ -0,0 +1,75 @@
+pub mod csv;
+pub mod html;
+pub mod protobuf;
+pub mod transform;
+pub mod xml;
+pub mod yaml;
+
+use crate::utils::json_path::JsonPath;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// A response body in the form which is compared.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Normalized {
+    /// The normalized body as text.
+    pub body: String,
+    /// The filtered body if it could be compared as json.
+    pub json: Option<Value>,
+}
+
+/// Turns the body of a response with a given content type into a stable text,
+/// leaving out the parts which are expected to change between responses.
+pub trait Normalizer {
+    /// Whether the normalizer handles the content type, e.g. `application/xml`.
+    fn accepts(&self, content_type: &str) -> bool;
+
+    /// Check the skip rules of the normalizer.
+    fn validate(&self) -> Result<()>;
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized>;
+
+    /// Render the json decoded by `normalize` again after it is transformed.
+    fn render_json(&self, json: &Value) -> Result<String> {
+        Ok(serde_json::to_string_pretty(json)?)
+    }
+}
+
+/// Pretty prints json bodies without the nodes at the skipped json paths.
+pub struct JsonNormalizer<'a> {
+    pub skip: &'a [String],
+}
+
+impl Normalizer for JsonNormalizer<'_> {
+    fn accepts(&self, content_type: &str) -> bool {
+        content_type == "application/json"
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_json_paths(self.skip).context("skip_body contains an invalid json path")
+    }
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized> {
+        let mut json = serde_json::from_slice(body)?;
+        skip_json(&mut json, self.skip)?;
+        Ok(Normalized {
+            body: serde_json::to_string_pretty(&json)?,
+            json: Some(json),
+        })
+    }
+}
+
+pub fn skip_json(json: &mut Value, skip: &[String]) -> Result<()> {
+    for path in skip {
+        path.parse::<JsonPath>()?.remove(json);
+    }
+    Ok(())
+}
+
+pub fn validate_json_paths(paths: &[String]) -> Result<()> {
+    for path in paths {
+        path.parse::<JsonPath>()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use super::{Normalized, Normalizer};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Rewrites csv bodies with one quoted row per line, so they are diffed row by row.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct CsvNormalizer {
+    /// Names of the columns to skip, as given by the header row.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub skip_columns: Vec<String>,
+
+    /// Sort the rows after the header, for bodies without a stable order.
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub sort_rows: bool,
+}
+
+impl Normalizer for CsvNormalizer {
+    fn accepts(&self, content_type: &str) -> bool {
+        matches!(content_type, "text/csv" | "application/csv")
+    }
+
+    fn validate(&self) -> Result<()> {
+        match self.skip_columns.iter().find(|c| c.is_empty()) {
+            Some(_) => Err(anyhow!("csv skip_columns contains an empty column name")),
+            None => Ok(()),
+        }
+    }
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized> {
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(body);
+        let headers = reader.headers().context("invalid csv body")?.clone();
+        let keep: Vec<usize> = (0..headers.len())
+            .filter(|&idx| !self.skip_columns.iter().any(|c| c == &headers[idx]))
+            .collect();
+
+        let mut rows = vec![];
+        for record in reader.records() {
+            let record = record.context("invalid csv body")?;
+            rows.push(write_row(
+                keep.iter().map(|&idx| record.get(idx).unwrap_or_default()),
+            )?);
+        }
+        if self.sort_rows {
+            rows.sort();
+        }
+
+        let mut body = write_row(keep.iter().map(|&idx| &headers[idx]))?;
+        for row in rows {
+            body.push_str(&row);
+        }
+        Ok(Normalized { body, json: None })
+    }
+}
+
+fn write_row<'a>(fields: impl Iterator<Item = &'a str>) -> Result<String> {
+    let mut writer = csv::WriterBuilder::new()
+        .quote_style(csv::QuoteStyle::Always)
+        .from_writer(vec![]);
+    writer.write_record(fields)?;
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_csv() {
+        let normalizer = CsvNormalizer {
+            skip_columns: vec!["updated".into()],
+            sort_rows: true,
+        };
+        let body = "id,name,updated\n2,\"b, c\",100\n1,a,101\n";
+        assert_eq!(
+            normalizer.normalize(body.as_bytes()).unwrap().body,
+            "\"id\",\"name\"\n\"1\",\"a\"\n\"2\",\"b, c\"\n"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use super::{Normalized, Normalizer};
+use std::fmt::Write;
+
+use anyhow::{anyhow, Context, Result};
+use ego_tree::NodeRef;
+use scraper::{Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+
+/// Elements whose content is kept as it is.
+const RAW_ELEMENTS: &[&str] = &["pre", "textarea"];
+
+/// Pretty prints html bodies with one tag or text per line, leaving out
+/// comments and the nodes matched by the css selectors to skip.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct HtmlNormalizer {
+    /// Css selectors of the volatile nodes, e.g. `script`, `meta[name=csrf-token]`
+    /// or `#footer .build-time`.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub skip: Vec<String>,
+}
+
+impl Normalizer for HtmlNormalizer {
+    fn accepts(&self, content_type: &str) -> bool {
+        matches!(content_type, "text/html" | "application/xhtml+xml")
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.selectors().map(|_| ())
+    }
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized> {
+        let text = std::str::from_utf8(body).context("html body is not valid utf8")?;
+        let mut html = Html::parse_document(text);
+        for selector in self.selectors()? {
+            let ids: Vec<_> = html.select(&selector).map(|el| el.id()).collect();
+            for id in ids {
+                if let Some(mut node) = html.tree.get_mut(id) {
+                    node.detach();
+                }
+            }
+        }
+
+        let mut output = String::new();
+        for child in html.tree.root().children() {
+            write_node(&mut output, child, 0)?;
+        }
+        Ok(Normalized {
+            body: output,
+            json: None,
+        })
+    }
+}
+
+impl HtmlNormalizer {
+    fn selectors(&self) -> Result<Vec<Selector>> {
+        self.skip
+            .iter()
+            .map(|s| Selector::parse(s).map_err(|e| anyhow!("invalid css selector {:?}: {}", s, e)))
+            .collect()
+    }
+}
+
+fn write_node(output: &mut String, node: NodeRef<Node>, depth: usize) -> Result<()> {
+    let indent = "  ".repeat(depth);
+    match node.value() {
+        Node::Doctype(doctype) => writeln!(output, "{}<!DOCTYPE {}>", indent, doctype.name())?,
+        Node::Text(text) if !text.trim().is_empty() => {
+            for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                writeln!(output, "{}{}", indent, line)?;
+            }
+        }
+        Node::Element(el) => {
+            let mut attrs: Vec<String> = el
+                .attrs()
+                .map(|(k, v)| format!(" {}=\"{}\"", k, v.replace('"', "&quot;")))
+                .collect();
+            attrs.sort();
+            writeln!(output, "{}<{}{}>", indent, el.name(), attrs.concat())?;
+            if RAW_ELEMENTS.contains(&el.name()) {
+                let text: String = node
+                    .descendants()
+                    .filter_map(|n| n.value().as_text().map(|t| t.to_string()))
+                    .collect();
+                writeln!(output, "{}", text)?;
+            } else {
+                for child in node.children() {
+                    write_node(output, child, depth + 1)?;
+                }
+            }
+            writeln!(output, "{}</{}>", indent, el.name())?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_html() {
+        let normalizer = HtmlNormalizer {
+            skip: vec!["script".into(), "meta[name=csrf-token]".into()],
+        };
+        normalizer.validate().unwrap();
+        let body = r#"<!DOCTYPE html><html><head><meta name="csrf-token" content="x1">
+<title>Todos</title><script>var now = 1;</script></head>
+<body><!-- build 42 --><ul class="list" id="todos"><li>a</li></ul></body></html>"#;
+        assert_eq!(
+            normalizer.normalize(body.as_bytes()).unwrap().body,
+            r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>
+      Todos
+    </title>
+  </head>
+  <body>
+    <ul class="list" id="todos">
+      <li>
+        a
+      </li>
+    </ul>
+  </body>
+</html>
+"#
+        );
+
+        let invalid = HtmlNormalizer {
+            skip: vec!["div[".into()],
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
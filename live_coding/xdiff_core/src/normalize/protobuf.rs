@@ -0,0 +1,135 @@
+use super::{skip_json, validate_json_paths, Normalized, Normalizer};
+use crate::utils::Cached;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde::{Deserialize, Serialize};
+
+/// Decodes protobuf bodies into json with a descriptor set, as generated by
+/// `protoc --include_imports --descriptor_set_out`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ProtobufNormalizer {
+    pub descriptor_set: PathBuf,
+
+    /// The full name of the message of the body, e.g. `todo.v1.Todo`.
+    pub message: String,
+
+    /// Json paths of the decoded fields to skip, by their json names, e.g. `updatedAt`.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub skip: Vec<String>,
+
+    /// The message decoded from the descriptor set when the config is validated.
+    #[serde(skip)]
+    pub descriptor: Cached<MessageDescriptor>,
+}
+
+impl Normalizer for ProtobufNormalizer {
+    fn accepts(&self, content_type: &str) -> bool {
+        matches!(
+            content_type,
+            "application/protobuf" | "application/x-protobuf" | "application/vnd.google.protobuf"
+        )
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.descriptor()?;
+        validate_json_paths(&self.skip).context("protobuf skip contains an invalid json path")
+    }
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized> {
+        let message = DynamicMessage::decode(self.descriptor()?.clone(), body)
+            .with_context(|| format!("failed to decode the body as {}", self.message))?;
+        let mut json = serde_json::to_value(&message)?;
+        skip_json(&mut json, &self.skip)?;
+        Ok(Normalized {
+            body: serde_json::to_string_pretty(&json)?,
+            json: Some(json),
+        })
+    }
+}
+
+impl ProtobufNormalizer {
+    /// The descriptor of the message, which is only decoded from the file the first time.
+    fn descriptor(&self) -> Result<&MessageDescriptor> {
+        self.descriptor.get_or_load(|| {
+            let bytes = std::fs::read(&self.descriptor_set)
+                .with_context(|| format!("failed to read {}", self.descriptor_set.display()))?;
+            let pool = DescriptorPool::decode(bytes.as_slice()).with_context(|| {
+                format!("invalid descriptor set {}", self.descriptor_set.display())
+            })?;
+            pool.get_message_by_name(&self.message)
+                .ok_or_else(|| anyhow!("message {} not found in the descriptor set", self.message))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_reflect::{
+        prost::Message,
+        prost_types::{
+            field_descriptor_proto::Type, DescriptorProto, FieldDescriptorProto,
+            FileDescriptorProto, FileDescriptorSet,
+        },
+        Value,
+    };
+    use serde_json::json;
+
+    fn field(name: &str, number: i32, ty: Type) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.into()),
+            json_name: Some(name.into()),
+            number: Some(number),
+            r#type: Some(ty as i32),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize_protobuf() {
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("todo.proto".into()),
+                package: Some("todo".into()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Todo".into()),
+                    field: vec![
+                        field("id", 1, Type::Int32),
+                        field("title", 2, Type::String),
+                        field("updated", 3, Type::Int64),
+                    ],
+                    ..Default::default()
+                }],
+                syntax: Some("proto3".into()),
+                ..Default::default()
+            }],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let descriptor_set = dir.path().join("todo.pb");
+        std::fs::write(&descriptor_set, set.encode_to_vec()).unwrap();
+
+        let normalizer = ProtobufNormalizer {
+            descriptor_set,
+            message: "todo.Todo".into(),
+            skip: vec!["updated".into()],
+            descriptor: Default::default(),
+        };
+        normalizer.validate().unwrap();
+
+        let mut todo = DynamicMessage::new(normalizer.descriptor().unwrap().clone());
+        todo.set_field_by_name("id", Value::I32(1));
+        todo.set_field_by_name("title", Value::String("hello".into()));
+        todo.set_field_by_name("updated", Value::I64(100));
+        let normalized = normalizer.normalize(&todo.encode_to_vec()).unwrap();
+        assert_eq!(normalized.json, Some(json!({"id": 1, "title": "hello"})));
+
+        let missing = ProtobufNormalizer {
+            message: "todo.Missing".into(),
+            descriptor: Default::default(),
+            ..normalizer
+        };
+        assert!(missing.validate().is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{profile::is_default, utils::json_path::JsonPath};
+use std::cmp::Ordering;
+
+use anyhow::{anyhow, Context, Result};
+use http::{header::HeaderMap, HeaderValue};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+/// A rule which rewrites the volatile parts of a response before it is diffed,
+/// rather than skipping the whole header or field.
+///
+/// ```yaml
+/// transforms:
+///   - replace: { pattern: "\\d{4}-\\d{2}-\\d{2}T[\\d:.]+Z", with: "<timestamp>" }
+///   - sort: { path: $.items, by: id }
+///   - round: { path: $..price, digits: 2 }
+///   - lowercase_headers: [etag]
+///   - null_as_missing
+/// ```
+///
+/// Sorting, rounding and nulls apply to the bodies decoded as json, i.e. json,
+/// yaml and protobuf bodies.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    /// Replace the matches of a regex in the header values and the body,
+    /// e.g. timestamps and uuids. `with` may refer to groups as `$1`.
+    Replace {
+        pattern: String,
+        with: String,
+        #[serde(skip_serializing_if = "is_default", default)]
+        target: ReplaceTarget,
+    },
+    /// Sort the arrays at a json path, by the value at a json path of their items if given.
+    Sort {
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        by: Option<String>,
+    },
+    /// Round the numbers at a json path to a number of decimal digits.
+    Round { path: String, digits: u32 },
+    /// Lowercase the values of the headers, or of all headers if none are given.
+    LowercaseHeaders(Vec<String>),
+    /// Remove the null fields of objects, so that a null equals a missing key.
+    NullAsMissing,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaceTarget {
+    #[default]
+    All,
+    Headers,
+    Body,
+}
+
+impl Transform {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Transform::Replace { pattern, .. } => {
+                Regex::new(pattern).with_context(|| format!("invalid regex {:?}", pattern))?;
+            }
+            Transform::Sort { path, by } => {
+                path.parse::<JsonPath>()?;
+                if let Some(by) = by {
+                    by.parse::<JsonPath>()?;
+                }
+            }
+            Transform::Round { path, digits } => {
+                path.parse::<JsonPath>()?;
+                if *digits > 15 {
+                    return Err(anyhow!("round to at most 15 digits"));
+                }
+            }
+            Transform::LowercaseHeaders(_) | Transform::NullAsMissing => {}
+        }
+        Ok(())
+    }
+}
+
+/// Updates a header value given its name, or keeps it if `None` is returned.
+type UpdateHeader<'a> = Box<dyn Fn(&str, &str) -> Option<String> + 'a>;
+
+pub fn transform_headers(headers: &HeaderMap, transforms: &[Transform]) -> Result<HeaderMap> {
+    let mut headers = headers.clone();
+    for transform in transforms {
+        let update: UpdateHeader = match transform {
+            Transform::Replace {
+                pattern,
+                with,
+                target: ReplaceTarget::All | ReplaceTarget::Headers,
+            } => {
+                let re = Regex::new(pattern)?;
+                let with = with.clone();
+                Box::new(move |_, v| Some(re.replace_all(v, with.as_str()).into_owned()))
+            }
+            Transform::LowercaseHeaders(names) => Box::new(move |k, v| {
+                (names.is_empty() || names.iter().any(|n| n.eq_ignore_ascii_case(k)))
+                    .then(|| v.to_lowercase())
+            }),
+            _ => continue,
+        };
+        for (k, v) in headers.iter_mut() {
+            // values which are not visible ascii are kept as they are
+            let Ok(value) = v.to_str() else {
+                continue;
+            };
+            if let Some(updated) = update(k.as_str(), value) {
+                *v = HeaderValue::from_str(&updated)
+                    .with_context(|| format!("invalid value of header {}: {}", k, updated))?;
+            }
+        }
+    }
+    Ok(headers)
+}
+
+/// Apply the transforms to a body which is decoded as json.
+pub fn transform_json(json: &mut Value, transforms: &[Transform]) -> Result<()> {
+    for transform in transforms {
+        match transform {
+            Transform::Replace {
+                pattern,
+                with,
+                target: ReplaceTarget::All | ReplaceTarget::Body,
+            } => replace_strings(json, &Regex::new(pattern)?, with),
+            Transform::Sort { path, by } => {
+                let by = by.as_deref().map(str::parse::<JsonPath>).transpose()?;
+                path.parse::<JsonPath>()?.update(json, &mut |value| {
+                    if let Value::Array(items) = value {
+                        items.sort_by(|a, b| match by {
+                            Some(ref by) => compare(by.select(a).first(), by.select(b).first()),
+                            None => compare(Some(&a), Some(&b)),
+                        });
+                    }
+                });
+            }
+            Transform::Round { path, digits } => {
+                let scale = 10f64.powi(*digits as i32);
+                path.parse::<JsonPath>()?.update(json, &mut |value| {
+                    if let Some(n) = value.as_f64().filter(|_| value.is_f64()) {
+                        if let Some(rounded) = Number::from_f64((n * scale).round() / scale) {
+                            *value = Value::Number(rounded);
+                        }
+                    }
+                });
+            }
+            Transform::NullAsMissing => remove_nulls(json),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Apply the transforms to a body which is compared as text.
+pub fn transform_text(text: &str, transforms: &[Transform]) -> Result<String> {
+    let mut text = text.to_string();
+    for transform in transforms {
+        if let Transform::Replace {
+            pattern,
+            with,
+            target: ReplaceTarget::All | ReplaceTarget::Body,
+        } = transform
+        {
+            text = Regex::new(pattern)?
+                .replace_all(&text, with.as_str())
+                .into_owned();
+        }
+    }
+    Ok(text)
+}
+
+fn replace_strings(value: &mut Value, re: &Regex, with: &str) {
+    match value {
+        Value::String(s) => *s = re.replace_all(s, with).into_owned(),
+        Value::Array(arr) => arr.iter_mut().for_each(|v| replace_strings(v, re, with)),
+        Value::Object(obj) => obj.values_mut().for_each(|v| replace_strings(v, re, with)),
+        _ => {}
+    }
+}
+
+fn remove_nulls(value: &mut Value) {
+    match value {
+        Value::Array(arr) => arr.iter_mut().for_each(remove_nulls),
+        Value::Object(obj) => {
+            obj.retain(|_, v| !v.is_null());
+            obj.values_mut().for_each(remove_nulls);
+        }
+        _ => {}
+    }
+}
+
+/// Order missing values first, then numbers and strings by value and anything else by its json.
+fn compare(a: Option<&&Value>, b: Option<&&Value>) -> Ordering {
+    match (a, b) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+        (Some(a), Some(b)) => a.to_string().cmp(&b.to_string()),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn transforms() -> Vec<Transform> {
+        let transforms: Vec<Transform> = serde_yaml::with::singleton_map_recursive::deserialize(
+            serde_yaml::Deserializer::from_str(
+                r#"
+- replace: { pattern: "[0-9a-f]{8}-[0-9a-f-]{27}", with: "<uuid>" }
+- sort: { path: $.items, by: id }
+- round: { path: $..price, digits: 1 }
+- lowercase_headers: [etag]
+- null_as_missing
+"#,
+            ),
+        )
+        .unwrap();
+        transforms.iter().for_each(|t| t.validate().unwrap());
+        transforms
+    }
+
+    #[test]
+    fn test_transform_json() {
+        let mut json = json!({
+            "id": "0b5e9c4a-3f1d-4c2e-9a7b-1d2e3f4a5b6c",
+            "items": [{"id": 2, "price": 1.26}, {"id": 1, "price": 3, "note": null}],
+        });
+        transform_json(&mut json, &transforms()).unwrap();
+        assert_eq!(
+            json,
+            json!({"id": "<uuid>", "items": [{"id": 1, "price": 3}, {"id": 2, "price": 1.3}]})
+        );
+    }
+
+    #[test]
+    fn test_transform_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("etag", "W/\"ABC\"".parse().unwrap());
+        headers.insert(
+            "x-request-id",
+            "0b5e9c4a-3f1d-4c2e-9a7b-1d2e3f4a5b6c".parse().unwrap(),
+        );
+        headers.insert("server", "Nginx".parse().unwrap());
+
+        let headers = transform_headers(&headers, &transforms()).unwrap();
+        assert_eq!(headers["etag"], "w/\"abc\"");
+        assert_eq!(headers["x-request-id"], "<uuid>");
+        assert_eq!(headers["server"], "Nginx");
+    }
+}
@@ -0,0 +1,254 @@
+use super::{Normalized, Normalizer};
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use roxmltree::{Document, Node, ParsingOptions};
+use serde::{Deserialize, Serialize};
+
+/// Canonicalizes xml bodies: attributes are sorted, comments and whitespace
+/// between elements are dropped, and the elements are indented.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct XmlNormalizer {
+    /// Paths of the elements or attributes to skip, matched by local name, e.g.
+    /// `/response/meta/timestamp`, `//etag`, `//item/@updated` or `/response/*/id`.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub skip: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct XmlPath {
+    /// Whether the path starts with `//` and matches at any depth.
+    anywhere: bool,
+    steps: Vec<String>,
+    attr: Option<String>,
+}
+
+impl FromStr for XmlPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (anywhere, rest) = match s.strip_prefix("//") {
+            Some(rest) => (true, rest),
+            None => (
+                false,
+                s.strip_prefix('/')
+                    .ok_or_else(|| anyhow!("xml path {:?} must start with / or //", s))?,
+            ),
+        };
+        let mut steps: Vec<String> = rest.split('/').map(|step| step.to_string()).collect();
+        let attr = match steps.last().and_then(|step| step.strip_prefix('@')) {
+            Some(attr) => {
+                let attr = attr.to_string();
+                steps.pop();
+                Some(attr)
+            }
+            None => None,
+        };
+        if steps
+            .iter()
+            .any(|step| step.is_empty() || step.contains('@'))
+        {
+            return Err(anyhow!("invalid xml path {:?}", s));
+        }
+        Ok(Self {
+            anywhere,
+            steps,
+            attr,
+        })
+    }
+}
+
+impl XmlPath {
+    /// Whether the path of element names is matched by the steps.
+    fn matches(&self, path: &[&str]) -> bool {
+        if path.len() < self.steps.len() || (!self.anywhere && path.len() != self.steps.len()) {
+            return false;
+        }
+        let tail = &path[path.len() - self.steps.len()..];
+        tail.iter()
+            .zip(&self.steps)
+            .all(|(name, step)| step == "*" || step == name)
+    }
+}
+
+impl Normalizer for XmlNormalizer {
+    fn accepts(&self, content_type: &str) -> bool {
+        matches!(content_type, "application/xml" | "text/xml") || content_type.ends_with("+xml")
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.paths().map(|_| ())
+    }
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized> {
+        let text = std::str::from_utf8(body).context("xml body is not valid utf8")?;
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        };
+        let doc = Document::parse_with_options(text, options).context("invalid xml body")?;
+
+        let mut writer = XmlWriter {
+            skip: self.paths()?,
+            path: vec![],
+            output: String::new(),
+        };
+        let root = doc.root_element();
+        if !writer.skips(root.tag_name().name()) {
+            writer.write_element(root, 0)?;
+        }
+        Ok(Normalized {
+            body: writer.output,
+            json: None,
+        })
+    }
+}
+
+impl XmlNormalizer {
+    fn paths(&self) -> Result<Vec<XmlPath>> {
+        self.skip.iter().map(|path| path.parse()).collect()
+    }
+}
+
+struct XmlWriter<'a> {
+    skip: Vec<XmlPath>,
+    path: Vec<&'a str>,
+    output: String,
+}
+
+impl<'a> XmlWriter<'a> {
+    /// Whether the child element of the current path is skipped.
+    fn skips(&self, name: &str) -> bool {
+        let mut path = self.path.clone();
+        path.push(name);
+        self.skip
+            .iter()
+            .any(|p| p.attr.is_none() && p.matches(&path))
+    }
+
+    fn write_element(&mut self, node: Node<'a, '_>, depth: usize) -> Result<()> {
+        self.path.push(node.tag_name().name());
+        self.write_tag(node, depth)?;
+        self.path.pop();
+        Ok(())
+    }
+
+    fn write_tag(&mut self, node: Node<'a, '_>, depth: usize) -> Result<()> {
+        let indent = "  ".repeat(depth);
+        let name = qualified_name(node, node.tag_name().namespace(), node.tag_name().name());
+        write!(self.output, "{}<{}", indent, name)?;
+
+        // the namespaces declared by the element rather than inherited from its parent
+        let parent = node.parent_element();
+        let mut namespaces: Vec<String> = node
+            .namespaces()
+            .filter(|ns| parent.is_none_or(|p| !p.namespaces().any(|pns| pns == *ns)))
+            .map(|ns| match ns.name() {
+                Some(prefix) => format!(" xmlns:{}=\"{}\"", prefix, escape(ns.uri(), true)),
+                None => format!(" xmlns=\"{}\"", escape(ns.uri(), true)),
+            })
+            .collect();
+        namespaces.sort();
+
+        let mut attrs: Vec<String> = node
+            .attributes()
+            .filter(|attr| {
+                !self.skip.iter().any(|p| {
+                    p.attr
+                        .as_deref()
+                        .is_some_and(|a| a == "*" || a == attr.name())
+                        && p.matches(&self.path)
+                })
+            })
+            .map(|attr| {
+                let name = qualified_name(node, attr.namespace(), attr.name());
+                format!(" {}=\"{}\"", name, escape(attr.value(), true))
+            })
+            .collect();
+        attrs.sort();
+        self.output.push_str(&namespaces.concat());
+        self.output.push_str(&attrs.concat());
+
+        let children: Vec<Node> = node
+            .children()
+            .filter(|child| match child.text() {
+                _ if child.is_element() => !self.skips(child.tag_name().name()),
+                Some(text) => child.is_text() && !text.trim().is_empty(),
+                None => false,
+            })
+            .collect();
+        match children.as_slice() {
+            [] => writeln!(self.output, "/>")?,
+            [child] if child.is_text() => writeln!(
+                self.output,
+                ">{}</{}>",
+                escape(child.text().unwrap().trim(), false),
+                name
+            )?,
+            children => {
+                writeln!(self.output, ">")?;
+                for child in children {
+                    match child.text() {
+                        Some(text) if child.is_text() => {
+                            writeln!(self.output, "{}  {}", indent, escape(text.trim(), false))?
+                        }
+                        _ => self.write_element(*child, depth + 1)?,
+                    }
+                }
+                writeln!(self.output, "{}</{}>", indent, name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn qualified_name(node: Node, namespace: Option<&str>, name: &str) -> String {
+    match namespace.and_then(|uri| node.lookup_prefix(uri)) {
+        Some(prefix) if !prefix.is_empty() => format!("{}:{}", prefix, name),
+        _ => name.to_string(),
+    }
+}
+
+fn escape(text: &str, attr: bool) -> String {
+    let text = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    match attr {
+        true => text.replace('"', "&quot;"),
+        false => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_xml() {
+        let normalizer = XmlNormalizer {
+            skip: vec!["//timestamp".into(), "/response/items/item/@updated".into()],
+        };
+        normalizer.validate().unwrap();
+        let body = r#"<?xml version="1.0"?>
+<!-- generated -->
+<response xmlns:x="urn:x"><timestamp>1</timestamp><items>
+  <item updated="2" id="1" x:kind="a">hello &amp; bye</item>
+  <item id="2"/>
+</items><meta><timestamp>2</timestamp></meta></response>"#;
+        assert_eq!(
+            normalizer.normalize(body.as_bytes()).unwrap().body,
+            r#"<response xmlns:x="urn:x">
+  <items>
+    <item id="1" x:kind="a">hello &amp; bye</item>
+    <item id="2"/>
+  </items>
+  <meta/>
+</response>
+"#
+        );
+
+        assert!("timestamp".parse::<XmlPath>().is_err());
+        assert!("/a/@b/c".parse::<XmlPath>().is_err());
+    }
+}
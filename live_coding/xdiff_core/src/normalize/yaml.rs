@@ -0,0 +1,61 @@
+use super::{skip_json, validate_json_paths, Normalized, Normalizer};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Re-serializes yaml bodies with sorted keys, leaving out the nodes at the
+/// json paths to skip.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct YamlNormalizer {
+    /// Json paths of the nodes to skip, e.g. `metadata.generated_at`.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub skip: Vec<String>,
+}
+
+impl Normalizer for YamlNormalizer {
+    fn accepts(&self, content_type: &str) -> bool {
+        matches!(
+            content_type,
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml"
+        ) || content_type.ends_with("+yaml")
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_json_paths(&self.skip).context("yaml skip contains an invalid json path")
+    }
+
+    fn normalize(&self, body: &[u8]) -> Result<Normalized> {
+        let mut json: Value = serde_yaml::from_slice(body).context("invalid yaml body")?;
+        skip_json(&mut json, &self.skip)?;
+        // the keys of json objects are sorted
+        Ok(Normalized {
+            body: self.render_json(&json)?,
+            json: Some(json),
+        })
+    }
+
+    fn render_json(&self, json: &Value) -> Result<String> {
+        Ok(serde_yaml::to_string(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_normalize_yaml() {
+        let normalizer = YamlNormalizer {
+            skip: vec!["meta.generated_at".into()],
+        };
+        let body = "name: a\nmeta:\n  generated_at: 1\n  version: 2\nid: 1\n";
+        let normalized = normalizer.normalize(body.as_bytes()).unwrap();
+        assert_eq!(normalized.body, "id: 1\nmeta:\n  version: 2\nname: a\n");
+        assert_eq!(
+            normalized.json,
+            Some(json!({"id": 1, "meta": {"version": 2}, "name": "a"}))
+        );
+    }
+}
@@ -1,3 +1,14 @@
+pub mod auth;
+pub mod body;
+pub mod expect;
 pub mod req;
 pub mod res;
+pub mod schema;
 pub mod xdiff;
+
+pub(crate) fn is_default<T>(v: &T) -> bool
+where
+    T: Default + PartialEq,
+{
+    v == &T::default()
+}
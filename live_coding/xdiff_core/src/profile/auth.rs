@@ -0,0 +1,574 @@
+use crate::{client::HttpClients, utils::resolve_path};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use hmac::{Mac, SimpleHmac};
+use reqwest::{
+    header::{self, HeaderValue},
+    Request,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{digest::core_api::BlockSizeUser, Digest, Sha256, Sha512};
+use url::Url;
+
+/// How long before it expires a cached token is refreshed.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+/// The authentication of a request, applied when it is sent.
+///
+/// ```yaml
+/// auth: { basic: { username: admin, password: { env: ADMIN_PASSWORD } } }
+/// auth: { bearer: { file: secrets/token } }
+/// auth:
+///   oauth2:
+///     token_url: https://auth.example.com/oauth/token
+///     client_id: xdiff
+///     client_secret: { env: CLIENT_SECRET }
+///     scopes: [todos.read]
+/// auth: { hmac: { key_id: ci, secret: { env: HMAC_SECRET } } }
+/// auth:
+///   sigv4:
+///     access_key: { env: AWS_ACCESS_KEY_ID }
+///     secret_key: { env: AWS_SECRET_ACCESS_KEY }
+///     region: us-east-1
+///     service: execute-api
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Auth {
+    Basic {
+        username: String,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        password: Option<Secret>,
+    },
+    /// A static token sent as `authorization: Bearer <token>`.
+    Bearer(Secret),
+    /// A token of the OAuth2 client credentials grant, cached until it expires.
+    #[serde(rename = "oauth2")]
+    OAuth2(OAuth2),
+    /// A signature of the method, path, timestamp and body in a header.
+    Hmac(HmacSigning),
+    /// AWS signature version 4.
+    Sigv4(SigV4),
+}
+
+/// A secret given in the config, or read from an env var or a file so that the config
+/// could be committed, e.g. `{ env: API_TOKEN }` or `{ file: secrets/token }`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Secret {
+    Env { env: String },
+    File { file: PathBuf },
+    Value(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OAuth2 {
+    pub token_url: Url,
+    pub client_id: String,
+    pub client_secret: Secret,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub scopes: Vec<String>,
+    /// The audience of the token, for the providers which require it.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub audience: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct HmacSigning {
+    /// Sent in the `x-key-id` header if given, for the server to look up the secret.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub key_id: Option<String>,
+    pub secret: Secret,
+    #[serde(default)]
+    pub algorithm: HmacAlgorithm,
+    /// The header of the signature.
+    #[serde(default = "default_signature_header")]
+    pub header: String,
+    /// The header of the unix timestamp which is signed.
+    #[serde(default = "default_timestamp_header")]
+    pub timestamp_header: String,
+    #[serde(default)]
+    pub encoding: SignatureEncoding,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HmacAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SigV4 {
+    pub access_key: Secret,
+    pub secret_key: Secret,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub session_token: Option<Secret>,
+    pub region: String,
+    pub service: String,
+}
+
+fn default_signature_header() -> String {
+    "x-signature".into()
+}
+
+fn default_timestamp_header() -> String {
+    "x-timestamp".into()
+}
+
+impl Secret {
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            Secret::Env { env } => {
+                std::env::var(env).with_context(|| format!("env var {} is not set", env))
+            }
+            Secret::File { file } => Ok(std::fs::read_to_string(file)
+                .with_context(|| format!("failed to read secret file {}", file.display()))?
+                .trim_end()
+                .to_string()),
+            Secret::Value(value) => Ok(value.clone()),
+        }
+    }
+}
+
+impl Auth {
+    /// Add the credentials or the signature to the request.
+    pub async fn apply(&self, req: &mut Request, clients: &HttpClients) -> Result<()> {
+        let authorization = match self {
+            Auth::Basic { username, password } => {
+                let password = password.as_ref().map(Secret::resolve).transpose()?;
+                let credentials = format!("{}:{}", username, password.unwrap_or_default());
+                format!("Basic {}", base64::encode(credentials))
+            }
+            Auth::Bearer(token) => format!("Bearer {}", token.resolve()?),
+            Auth::OAuth2(oauth2) => format!("Bearer {}", oauth2.token(clients).await?),
+            Auth::Hmac(hmac) => return hmac.sign(req, unix_time()),
+            Auth::Sigv4(sigv4) => return sigv4.sign(req, SystemTime::now()),
+        };
+        let mut value = HeaderValue::from_str(&authorization)?;
+        value.set_sensitive(true);
+        req.headers_mut().insert(header::AUTHORIZATION, value);
+        Ok(())
+    }
+
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        let secrets = match self {
+            Auth::Basic { password, .. } => password.iter_mut().collect(),
+            Auth::Bearer(token) => vec![token],
+            Auth::OAuth2(oauth2) => vec![&mut oauth2.client_secret],
+            Auth::Hmac(hmac) => vec![&mut hmac.secret],
+            Auth::Sigv4(sigv4) => [&mut sigv4.access_key, &mut sigv4.secret_key]
+                .into_iter()
+                .chain(sigv4.session_token.as_mut())
+                .collect(),
+        };
+        for secret in secrets {
+            if let Secret::File { file } = secret {
+                resolve_path(file, dir);
+            }
+        }
+    }
+
+    /// Check the settings which do not depend on the secrets, which may only be set when sending.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Auth::Hmac(hmac) => {
+                for name in [&hmac.header, &hmac.timestamp_header] {
+                    header::HeaderName::try_from(name.as_str())
+                        .with_context(|| format!("invalid header name {}", name))?;
+                }
+            }
+            Auth::Sigv4(sigv4) if sigv4.region.is_empty() || sigv4.service.is_empty() => {
+                return Err(anyhow!("sigv4 requires a region and a service"));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Drop the cached token after the server rejects it, returns whether there was any.
+    pub fn invalidate(&self, clients: &HttpClients) -> bool {
+        match self {
+            Auth::OAuth2(oauth2) => clients.tokens.remove(&oauth2.cache_key()),
+            _ => false,
+        }
+    }
+}
+
+/// The OAuth2 tokens fetched by the requests of a config, by client and scopes.
+#[derive(Debug, Clone, Default)]
+pub struct TokenCache(Arc<Mutex<HashMap<String, (String, Instant)>>>);
+
+impl TokenCache {
+    fn get(&self, key: &str) -> Option<String> {
+        let tokens = self.0.lock().unwrap();
+        let (token, expires_at) = tokens.get(key)?;
+        (Instant::now() + TOKEN_REFRESH_MARGIN < *expires_at).then(|| token.clone())
+    }
+
+    fn insert(&self, key: String, token: String, expires_at: Instant) {
+        self.0.lock().unwrap().insert(key, (token, expires_at));
+    }
+
+    fn remove(&self, key: &str) -> bool {
+        self.0.lock().unwrap().remove(key).is_some()
+    }
+}
+
+impl OAuth2 {
+    fn cache_key(&self) -> String {
+        format!(
+            "{} {} {}",
+            self.token_url,
+            self.client_id,
+            self.scopes.join(" ")
+        )
+    }
+
+    async fn token(&self, clients: &HttpClients) -> Result<String> {
+        let key = self.cache_key();
+        if let Some(token) = clients.tokens.get(&key) {
+            return Ok(token);
+        }
+
+        let secret = self.client_secret.resolve()?;
+        let mut form = vec![
+            ("grant_type", "client_credentials".to_string()),
+            ("client_id", self.client_id.clone()),
+            ("client_secret", secret),
+        ];
+        if !self.scopes.is_empty() {
+            form.push(("scope", self.scopes.join(" ")));
+        }
+        if let Some(ref audience) = self.audience {
+            form.push(("audience", audience.clone()));
+        }
+        let client = clients.get(None)?;
+        let req = client
+            .client
+            .post(self.token_url.clone())
+            .form(&form)
+            .build()?;
+        let res = client
+            .execute(req)
+            .await
+            .with_context(|| format!("failed to request a token from {}", self.token_url))?;
+        let status = res.status();
+        let body: Value =
+            serde_json::from_slice(&res.bytes().await?).context("invalid token response")?;
+        if !status.is_success() {
+            return Err(anyhow!(
+                "token request to {} failed with {}: {}",
+                self.token_url,
+                status,
+                body
+            ));
+        }
+
+        let token = body["access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow!("no access_token in the token response"))?
+            .to_string();
+        // tokens without an expiry are only used once
+        let expires_in = body["expires_in"].as_u64().unwrap_or_default();
+        let expires_at = Instant::now() + Duration::from_secs(expires_in);
+        clients.tokens.insert(key, token.clone(), expires_at);
+        Ok(token)
+    }
+}
+
+impl HmacSigning {
+    /// Sign `<method>\n<path and query>\n<timestamp>\n<hex sha256 of the body>`.
+    fn sign(&self, req: &mut Request, timestamp: u64) -> Result<()> {
+        let url = req.url();
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let payload = format!(
+            "{}\n{}\n{}\n{}",
+            req.method(),
+            path,
+            timestamp,
+            hex::encode(Sha256::digest(body_bytes(req)?))
+        );
+
+        let secret = self.secret.resolve()?;
+        let signature = match self.algorithm {
+            HmacAlgorithm::Sha256 => hmac::<Sha256>(secret.as_bytes(), payload.as_bytes()),
+            HmacAlgorithm::Sha512 => hmac::<Sha512>(secret.as_bytes(), payload.as_bytes()),
+        };
+        let signature = match self.encoding {
+            SignatureEncoding::Hex => hex::encode(signature),
+            SignatureEncoding::Base64 => base64::encode(signature),
+        };
+
+        let headers = req.headers_mut();
+        headers.insert(
+            header::HeaderName::try_from(self.timestamp_header.as_str())?,
+            timestamp.into(),
+        );
+        headers.insert(
+            header::HeaderName::try_from(self.header.as_str())?,
+            HeaderValue::from_str(&signature)?,
+        );
+        if let Some(ref key_id) = self.key_id {
+            headers.insert("x-key-id", HeaderValue::from_str(key_id)?);
+        }
+        Ok(())
+    }
+}
+
+impl SigV4 {
+    fn sign(&self, req: &mut Request, now: SystemTime) -> Result<()> {
+        let (date, datetime) = amz_date(now);
+        let payload_hash = hex::encode(Sha256::digest(body_bytes(req)?));
+        let host = match (req.url().host_str(), req.url().port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(anyhow!("url {} has no host", req.url())),
+        };
+
+        let headers = req.headers_mut();
+        headers.insert(header::HOST, HeaderValue::from_str(&host)?);
+        headers.insert("x-amz-date", HeaderValue::from_str(&datetime)?);
+        if self.service == "s3" {
+            headers.insert(
+                "x-amz-content-sha256",
+                HeaderValue::from_str(&payload_hash)?,
+            );
+        }
+        if let Some(ref token) = self.session_token {
+            headers.insert(
+                "x-amz-security-token",
+                HeaderValue::from_str(&token.resolve()?)?,
+            );
+        }
+
+        let mut signed: Vec<(String, String)> = req
+            .headers()
+            .iter()
+            .filter(|(k, _)| {
+                let k = k.as_str();
+                k == "host" || k == "content-type" || k.starts_with("x-amz-")
+            })
+            .map(|(k, v)| Ok((k.to_string(), v.to_str()?.trim().to_string())))
+            .collect::<Result<_>>()?;
+        signed.sort();
+        let signed_headers: Vec<&str> = signed.iter().map(|(k, _)| k.as_str()).collect();
+        let signed_headers = signed_headers.join(";");
+        let canonical_headers: String = signed
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v))
+            .collect();
+
+        let mut query: Vec<(String, String)> = req
+            .url()
+            .query_pairs()
+            .map(|(k, v)| (uri_encode(&k), uri_encode(&v)))
+            .collect();
+        query.sort();
+        let query: Vec<String> = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            req.method(),
+            req.url().path(),
+            query.join("&"),
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            datetime,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let secret = format!("AWS4{}", self.secret_key.resolve()?);
+        let key = [date.as_str(), &self.region, &self.service, "aws4_request"]
+            .iter()
+            .fold(secret.into_bytes(), |key, part| {
+                hmac::<Sha256>(&key, part.as_bytes())
+            });
+        let signature = hex::encode(hmac::<Sha256>(&key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key.resolve()?,
+            scope,
+            signed_headers,
+            signature
+        );
+        let mut value = HeaderValue::from_str(&authorization)?;
+        value.set_sensitive(true);
+        req.headers_mut().insert(header::AUTHORIZATION, value);
+        Ok(())
+    }
+}
+
+fn hmac<D: Digest + BlockSizeUser>(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = SimpleHmac::<D>::new_from_slice(key).expect("hmac accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn body_bytes(req: &Request) -> Result<&[u8]> {
+    match req.body() {
+        Some(body) => body
+            .as_bytes()
+            .ok_or_else(|| anyhow!("streamed bodies could not be signed")),
+        None => Ok(&[]),
+    }
+}
+
+/// Percent-encode everything but the unreserved characters, as AWS requires.
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// The date and time in UTC as `20150830` and `20150830T123600Z`.
+fn amz_date(time: SystemTime) -> (String, String) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // the civil date of the days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let datetime = format!(
+        "{}T{:02}{:02}{:02}Z",
+        date,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+    (date, datetime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Method;
+
+    fn auth(yaml: &str) -> Auth {
+        serde_yaml::with::singleton_map_recursive::deserialize(serde_yaml::Deserializer::from_str(
+            yaml,
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_basic_and_bearer() {
+        std::env::set_var("XDIFF_TEST_PASSWORD", "secret");
+        let clients = HttpClients::default();
+        let mut req = Request::new(Method::GET, "http://localhost/".parse().unwrap());
+
+        let basic = auth("basic: { username: admin, password: { env: XDIFF_TEST_PASSWORD } }");
+        basic.apply(&mut req, &clients).await.unwrap();
+        assert_eq!(req.headers()["authorization"], "Basic YWRtaW46c2VjcmV0");
+
+        auth("bearer: abc").apply(&mut req, &clients).await.unwrap();
+        assert_eq!(req.headers()["authorization"], "Bearer abc");
+
+        let missing = auth("bearer: { env: XDIFF_TEST_MISSING }");
+        assert!(missing.apply(&mut req, &clients).await.is_err());
+    }
+
+    #[test]
+    fn test_sign_hmac() {
+        let signing = match auth("hmac: { key_id: ci, secret: key }") {
+            Auth::Hmac(hmac) => hmac,
+            _ => unreachable!(),
+        };
+        let mut req = Request::new(Method::GET, "http://localhost/todos?a=1".parse().unwrap());
+        signing.sign(&mut req, 1700000000).unwrap();
+
+        let payload = format!(
+            "GET\n/todos?a=1\n1700000000\n{}",
+            hex::encode(Sha256::digest(b""))
+        );
+        let expected = hex::encode(hmac::<Sha256>(b"key", payload.as_bytes()));
+        assert_eq!(req.headers()["x-signature"], expected.as_str());
+        assert_eq!(req.headers()["x-timestamp"], "1700000000");
+        assert_eq!(req.headers()["x-key-id"], "ci");
+    }
+
+    #[test]
+    fn test_sign_sigv4() {
+        // the example of the AWS docs signing a request to IAM
+        let sigv4 = match auth(
+            r#"
+sigv4:
+  access_key: AKIDEXAMPLE
+  secret_key: wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY
+  region: us-east-1
+  service: iam
+"#,
+        ) {
+            Auth::Sigv4(sigv4) => sigv4,
+            _ => unreachable!(),
+        };
+        let url = "https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08";
+        let mut req = Request::new(Method::GET, url.parse().unwrap());
+        req.headers_mut().insert(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded; charset=utf-8"
+                .parse()
+                .unwrap(),
+        );
+        let now = UNIX_EPOCH + Duration::from_secs(1440938160);
+        assert_eq!(amz_date(now).1, "20150830T123600Z");
+
+        sigv4.sign(&mut req, now).unwrap();
+        assert_eq!(
+            req.headers()["authorization"],
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+}
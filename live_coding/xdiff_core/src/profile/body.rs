@@ -0,0 +1,382 @@
+use crate::utils::resolve_path;
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use url::form_urlencoded;
+
+/// The body of a request.
+///
+/// A plain value is sent as json. The other encodings are given by a yaml tag:
+///
+/// ```yaml
+/// body: !form { name: alice }
+/// body: !text hello
+/// body: !base64 aGVsbG8=
+/// body: !multipart
+///   - { name: title, value: report }
+///   - { name: file, file: ./report.pdf, content_type: application/pdf }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Body {
+    Json(Value),
+    /// Fields sent as `application/x-www-form-urlencoded`.
+    Form(Map<String, Value>),
+    /// Parts sent as `multipart/form-data`.
+    Multipart(Vec<Part>),
+    /// Raw text, sent as `text/plain` unless a content type header is given.
+    Text(String),
+    /// Binary content encoded in base64, sent as `application/octet-stream`
+    /// unless a content type header is given.
+    Base64(String),
+}
+
+/// A part of a multipart body, with either a text value or the content of a file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Part {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub value: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub file: Option<PathBuf>,
+
+    /// The file name sent for a file, defaults to the name of the file.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub filename: Option<String>,
+
+    /// Defaults to `application/octet-stream` for a file.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub content_type: Option<String>,
+}
+
+impl Body {
+    /// A plain mapping sent with a form or multipart content type header, as profiles had
+    /// before the typed bodies, is encoded as the content type says.
+    pub fn for_content_type(self, content_type: Option<&str>) -> Body {
+        match (content_type, self) {
+            (Some("application/x-www-form-urlencoded"), Body::Json(Value::Object(fields))) => {
+                Body::Form(fields)
+            }
+            (Some("multipart/form-data"), Body::Json(Value::Object(fields))) => Body::Multipart(
+                fields
+                    .iter()
+                    .map(|(name, value)| Part::text(name, &to_text(value)))
+                    .collect(),
+            ),
+            (_, body) => body,
+        }
+    }
+
+    /// The content type sent when the request has no content type header.
+    pub fn default_content_type(&self) -> &'static str {
+        match self {
+            Body::Json(_) => "application/json",
+            Body::Form(_) => "application/x-www-form-urlencoded",
+            Body::Multipart(_) => "multipart/form-data",
+            Body::Text(_) => "text/plain",
+            Body::Base64(_) => "application/octet-stream",
+        }
+    }
+
+    /// Set the fields of a json object, form or multipart body, e.g. from `-o @key=value`.
+    pub fn set_field(&mut self, key: &str, value: &str) -> Result<()> {
+        match self {
+            Body::Json(Value::Object(obj)) => {
+                obj.insert(key.to_string(), parse_value(value));
+            }
+            Body::Form(fields) => {
+                fields.insert(key.to_string(), Value::String(value.to_string()));
+            }
+            Body::Multipart(parts) => {
+                parts.retain(|p| p.name != key);
+                parts.push(Part::text(key, value));
+            }
+            _ => {
+                return Err(anyhow!(
+                    "only json object, form and multipart bodies have fields"
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        if let Body::Multipart(parts) = self {
+            for file in parts.iter_mut().filter_map(|part| part.file.as_mut()) {
+                resolve_path(file, dir);
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Body::Json(_) | Body::Text(_) => {}
+            Body::Form(fields) => {
+                for (k, v) in fields {
+                    if v.is_object() || v.is_array() {
+                        return Err(anyhow!("form field {} must be a scalar", k));
+                    }
+                }
+            }
+            Body::Multipart(parts) => {
+                for part in parts {
+                    if let Some(ref content_type) = part.content_type {
+                        if content_type.contains(['\r', '\n']) {
+                            return Err(anyhow!(
+                                "content type of part {} must be a single line",
+                                part.name
+                            ));
+                        }
+                    }
+                    match (&part.value, &part.file) {
+                        (Some(_), None) => {}
+                        (None, Some(file)) => {
+                            if !file.is_file() {
+                                return Err(anyhow!(
+                                    "file {} of part {} not found",
+                                    file.display(),
+                                    part.name
+                                ));
+                            }
+                        }
+                        _ => {
+                            return Err(anyhow!(
+                                "part {} must have either a value or a file",
+                                part.name
+                            ))
+                        }
+                    }
+                }
+            }
+            Body::Base64(data) => {
+                base64::decode(data).context("invalid base64 body")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode the body. Returns the content type to send if it is decided
+    /// by the encoding, e.g. the boundary of a multipart body.
+    pub async fn encode(&self) -> Result<(Option<String>, Vec<u8>)> {
+        match self {
+            Body::Json(value) => Ok((None, serde_json::to_vec(value)?)),
+            Body::Form(fields) => Ok((None, encode_form(fields).into_bytes())),
+            Body::Text(text) => Ok((None, text.clone().into_bytes())),
+            Body::Base64(data) => Ok((None, base64::decode(data)?)),
+            Body::Multipart(parts) => {
+                let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+                let boundary = format!("xdiff-boundary-{:x}", nanos);
+                let content_type = format!("multipart/form-data; boundary={}", boundary);
+                Ok((
+                    Some(content_type),
+                    encode_multipart(parts, &boundary).await?,
+                ))
+            }
+        }
+    }
+}
+
+impl Part {
+    pub fn text(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: Some(value.to_string()),
+            file: None,
+            filename: None,
+            content_type: None,
+        }
+    }
+
+    /// The file name sent for a file part.
+    pub fn filename(&self) -> Option<String> {
+        self.filename.clone().or_else(|| {
+            self.file
+                .as_deref()
+                .and_then(Path::file_name)
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+    }
+}
+
+/// Parse a value given in cli args as json, or keep it as a string.
+pub fn parse_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+pub fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+pub fn encode_form(fields: &Map<String, Value>) -> String {
+    let mut form = form_urlencoded::Serializer::new(String::new());
+    for (k, v) in fields {
+        form.append_pair(k, &to_text(v));
+    }
+    form.finish()
+}
+
+async fn encode_multipart(parts: &[Part], boundary: &str) -> Result<Vec<u8>> {
+    let mut body = vec![];
+    for part in parts {
+        body.extend(format!("--{}\r\n", boundary).into_bytes());
+        let disposition = format!(
+            "Content-Disposition: form-data; name=\"{}\"",
+            escape_quoted(&part.name)
+        );
+        match (&part.value, &part.file) {
+            (_, Some(file)) => {
+                let content = tokio::fs::read(file)
+                    .await
+                    .with_context(|| format!("failed to read {}", file.display()))?;
+                let filename = escape_quoted(&part.filename().unwrap_or_default());
+                let content_type = part
+                    .content_type
+                    .as_deref()
+                    .unwrap_or("application/octet-stream");
+                body.extend(
+                    format!(
+                        "{}; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                        disposition, filename, content_type
+                    )
+                    .into_bytes(),
+                );
+                body.extend(content);
+            }
+            (value, None) => {
+                body.extend(format!("{}\r\n", disposition).into_bytes());
+                if let Some(ref content_type) = part.content_type {
+                    body.extend(format!("Content-Type: {}\r\n", content_type).into_bytes());
+                }
+                body.extend(format!("\r\n{}", value.as_deref().unwrap_or_default()).into_bytes());
+            }
+        }
+        body.extend(b"\r\n");
+    }
+    body.extend(format!("--{}--\r\n", boundary).into_bytes());
+    Ok(body)
+}
+
+/// Escape a name in the quotes of a multipart header as browsers do, so that quotes and
+/// line breaks could not end the header.
+fn escape_quoted(s: &str) -> String {
+    s.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Serialize a json body as a plain value, and the other encodings with their yaml tag.
+pub fn serialize<S: Serializer>(body: &Option<Body>, serializer: S) -> Result<S::Ok, S::Error> {
+    match body {
+        Some(Body::Json(value)) => value.serialize(serializer),
+        body => body.serialize(serializer),
+    }
+}
+
+/// A tagged value is read as the encoding of its tag, any other value as json.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Body>, D::Error> {
+    let value = Option::<serde_yaml::Value>::deserialize(deserializer)?;
+    let body = match value {
+        None | Some(serde_yaml::Value::Null) => return Ok(None),
+        Some(value @ serde_yaml::Value::Tagged(_)) => serde_yaml::from_value(value),
+        Some(value) => serde_yaml::from_value(value).map(Body::Json),
+    };
+    body.map(Some).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Wrapper {
+        #[serde(with = "super", default)]
+        body: Option<Body>,
+    }
+
+    fn parse(yaml: &str) -> Option<Body> {
+        serde_yaml::from_str::<Wrapper>(yaml).unwrap().body
+    }
+
+    #[test]
+    fn test_parse_body() {
+        assert_eq!(
+            parse("body: { title: a }"),
+            Some(Body::Json(json!({"title": "a"})))
+        );
+        assert_eq!(parse("body: !json [1, 2]"), Some(Body::Json(json!([1, 2]))));
+        assert_eq!(
+            parse("body: !form { a: 1 }"),
+            Some(Body::Form(json!({"a": 1}).as_object().unwrap().clone()))
+        );
+        assert_eq!(parse("body: !text hi"), Some(Body::Text("hi".into())));
+        assert_eq!(parse("{}"), None);
+
+        // a plain mapping whose only key names an encoding is still json
+        for key in ["json", "form", "multipart", "text", "base64"] {
+            let body = Body::Json(json!({ key: "hi" }));
+            assert_eq!(
+                parse(&format!("body: {{ {}: hi }}", key)),
+                Some(body.clone())
+            );
+            let yaml = serde_yaml::to_string(&Wrapper {
+                body: Some(body.clone()),
+            })
+            .unwrap();
+            assert_eq!(parse(&yaml), Some(body));
+        }
+
+        let body = parse("body: !multipart [{ name: a, value: x }]").unwrap();
+        let yaml = serde_yaml::to_string(&Wrapper {
+            body: Some(body.clone()),
+        })
+        .unwrap();
+        assert_eq!(yaml, "body: !multipart\n- name: a\n  value: x\n");
+        assert_eq!(parse(&yaml), Some(body));
+        assert!(serde_yaml::from_str::<Wrapper>("body: !xml <a/>").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encode_body() {
+        let form = parse("body: !form { q: a b, n: 1 }").unwrap();
+        assert_eq!(form.encode().await.unwrap().1, b"n=1&q=a+b");
+
+        let binary = Body::Base64("aGVsbG8=".into());
+        assert_eq!(binary.encode().await.unwrap().1, b"hello");
+        assert!(Body::Base64("!!".into()).validate().is_err());
+
+        let multipart = parse("body: !multipart [{ name: a, value: x }]").unwrap();
+        let (content_type, encoded) = multipart.encode().await.unwrap();
+        let boundary = content_type
+            .unwrap()
+            .split("boundary=")
+            .nth(1)
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            String::from_utf8(encoded).unwrap(),
+            format!(
+                "--{b}\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nx\r\n--{b}--\r\n",
+                b = boundary
+            )
+        );
+
+        let invalid = parse("body: !multipart [{ name: a }]").unwrap();
+        assert!(invalid.validate().is_err());
+
+        // quotes and line breaks could not end the header of a part
+        let multipart = Body::Multipart(vec![Part::text("a\"\r\nX-Injected: 1", "x")]);
+        let encoded = String::from_utf8(multipart.encode().await.unwrap().1).unwrap();
+        assert!(encoded.contains("name=\"a%22%0D%0AX-Injected: 1\"\r\n"));
+    }
+}
@@ -0,0 +1,391 @@
+use super::res::ResponseExt;
+use crate::utils::json_path::JsonPath;
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Assertions on the response of a request, e.g.
+///
+/// ```yaml
+/// expect:
+///   status: [2xx, 304]
+///   headers: [content-type]
+///   forbidden_headers: [server]
+///   body:
+///     - { path: $.id, equals: 1 }
+///     - { path: $.title, regex: "^delectus" }
+///     - { path: $.tags, type: array }
+///   max_latency_ms: 500
+///   max_body_size: 10240
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Expect {
+    /// Accepted status codes: `200`, `2xx` or `200-299`.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub status: Vec<StatusRange>,
+
+    /// Headers which must be present.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub headers: Vec<String>,
+
+    /// Headers which must not be present.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub forbidden_headers: Vec<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub body: Vec<BodyAssertion>,
+
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max_latency_ms: Option<u64>,
+
+    /// The maximum size of the body in bytes.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max_body_size: Option<usize>,
+}
+
+/// An inclusive range of status codes.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "StatusValue", into = "StatusValue")]
+pub struct StatusRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum StatusValue {
+    Code(u16),
+    Text(String),
+}
+
+/// A check on the body nodes selected by a json path.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BodyAssertion {
+    pub path: String,
+    #[serde(flatten)]
+    pub check: BodyCheck,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyCheck {
+    Equals(Value),
+    /// A substring of a string, an element of an array or a key of an object.
+    Contains(Value),
+    Regex(String),
+    Type(JsonType),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonType {
+    Null,
+    Boolean,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+/// The outcome of one assertion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertionResult {
+    pub name: String,
+    /// Why the assertion failed, `None` if it passed.
+    pub failure: Option<String>,
+}
+
+impl AssertionResult {
+    fn new(name: impl Into<String>, failure: Option<String>) -> Self {
+        Self {
+            name: name.into(),
+            failure,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+impl FromStr for StatusRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let err = || anyhow!("invalid status {:?}, expect e.g. 200, 2xx or 200-299", s);
+        let s = s.trim();
+        let (start, end) = if let Some(class) = s.strip_suffix("xx") {
+            let class: u16 = class.parse().map_err(|_| err())?;
+            (class * 100, class * 100 + 99)
+        } else if let Some((start, end)) = s.split_once('-') {
+            let start = start.trim().parse().map_err(|_| err())?;
+            let end = end.trim().parse().map_err(|_| err())?;
+            (start, end)
+        } else {
+            let code = s.parse().map_err(|_| err())?;
+            (code, code)
+        };
+        if !(100..=599).contains(&start) || !(100..=599).contains(&end) || start > end {
+            return Err(err());
+        }
+        Ok(Self { start, end })
+    }
+}
+
+impl TryFrom<StatusValue> for StatusRange {
+    type Error = anyhow::Error;
+
+    fn try_from(value: StatusValue) -> Result<Self> {
+        match value {
+            StatusValue::Code(code) => code.to_string().parse(),
+            StatusValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+impl From<StatusRange> for StatusValue {
+    fn from(range: StatusRange) -> Self {
+        if range.start == range.end {
+            StatusValue::Code(range.start)
+        } else {
+            StatusValue::Text(range.to_string())
+        }
+    }
+}
+
+impl fmt::Display for StatusRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else if self.start.is_multiple_of(100) && self.end == self.start + 99 {
+            write!(f, "{}xx", self.start / 100)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+impl JsonType {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => JsonType::Null,
+            Value::Bool(_) => JsonType::Boolean,
+            Value::Number(_) => JsonType::Number,
+            Value::String(_) => JsonType::String,
+            Value::Array(_) => JsonType::Array,
+            Value::Object(_) => JsonType::Object,
+        }
+    }
+}
+
+impl fmt::Display for JsonType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = serde_json::to_value(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", name.as_str().unwrap_or_default())
+    }
+}
+
+impl BodyAssertion {
+    fn name(&self) -> String {
+        match &self.check {
+            BodyCheck::Equals(v) => format!("{} equals {}", self.path, v),
+            BodyCheck::Contains(v) => format!("{} contains {}", self.path, v),
+            BodyCheck::Regex(re) => format!("{} matches /{}/", self.path, re),
+            BodyCheck::Type(t) => format!("{} is {}", self.path, t),
+        }
+    }
+
+    fn check(&self, json: Option<&Value>) -> Option<String> {
+        let Some(json) = json else {
+            return Some("body is not json".to_string());
+        };
+        let path: JsonPath = self.path.parse().ok()?;
+        let nodes = path.select(json);
+        if nodes.is_empty() {
+            return Some("not found".to_string());
+        }
+        nodes
+            .into_iter()
+            .find(|node| !self.check_node(node))
+            .map(|node| format!("got {}", node))
+    }
+
+    fn check_node(&self, node: &Value) -> bool {
+        match &self.check {
+            BodyCheck::Equals(expected) => node == expected,
+            BodyCheck::Contains(expected) => match (node, expected) {
+                (Value::String(s), Value::String(sub)) => s.contains(sub.as_str()),
+                (Value::Array(arr), _) => arr.contains(expected),
+                (Value::Object(obj), Value::String(key)) => obj.contains_key(key),
+                _ => false,
+            },
+            BodyCheck::Regex(re) => {
+                let text = match node {
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                Regex::new(re).is_ok_and(|re| re.is_match(&text))
+            }
+            BodyCheck::Type(t) => JsonType::of(node) == *t,
+        }
+    }
+}
+
+impl Expect {
+    pub fn is_empty(&self) -> bool {
+        self == &Expect::default()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for assertion in &self.body {
+            assertion
+                .path
+                .parse::<JsonPath>()
+                .context("body assertion contains an invalid json path")?;
+            if let BodyCheck::Regex(ref re) = assertion.check {
+                Regex::new(re).with_context(|| format!("invalid regex {:?}", re))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluate the assertions against the response. The latency is the total time of
+    /// the request itself, without its pre-requests and retries.
+    pub fn evaluate(&self, res: &ResponseExt) -> Vec<AssertionResult> {
+        let mut results = vec![];
+
+        if !self.status.is_empty() {
+            let code = res.status.as_u16();
+            let accepted: Vec<String> = self.status.iter().map(|s| s.to_string()).collect();
+            let passed = self
+                .status
+                .iter()
+                .any(|s| (s.start..=s.end).contains(&code));
+            results.push(AssertionResult::new(
+                format!("status is {}", accepted.join(" or ")),
+                (!passed).then(|| format!("got {}", code)),
+            ));
+        }
+
+        for key in &self.headers {
+            results.push(AssertionResult::new(
+                format!("header {} is present", key),
+                (!res.headers.contains_key(key.as_str())).then(|| "missing".to_string()),
+            ));
+        }
+        for key in &self.forbidden_headers {
+            results.push(AssertionResult::new(
+                format!("header {} is absent", key),
+                res.headers
+                    .get(key.as_str())
+                    .map(|v| format!("got {:?}", v)),
+            ));
+        }
+
+        if !self.body.is_empty() {
+            let json: Option<Value> = serde_json::from_str(&res.body).ok();
+            for assertion in &self.body {
+                results.push(AssertionResult::new(
+                    assertion.name(),
+                    assertion.check(json.as_ref()),
+                ));
+            }
+        }
+
+        if let Some(max) = self.max_latency_ms {
+            let ms = res.metrics.total_ms;
+            results.push(AssertionResult::new(
+                format!("latency <= {}ms", max),
+                (ms > max as f64).then(|| format!("took {:.0}ms", ms)),
+            ));
+        }
+        if let Some(max) = self.max_body_size {
+            let size = res.body_bytes().map_or(res.body.len(), |b| b.len());
+            results.push(AssertionResult::new(
+                format!("body size <= {} bytes", max),
+                (size > max).then(|| format!("got {} bytes", size)),
+            ));
+        }
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::res::Metrics;
+    use reqwest::{header::HeaderMap, StatusCode, Version};
+
+    #[test]
+    fn test_parse_status_range() {
+        for (input, expected) in [
+            ("200", (200, 200)),
+            ("2xx", (200, 299)),
+            ("400-403", (400, 403)),
+        ] {
+            let range: StatusRange = input.parse().unwrap();
+            assert_eq!((range.start, range.end), expected);
+            assert_eq!(range.to_string(), input);
+        }
+
+        for input in ["", "abc", "9xx", "300-200", "2000"] {
+            assert!(input.parse::<StatusRange>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_expect() {
+        let expect: Expect = serde_yaml::from_str(
+            r#"
+status: [2xx]
+headers: [content-type]
+forbidden_headers: [server]
+body:
+  - { path: $.id, equals: 1 }
+  - { path: $.title, contains: world }
+  - { path: $.title, regex: "^hello" }
+  - { path: $.tags, contains: b }
+  - { path: $.tags, type: object }
+  - { path: $.missing, type: string }
+max_latency_ms: 10
+max_body_size: 10
+"#,
+        )
+        .unwrap();
+        expect.validate().unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().unwrap());
+        let res = ResponseExt {
+            version: Version::HTTP_11,
+            status: StatusCode::CREATED,
+            headers,
+            body: r#"{"id": 1, "title": "hello world", "tags": ["a", "b"]}"#.to_string(),
+            binary: false,
+            metrics: Metrics {
+                total_ms: 12.4,
+                ..Default::default()
+            },
+        };
+
+        let failures: Vec<(String, Option<String>)> = expect
+            .evaluate(&res)
+            .into_iter()
+            .filter(|r| !r.passed())
+            .map(|r| (r.name, r.failure))
+            .collect();
+        assert_eq!(
+            failures,
+            vec![
+                ("$.tags is object".into(), Some(r#"got ["a","b"]"#.into())),
+                ("$.missing is string".into(), Some("not found".into())),
+                ("latency <= 10ms".into(), Some("took 12ms".into())),
+                ("body size <= 10 bytes".into(), Some("got 53 bytes".into())),
+            ]
+        );
+    }
+}
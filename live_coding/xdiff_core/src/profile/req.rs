@@ -1,13 +1,25 @@
-use super::res::ResponseExt;
+use super::{
+    auth::Auth,
+    body::{self, parse_value, Body},
+    expect::Expect,
+    res::ResponseExt,
+    schema::SchemaFile,
+};
 use crate::cli::OverrideArgs;
+use crate::client::{ClientConfig, HttpClients};
+use crate::utils::{json_path::JsonPath, resolve_path, template::render_template};
 
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    str::FromStr,
+};
 
-use anyhow::{anyhow, Ok, Result};
+use anyhow::{anyhow, Context, Ok, Result};
 use http::header;
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
-    Client, Method,
+    Method, StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -18,7 +30,9 @@ pub struct RequestProfile {
     #[serde(with = "http_serde::method", default)]
     pub method: Method,
 
-    pub url: Url,
+    /// The url may contain `{{ var }}` placeholders of captured values, so it is
+    /// only parsed once they are rendered.
+    pub url: String,
 
     #[serde(skip_serializing_if = "is_empty_json_value", default)]
     pub params: Option<serde_json::Value>,
@@ -30,14 +44,63 @@ pub struct RequestProfile {
     )]
     pub headers: HeaderMap,
 
+    #[serde(skip_serializing_if = "Option::is_none", with = "body", default)]
+    pub body: Option<Body>,
+
+    /// Requests sent in order before this one. The values they capture
+    /// could be used as `{{ var }}` in this request.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub pre: Vec<RequestProfile>,
+
+    /// Values captured from the response into variables for the later requests.
+    #[serde(
+        with = "serde_yaml::with::singleton_map_recursive",
+        skip_serializing_if = "BTreeMap::is_empty",
+        default
+    )]
+    pub capture: BTreeMap<String, Capture>,
+
+    /// How the request is authenticated, with secrets from env vars or files.
+    #[serde(
+        with = "serde_yaml::with::singleton_map_recursive",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub auth: Option<Auth>,
+
+    /// Overrides of the top-level client settings for this request.
     #[serde(skip_serializing_if = "Option::is_none", default)]
-    pub body: Option<serde_json::Value>,
+    pub client: Option<ClientConfig>,
+
+    /// Assertions on the response, evaluated by `xreq run`.
+    #[serde(skip_serializing_if = "Expect::is_empty", default)]
+    pub expect: Expect,
+
+    /// A JSON Schema file in json or yaml which the body of the response is validated against.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub schema: Option<SchemaFile>,
+
+    /// Response headers left out of the output of `xreq run`.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub skip_headers: Vec<String>,
 }
 
+/// Where a variable is captured from in a response.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Capture {
+    /// A json path into the body, e.g. `{ body: $.access_token }`.
+    Body(String),
+    /// A header name, e.g. `{ header: x-request-id }`.
+    Header(String),
+}
+
+/// The values captured by the requests sent so far.
+pub type Captures = HashMap<String, String>;
+
 fn is_empty_json_value(v: &Option<serde_json::Value>) -> bool {
-    v.as_ref().map_or(true, |v| {
-        v.is_null() || (v.is_object() && v.as_object().unwrap().is_empty())
-    })
+    v.as_ref()
+        .is_none_or(|v| v.is_null() || (v.is_object() && v.as_object().unwrap().is_empty()))
 }
 
 impl FromStr for RequestProfile {
@@ -65,72 +128,248 @@ impl FromStr for RequestProfile {
 impl RequestProfile {
     pub fn new(
         method: Method,
-        url: Url,
+        url: impl Into<String>,
         params: Option<serde_json::Value>,
         headers: HeaderMap,
-        body: Option<serde_json::Value>,
+        body: Option<Body>,
     ) -> Self {
         Self {
             method,
-            url,
+            url: url.into(),
             params,
             headers,
             body,
+            pre: vec![],
+            capture: BTreeMap::new(),
+            client: None,
+            expect: Expect::default(),
+            skip_headers: vec![],
+            auth: None,
+            schema: None,
         }
     }
 
-    pub async fn send(&self, args: &OverrideArgs) -> Result<ResponseExt> {
-        let (headers, query, body) = self.generate(args)?;
-        let client = Client::new();
-        let req = client
-            .request(self.method.clone(), self.url.clone())
+    /// Parse the url, which must not contain placeholders any more.
+    pub fn parse_url(&self) -> Result<Url> {
+        Url::parse(&self.url).with_context(|| format!("invalid url {}", self.url))
+    }
+
+    pub async fn send(&self, clients: &HttpClients, args: &OverrideArgs) -> Result<ResponseExt> {
+        self.send_with(clients, args, &mut Captures::new()).await
+    }
+
+    /// Send the pre-requests and then this request. The `{{ var }}` placeholders are
+    /// resolved from the captures, and the values captured by every request are added to them.
+    pub async fn send_with(
+        &self,
+        clients: &HttpClients,
+        args: &OverrideArgs,
+        captures: &mut Captures,
+    ) -> Result<ResponseExt> {
+        let chain = self.chain();
+        let mut res = None;
+        for (idx, req) in chain.iter().enumerate() {
+            let sent = req.render(captures)?.execute(clients, args).await;
+            let sent = if idx + 1 < chain.len() {
+                sent.with_context(|| format!("pre-request to {} failed", req.url))?
+            } else {
+                sent?
+            };
+            req.capture_into(&sent, captures)?;
+            res = Some(sent);
+        }
+        Ok(res.expect("the chain contains at least this request"))
+    }
+
+    /// Send the pre-requests and resolve this request with what they captured, so that
+    /// it could be sent repeatedly without them.
+    pub async fn prepare(
+        &self,
+        clients: &HttpClients,
+        args: &OverrideArgs,
+        captures: &mut Captures,
+    ) -> Result<RequestProfile> {
+        let chain = self.chain();
+        for req in &chain[..chain.len() - 1] {
+            let sent = req
+                .render(captures)?
+                .execute(clients, args)
+                .await
+                .with_context(|| format!("pre-request to {} failed", req.url))?;
+            req.capture_into(&sent, captures)?;
+        }
+        self.render(captures)
+    }
+
+    /// The pre-requests in the order to send, followed by this request.
+    pub fn chain(&self) -> Vec<&RequestProfile> {
+        let mut chain: Vec<&RequestProfile> = self.pre.iter().flat_map(|pre| pre.chain()).collect();
+        chain.push(self);
+        chain
+    }
+
+    /// Resolve the `{{ var }}` placeholders left in the profile with the captured values.
+    fn render(&self, captures: &Captures) -> Result<RequestProfile> {
+        let mut req = self.clone();
+        req.pre.clear();
+        // through yaml, which keeps the tags of the typed bodies
+        let mut value = serde_yaml::to_value(&req)?;
+        let mut missing = vec![];
+        render_value(&mut value, captures, &mut missing);
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "variables not captured by any previous request: {}",
+                missing.join(", ")
+            ));
+        }
+        Ok(serde_yaml::from_value(value)?)
+    }
+
+    fn capture_into(&self, res: &ResponseExt, captures: &mut Captures) -> Result<()> {
+        let mut json = None;
+        for (name, capture) in &self.capture {
+            let value = match capture {
+                Capture::Header(key) => res
+                    .headers
+                    .get(key)
+                    .ok_or_else(|| anyhow!("header {} not found to capture {}", key, name))?
+                    .to_str()?
+                    .to_string(),
+                Capture::Body(path) => {
+                    if json.is_none() {
+                        json = Some(
+                            serde_json::from_str::<serde_json::Value>(&res.body)
+                                .with_context(|| format!("failed to capture {}", name))?,
+                        );
+                    }
+                    let path: JsonPath = path.parse()?;
+                    match path.select(json.as_ref().unwrap()).first() {
+                        Some(serde_json::Value::String(s)) => s.clone(),
+                        Some(v) => v.to_string(),
+                        None => {
+                            return Err(anyhow!("{} not found in body to capture {}", path, name))
+                        }
+                    }
+                }
+            };
+            captures.insert(name.clone(), value);
+        }
+        Ok(())
+    }
+
+    async fn execute(&self, clients: &HttpClients, args: &OverrideArgs) -> Result<ResponseExt> {
+        let (mut headers, query, body) = self.generate(args)?;
+        let body = match body {
+            Some(body) => {
+                let (content_type, encoded) = body.encode().await?;
+                if let Some(content_type) = content_type {
+                    headers.insert(header::CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+                }
+                encoded
+            }
+            None => vec![],
+        };
+
+        let client = clients.get(self.client.as_ref())?;
+        let mut req = client
+            .client
+            .request(self.method.clone(), self.parse_url()?)
             .headers(headers)
             .query(&query)
             .body(body)
             .build()?;
-        let res = client.execute(req).await?;
-        Ok(ResponseExt(res))
+        let retry = self.auth.as_ref().and_then(|_| req.try_clone());
+        if let Some(ref auth) = self.auth {
+            auth.apply(&mut req, clients).await?;
+        }
+        let mut sent = client.execute_timed(req).await?;
+        // the cached token could be revoked before it expires, so fetch a new one
+        if let (Some(auth), Some(mut req)) = (&self.auth, retry) {
+            if sent.0.status() == StatusCode::UNAUTHORIZED && auth.invalidate(clients) {
+                auth.apply(&mut req, clients).await?;
+                sent = client.execute_timed(req).await?;
+            }
+        }
+        let (res, timings) = sent;
+        ResponseExt::from_response(res, timings).await
     }
 
-    pub fn generate(&self, args: &OverrideArgs) -> Result<(HeaderMap, serde_json::Value, String)> {
+    /// Apply the override args to the headers, query and body of the request.
+    /// The content type header is set by the kind of body if it is not given.
+    pub fn generate(
+        &self,
+        args: &OverrideArgs,
+    ) -> Result<(HeaderMap, serde_json::Value, Option<Body>)> {
         let mut headers = self.headers.clone();
         let mut query = self.params.clone().unwrap_or_else(|| json!({}));
-        let mut body = self.body.clone().unwrap_or_else(|| json!({}));
+        let mut body = self.body.clone();
 
         for (k, v) in &args.headers {
             headers.insert(HeaderName::from_str(k)?, HeaderValue::from_str(v)?);
         }
 
-        if !headers.contains_key(header::CONTENT_TYPE) {
-            headers.insert(
-                header::CONTENT_TYPE,
-                HeaderValue::from_static("application/json"),
-            );
-        }
-
         for (k, v) in &args.query {
-            query[k] = v.parse()?;
+            query[k] = parse_value(v);
         }
 
-        for (k, v) in &args.body {
-            body[k] = v.parse()?;
+        if !args.body.is_empty() {
+            let body = body.get_or_insert_with(|| Body::Json(json!({})));
+            for (k, v) in &args.body {
+                body.set_field(k, v)?;
+            }
         }
 
-        let content_type = get_content_type(&headers);
-        match content_type.as_deref() {
-            Some("application/json") => {
-                let body = serde_json::to_string(&body)?;
-                Ok((headers, query, body))
-            }
-            Some("applications/x-www-form-urlencoded" | "multipart/form-data") => {
-                let body = serde_urlencoded::to_string(&body)?;
-                Ok((headers, query, body))
+        if let Some(ref mut body) = body {
+            let content_type = get_content_type(&headers);
+            if content_type.is_none() {
+                headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(body.default_content_type()),
+                );
             }
-            _ => Err(anyhow!("Unsupported content type")),
+            *body = body.clone().for_content_type(content_type.as_deref());
+        }
+
+        Ok((headers, query, body))
+    }
+
+    /// Make the paths of the files of the request relative to the directory of its config.
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        for pre in &mut self.pre {
+            pre.resolve_paths(dir);
+        }
+        if let Some(ref mut body) = self.body {
+            body.resolve_paths(dir);
+        }
+        if let Some(ref mut auth) = self.auth {
+            auth.resolve_paths(dir);
+        }
+        if let Some(ref mut client) = self.client {
+            client.resolve_paths(dir);
+        }
+        if let Some(ref mut schema) = self.schema {
+            resolve_path(&mut schema.path, dir);
         }
     }
 
     pub fn validate(&self) -> Result<()> {
+        for (idx, pre) in self.pre.iter().enumerate() {
+            pre.validate()
+                .with_context(|| format!("pre-request {} failed to validate", idx))?;
+        }
+        for (name, capture) in &self.capture {
+            if let Capture::Body(path) = capture {
+                path.parse::<JsonPath>()
+                    .with_context(|| format!("invalid capture {}", name))?;
+            }
+        }
+        self.expect
+            .validate()
+            .context("expect failed to validate")?;
+        // a url with placeholders is parsed when they are rendered
+        if !self.url.contains("{{") {
+            self.parse_url()?;
+        }
         if let Some(ref params) = self.params {
             if !params.is_object() {
                 return Err(anyhow!(
@@ -140,19 +379,130 @@ impl RequestProfile {
             }
         }
         if let Some(ref body) = self.body {
-            if !body.is_object() {
+            let content_type = get_content_type(&self.headers);
+            let body = body.clone().for_content_type(content_type.as_deref());
+            if matches!(
+                (content_type.as_deref(), &body),
+                (
+                    Some("application/x-www-form-urlencoded" | "multipart/form-data"),
+                    Body::Json(_)
+                )
+            ) {
                 return Err(anyhow!(
-                    "Body must be an object but got:\n{}",
-                    serde_yaml::to_string(body)?
+                    "a json body sent as {} must be a mapping",
+                    content_type.unwrap()
                 ));
             }
+            body.validate().context("body failed to validate")?;
+        }
+        if let Some(ref auth) = self.auth {
+            auth.validate().context("auth failed to validate")?;
+        }
+        if let Some(ref schema) = self.schema {
+            schema.load()?;
         }
         Ok(())
     }
 }
 
+fn render_value(value: &mut serde_yaml::Value, captures: &Captures, missing: &mut Vec<String>) {
+    match value {
+        serde_yaml::Value::String(s) if s.contains("{{") => {
+            let (output, unresolved) = render_template(s, |name| captures.get(name).cloned());
+            missing.extend(unresolved);
+            *s = output;
+        }
+        serde_yaml::Value::Sequence(seq) => {
+            for v in seq {
+                render_value(v, captures, missing);
+            }
+        }
+        serde_yaml::Value::Mapping(map) => {
+            for (_, v) in map.iter_mut() {
+                render_value(v, captures, missing);
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => render_value(&mut tagged.value, captures, missing),
+        _ => {}
+    }
+}
+
 pub fn get_content_type(headers: &HeaderMap) -> Option<String> {
     headers
         .get(header::CONTENT_TYPE)
         .and_then(|v| v.to_str().unwrap().split(';').next().map(|s| s.to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_captures() {
+        let req: RequestProfile = serde_yaml::from_str(
+            r#"
+url: http://{{ host }}/items/{{ id }}?q=a%20b
+headers:
+  authorization: Bearer {{ token }}
+body: !text "100%7B%7B"
+"#,
+        )
+        .unwrap();
+        let captures: Captures = [("host", "localhost:8080"), ("id", "42"), ("token", "abc")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let rendered = req.render(&captures).unwrap();
+        assert_eq!(
+            rendered.parse_url().unwrap().as_str(),
+            "http://localhost:8080/items/42?q=a%20b"
+        );
+        assert_eq!(rendered.headers["authorization"], "Bearer abc");
+        assert_eq!(rendered.body, Some(Body::Text("100%7B%7B".into())));
+        assert!(req.validate().is_ok());
+        assert!(req.render(&Captures::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_bodies() {
+        let mut req: RequestProfile = serde_yaml::from_str(
+            r#"
+url: http://localhost/upload
+headers:
+  content-type: multipart/form-data
+body:
+  title: report
+  pages: 2
+"#,
+        )
+        .unwrap();
+        req.validate().unwrap();
+
+        let (headers, _, body) = req.generate(&OverrideArgs::default()).unwrap();
+        assert_eq!(headers[header::CONTENT_TYPE], "multipart/form-data");
+        let body = body.unwrap();
+        assert_eq!(
+            body,
+            Body::Multipart(vec![
+                body::Part::text("pages", "2"),
+                body::Part::text("title", "report"),
+            ])
+        );
+        // the boundary is added to the content type when the body is encoded
+        let (content_type, _) = body.encode().await.unwrap();
+        assert!(content_type
+            .unwrap()
+            .starts_with("multipart/form-data; boundary="));
+
+        req.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        let (_, _, body) = req.generate(&OverrideArgs::default()).unwrap();
+        assert!(matches!(body, Some(Body::Form(_))));
+
+        req.body = Some(Body::Json(json!([1, 2])));
+        assert!(req.validate().is_err());
+    }
+}
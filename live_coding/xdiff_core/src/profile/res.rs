@@ -1,8 +1,20 @@
-use super::req::get_content_type;
-use std::fmt::Write;
+use super::{is_default, req::get_content_type, schema::SchemaFile};
+use crate::client::AttemptTimings;
+use crate::normalize::{
+    csv::CsvNormalizer,
+    html::HtmlNormalizer,
+    protobuf::ProtobufNormalizer,
+    skip_json,
+    transform::{transform_headers, transform_json, transform_text, Transform},
+    xml::XmlNormalizer,
+    yaml::YamlNormalizer,
+    JsonNormalizer, Normalized, Normalizer,
+};
+use crate::utils::resolve_path;
+use std::{fmt::Write, path::Path, time::Duration};
 
-use anyhow::{Ok, Result};
-use reqwest::Response;
+use anyhow::{Context, Ok, Result};
+use reqwest::{header::HeaderMap, Response, StatusCode, Version};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
@@ -10,8 +22,164 @@ pub struct ResponseProfile {
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub skip_headers: Vec<String>,
 
+    /// Json paths of the body nodes to skip, e.g. `data.meta.request_id`,
+    /// `items[*].updated_at` or `$..etag`.
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub skip_body: Vec<String>,
+
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub diff_mode: DiffMode,
+
+    /// Rewrites of the volatile parts of the headers and body, applied before diffing.
+    #[serde(
+        with = "serde_yaml::with::singleton_map_recursive",
+        skip_serializing_if = "Vec::is_empty",
+        default
+    )]
+    pub transforms: Vec<Transform>,
+
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub xml: XmlNormalizer,
+
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub html: HtmlNormalizer,
+
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub yaml: YamlNormalizer,
+
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub csv: CsvNormalizer,
+
+    /// Protobuf bodies are only decoded if a descriptor set is given.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub protobuf: Option<ProtobufNormalizer>,
+
+    /// A JSON Schema file in json or yaml which the bodies of both responses are validated
+    /// against, as the contract of the endpoints.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub schema: Option<SchemaFile>,
+
+    /// How much slower or larger the response may be than the one of req1.
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub threshold: Threshold,
+}
+
+/// Regressions which count as a difference even if the responses are the same, in percent
+/// of req1, e.g. `{ slower: 20 }` for the total time of req2 being at most 20% longer.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Threshold {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub slower: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub larger: Option<u32>,
+}
+
+impl Threshold {
+    /// The thresholds exceeded by the second response. Responses without any timing,
+    /// e.g. snapshots recorded before they were measured, are not compared by time.
+    pub fn check(&self, metrics1: &Metrics, metrics2: &Metrics) -> Vec<String> {
+        let mut exceeded = vec![];
+        if let Some(slower) = self.slower {
+            let (t1, t2) = (metrics1.total_ms, metrics2.total_ms);
+            if t1 > 0.0 && t2 > t1 * (1.0 + slower as f64 / 100.0) {
+                exceeded.push(format!(
+                    "total time {:.1}ms -> {:.1}ms ({}), over the {}% threshold",
+                    t1,
+                    t2,
+                    change(t1, t2),
+                    slower
+                ));
+            }
+        }
+        if let Some(larger) = self.larger {
+            let (s1, s2) = (metrics1.size as f64, metrics2.size as f64);
+            if s2 > s1 * (1.0 + larger as f64 / 100.0) {
+                exceeded.push(format!(
+                    "size {}B -> {}B ({}), over the {}% threshold",
+                    metrics1.size,
+                    metrics2.size,
+                    change(s1, s2),
+                    larger
+                ));
+            }
+        }
+        exceeded
+    }
+}
+
+/// The relative change from one value to another, e.g. `+25%`.
+pub fn change(from: f64, to: f64) -> String {
+    match from > 0.0 {
+        true => format!("{:+.0}%", (to - from) / from * 100.0),
+        false => "new".into(),
+    }
+}
+
+/// How long a request took and how large its body is. The time to resolve the host is
+/// only known if the request opened a new connection, the time to connect is never known.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Metrics {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub dns_ms: Option<f64>,
+    /// Not measured, as reqwest has no hook into its connector; kept so the snapshots and
+    /// reports have a place for it.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub connect_ms: Option<f64>,
+    /// The time until the response headers are received.
+    #[serde(default)]
+    pub ttfb_ms: f64,
+    /// The time until the whole body is received, including the redirects. Only the
+    /// attempt which got the response is measured if the request was retried.
+    #[serde(default)]
+    pub total_ms: f64,
+    /// The size of the body in bytes.
+    #[serde(default)]
+    pub size: usize,
+}
+
+impl Metrics {
+    pub fn new(timings: AttemptTimings, total: Duration, size: usize) -> Self {
+        Self {
+            dns_ms: timings.dns.map(millis),
+            connect_ms: None,
+            ttfb_ms: millis(timings.ttfb),
+            total_ms: millis(total),
+            size,
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// The metrics of the two responses of a diff, and the thresholds the second one exceeds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsDiff {
+    pub res1: Metrics,
+    pub res2: Metrics,
+    pub exceeded: Vec<String>,
+}
+
+impl MetricsDiff {
+    pub fn new(threshold: &Threshold, res1: &ResponseExt, res2: &ResponseExt) -> Self {
+        Self {
+            exceeded: threshold.check(&res1.metrics, &res2.metrics),
+            res1: res1.metrics.clone(),
+            res2: res2.metrics.clone(),
+        }
+    }
+}
+
+/// How the two responses are compared.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffMode {
+    /// Line based diff of the formatted responses.
+    #[default]
+    Text,
+    /// Structural diff of the json bodies, reported by json path.
+    Json,
 }
 
 impl ResponseProfile {
@@ -19,47 +187,224 @@ impl ResponseProfile {
         Self {
             skip_headers,
             skip_body,
+            ..Default::default()
+        }
+    }
+
+    /// The normalizers of the bodies, by the content types they accept.
+    pub fn normalizers(&self) -> Vec<&dyn Normalizer> {
+        let mut normalizers: Vec<&dyn Normalizer> =
+            vec![&self.xml, &self.html, &self.yaml, &self.csv];
+        if let Some(ref protobuf) = self.protobuf {
+            normalizers.push(protobuf);
+        }
+        normalizers
+    }
+
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        if let Some(ref mut protobuf) = self.protobuf {
+            resolve_path(&mut protobuf.descriptor_set, dir);
+        }
+        if let Some(ref mut schema) = self.schema {
+            resolve_path(&mut schema.path, dir);
         }
     }
+
+    pub fn validate(&self) -> Result<()> {
+        self.json_normalizer().validate()?;
+        for normalizer in self.normalizers() {
+            normalizer.validate()?;
+        }
+        for transform in &self.transforms {
+            transform.validate().context("invalid transform")?;
+        }
+        if let Some(ref schema) = self.schema {
+            schema.load()?;
+        }
+        Ok(())
+    }
+
+    fn json_normalizer(&self) -> JsonNormalizer<'_> {
+        JsonNormalizer {
+            skip: &self.skip_body,
+        }
+    }
+}
+
+/// The filtered parts of a response which are compared.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseParts {
+    /// The status line followed by the remaining headers.
+    pub headers: String,
+    /// The body, normalized by its content type.
+    pub body: String,
+    /// The filtered body if the response is json.
+    pub json: Option<serde_json::Value>,
+}
+
+impl ResponseParts {
+    pub fn text(&self) -> String {
+        format!("{}{}\n", self.headers, self.body)
+    }
 }
 
-/// A Response Wrapper
-#[derive(Debug)]
-pub struct ResponseExt(pub Response);
+/// A Response Wrapper which holds the whole response in memory,
+/// so it could be recorded as a snapshot and replayed later.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResponseExt {
+    #[serde(with = "http_serde::version")]
+    pub version: Version,
+
+    #[serde(with = "http_serde::status_code")]
+    pub status: StatusCode,
+
+    #[serde(with = "http_serde::header_map")]
+    pub headers: HeaderMap,
+
+    pub body: String,
+
+    /// The body is encoded in base64 as it is not valid utf8, e.g. protobuf.
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub binary: bool,
+
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub metrics: Metrics,
+}
 
 impl ResponseExt {
-    pub async fn get_text(self, profile: &ResponseProfile) -> Result<String> {
-        let mut output = filter_headers(&self.0, &profile.skip_headers)?;
-
-        let content_type = get_content_type(self.0.headers());
-        let text = self.0.text().await?;
-        match content_type.as_deref() {
-            Some("application/json") => {
-                let text = filter_json(&text, &profile.skip_body)?;
-                writeln!(&mut output, "{}", text)?;
-            }
-            _ => {
-                writeln!(&mut output, "{}", text)?;
+    /// Read the body of a response, timed from the start of the attempt which got it.
+    pub async fn from_response(res: Response, timings: AttemptTimings) -> Result<Self> {
+        let version = res.version();
+        let status = res.status();
+        let headers = res.headers().clone();
+        let bytes = res.bytes().await?;
+        let metrics = Metrics::new(timings, timings.start.elapsed(), bytes.len());
+        let (body, binary) = match String::from_utf8(bytes.to_vec()) {
+            Result::Ok(text) => (text, false),
+            Err(_) => (base64::encode(&bytes), true),
+        };
+        Ok(Self {
+            version,
+            status,
+            headers,
+            body,
+            binary,
+            metrics,
+        })
+    }
+
+    /// The raw bytes of the body.
+    pub fn body_bytes(&self) -> Result<Vec<u8>> {
+        match self.binary {
+            true => Ok(base64::decode(&self.body)?),
+            false => Ok(self.body.clone().into_bytes()),
+        }
+    }
+
+    pub fn get_text(&self, profile: &ResponseProfile) -> Result<String> {
+        Ok(self.get_parts(profile)?.text())
+    }
+
+    /// Get the filtered headers and body of the response.
+    pub fn get_parts(&self, profile: &ResponseProfile) -> Result<ResponseParts> {
+        let headers = filter_headers(self, &profile.skip_headers, &profile.transforms)?;
+
+        let json_normalizer = profile.json_normalizer();
+        let mut normalizers = profile.normalizers();
+        normalizers.insert(0, &json_normalizer);
+
+        let content_type = get_content_type(&self.headers).unwrap_or_default();
+        let normalizer = normalizers.into_iter().find(|n| n.accepts(&content_type));
+        let Normalized { mut body, mut json } = match normalizer {
+            Some(normalizer) => normalizer.normalize(&self.body_bytes()?)?,
+            None => Normalized {
+                body: self.body.clone(),
+                json: None,
+            },
+        };
+
+        if !profile.transforms.is_empty() {
+            match (normalizer, json.as_mut()) {
+                (Some(normalizer), Some(json)) => {
+                    transform_json(json, &profile.transforms)?;
+                    body = normalizer.render_json(json)?;
+                }
+                _ => body = transform_text(&body, &profile.transforms)?,
             }
         }
-        Ok(output)
+
+        Ok(ResponseParts {
+            headers,
+            body,
+            json,
+        })
     }
 
     pub fn get_header_keys(&self) -> Vec<String> {
-        self.0
-            .headers()
+        self.headers.iter().map(|(k, _)| k.to_string()).collect()
+    }
+
+    /// The status line, e.g. `HTTP/1.1 200 OK`.
+    pub fn status_line(&self) -> String {
+        format!("{:?} {}", self.version, self.status)
+    }
+
+    /// The headers except the skipped ones, with the values which aren't visible ascii escaped.
+    pub fn visible_headers(&self, skip_headers: &[String]) -> Vec<(String, String)> {
+        self.headers
             .iter()
-            .map(|(k, _)| k.to_string())
+            .filter(|(k, _)| {
+                !skip_headers
+                    .iter()
+                    .any(|s| s.eq_ignore_ascii_case(k.as_str()))
+            })
+            .map(|(k, v)| {
+                let value = match v.to_str() {
+                    Result::Ok(v) => v.to_string(),
+                    Err(_) => format!("{:?}", v),
+                };
+                (k.to_string(), value)
+            })
             .collect()
     }
+
+    /// The body formatted by its content type like when it is diffed, or as it is if it
+    /// can't be parsed. `None` for a binary body.
+    pub fn pretty_body(&self) -> Option<String> {
+        if self.binary {
+            return None;
+        }
+        let parts = self.get_parts(&ResponseProfile::default());
+        Some(parts.map_or_else(|_| self.body.clone(), |parts| parts.body))
+    }
+
+    /// The extension of the syntax to highlight the body with, by its content type.
+    pub fn syntax(&self) -> Option<&'static str> {
+        let content_type = get_content_type(&self.headers)?;
+        let syntax = match content_type.as_str() {
+            "application/json" => "json",
+            "text/html" | "application/xhtml+xml" => "html",
+            "application/xml" | "text/xml" => "xml",
+            "application/yaml" | "application/x-yaml" | "text/yaml" => "yaml",
+            "application/javascript" | "text/javascript" => "js",
+            "text/css" => "css",
+            ct if ct.ends_with("+json") => "json",
+            ct if ct.ends_with("+xml") => "xml",
+            _ => return None,
+        };
+        Some(syntax)
+    }
 }
 
-pub fn filter_headers(res: &Response, skip_headers: &[String]) -> Result<String> {
+pub fn filter_headers(
+    res: &ResponseExt,
+    skip_headers: &[String],
+    transforms: &[Transform],
+) -> Result<String> {
     let mut output = String::new();
-    writeln!(&mut output, "\n{:?} {:?}", res.version(), res.status())?;
-    let headers = res.headers();
+    writeln!(&mut output, "\n{:?} {:?}", res.version, res.status)?;
 
-    for (k, v) in headers.iter() {
+    for (k, v) in transform_headers(&res.headers, transforms)?.iter() {
         if !skip_headers.contains(&k.to_string()) {
             writeln!(&mut output, "{}: {:?}", k, v)?;
         }
@@ -69,10 +414,76 @@ pub fn filter_headers(res: &Response, skip_headers: &[String]) -> Result<String>
 
 pub fn filter_json(text: &str, skip_body: &[String]) -> Result<String> {
     let mut json: serde_json::Value = serde_json::from_str(text)?;
-    if let serde_json::Value::Object(ref mut obj) = json {
-        for k in skip_body {
-            obj.remove(k);
-        }
-    }
+    skip_json(&mut json, skip_body)?;
     Ok(serde_json::to_string_pretty(&json)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_response() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "content-type",
+            "application/json; charset=utf-8".parse().unwrap(),
+        );
+        headers.insert("date", "Sun, 18 Oct 2026 10:00:00 GMT".parse().unwrap());
+        let res = ResponseExt {
+            version: Version::HTTP_11,
+            status: StatusCode::OK,
+            headers,
+            body: r#"{"id":1,"tags":["a"]}"#.into(),
+            binary: false,
+            metrics: Metrics::default(),
+        };
+
+        assert_eq!(res.status_line(), "HTTP/1.1 200 OK");
+        assert_eq!(
+            res.visible_headers(&["Date".into()]),
+            vec![(
+                "content-type".into(),
+                "application/json; charset=utf-8".into()
+            )]
+        );
+        assert_eq!(
+            res.pretty_body().unwrap(),
+            "{\n  \"id\": 1,\n  \"tags\": [\n    \"a\"\n  ]\n}"
+        );
+        assert_eq!(res.syntax(), Some("json"));
+
+        let binary = ResponseExt {
+            binary: true,
+            ..res
+        };
+        assert_eq!(binary.pretty_body(), None);
+    }
+
+    #[test]
+    fn test_threshold() {
+        let metrics = |total_ms, size| Metrics {
+            total_ms,
+            size,
+            ..Default::default()
+        };
+        let threshold = Threshold {
+            slower: Some(20),
+            larger: Some(50),
+        };
+
+        assert!(threshold
+            .check(&metrics(100.0, 100), &metrics(119.0, 150))
+            .is_empty());
+        assert_eq!(
+            threshold.check(&metrics(100.0, 100), &metrics(130.0, 151)),
+            vec![
+                "total time 100.0ms -> 130.0ms (+30%), over the 20% threshold",
+                "size 100B -> 151B (+51%), over the 50% threshold"
+            ]
+        );
+        assert!(threshold
+            .check(&metrics(0.0, 100), &metrics(130.0, 100))
+            .is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use super::res::ResponseExt;
+use crate::utils::Cached;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use jsonschema::{paths::LocationSegment, Validator};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A JSON Schema loaded from a json or yaml file, which the json bodies of responses
+/// are validated against.
+#[derive(Debug)]
+pub struct Schema {
+    path: PathBuf,
+    validator: Validator,
+}
+
+/// The schema file of a profile, compiled once when the config is validated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SchemaFile {
+    pub path: PathBuf,
+    #[serde(skip)]
+    schema: Cached<Schema>,
+}
+
+/// A part of a body which doesn't match the schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    /// The json path of the part, e.g. `$.items[0].id`.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Schema {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read schema {}", path.display()))?;
+        let schema: Value = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_str(&content)?,
+            _ => serde_json::from_str(&content)?,
+        };
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| anyhow!("invalid schema {}: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            validator,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn validate(&self, json: &Value) -> Vec<Violation> {
+        self.validator
+            .iter_errors(json)
+            .map(|e| Violation {
+                path: json_path(&e.instance_path),
+                message: e.to_string(),
+            })
+            .collect()
+    }
+
+    /// Validate the body of a response, which violates the schema as a whole if it isn't json.
+    pub fn validate_response(&self, res: &ResponseExt) -> Vec<Violation> {
+        let json = match res.binary {
+            true => Err("body is binary".to_string()),
+            false => serde_json::from_str(&res.body).map_err(|e| e.to_string()),
+        };
+        match json {
+            Ok(json) => self.validate(&json),
+            Err(e) => vec![Violation {
+                path: "$".into(),
+                message: format!("not json: {}", e),
+            }],
+        }
+    }
+}
+
+impl SchemaFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            schema: Cached::default(),
+        }
+    }
+
+    /// The compiled schema, which is only read from the file the first time.
+    pub fn load(&self) -> Result<&Schema> {
+        self.schema.get_or_load(|| Schema::load(&self.path))
+    }
+}
+
+/// Validate the response against every schema file, skipping the files given twice.
+pub fn validate_response(res: &ResponseExt, schemas: &[&SchemaFile]) -> Result<Vec<Violation>> {
+    let mut violations = vec![];
+    for (idx, file) in schemas.iter().enumerate() {
+        if schemas[..idx].iter().any(|other| other.path == file.path) {
+            continue;
+        }
+        violations.extend(file.load()?.validate_response(res));
+    }
+    Ok(violations)
+}
+
+fn json_path<'a>(location: impl IntoIterator<Item = LocationSegment<'a>>) -> String {
+    let mut path = String::from("$");
+    for segment in location {
+        match segment {
+            LocationSegment::Property(name) => {
+                let plain =
+                    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                match plain {
+                    true => path.push_str(&format!(".{}", name)),
+                    false => path.push_str(&format!("[{:?}]", name)),
+                }
+            }
+            LocationSegment::Index(idx) => path.push_str(&format!("[{}]", idx)),
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schema.yaml");
+        std::fs::write(
+            &path,
+            r#"
+type: object
+required: [id, items]
+properties:
+  id: { type: integer }
+  items:
+    type: array
+    items:
+      type: object
+      properties:
+        "content-type": { type: string }
+"#,
+        )
+        .unwrap();
+        let schema = Schema::load(&path).unwrap();
+
+        assert!(schema
+            .validate(&json!({ "id": 1, "items": [{ "content-type": "a" }] }))
+            .is_empty());
+        let violations = schema.validate(&json!({ "id": "1", "items": [{ "content-type": 1 }] }));
+        let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            violations,
+            [
+                r#"$.id: "1" is not of type "integer""#,
+                r#"$.items[0]["content-type"]: 1 is not of type "string""#
+            ]
+        );
+
+        // compiled once, so it doesn't need the file any more
+        let file = SchemaFile::new(&path);
+        file.load().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(file.load().is_ok());
+        assert!(SchemaFile::new(&path).load().is_err());
+    }
+}
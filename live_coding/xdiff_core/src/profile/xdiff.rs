@@ -1,45 +1,357 @@
-use super::{req::RequestProfile, res::ResponseProfile};
-use crate::utils::diff_text;
-use crate::{cli::OverrideArgs, config::ValidateConfig};
+use super::{
+    is_default,
+    req::{Captures, RequestProfile},
+    res::{DiffMode, MetricsDiff, ResponseExt, ResponseParts, ResponseProfile},
+    schema::{validate_response, SchemaFile, Violation},
+};
+use crate::utils::{
+    json_diff::{diff_json, JsonChange},
+    resolve_path,
+};
+use crate::{
+    cli::OverrideArgs, client::HttpClients, config::ValidateConfig, snapshot::load_snapshot,
+};
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Ok, Result};
-use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Context, Ok, Result};
+use indexmap::IndexMap;
+use serde::{de, Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DiffProfile {
+    /// Requests sent before req1 and the targets, e.g. to login.
+    /// The values they capture could be used in all of them.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub pre: Vec<RequestProfile>,
+    /// The baseline which req2 and the targets are compared against.
     pub req1: RequestProfile,
-    pub req2: RequestProfile,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub req2: Option<DiffTarget>,
+    /// Named targets compared against req1, e.g. the regions or canaries of a rollout.
+    #[serde(skip_serializing_if = "IndexMap::is_empty", default)]
+    pub targets: IndexMap<String, DiffTarget>,
     #[serde(skip_serializing_if = "is_default", default)]
     pub res: ResponseProfile,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
 }
 
-fn is_default<T>(v: &T) -> bool
-where
-    T: Default + PartialEq,
-{
-    v == &T::default()
+/// The second side of a diff, either a live request or a recorded response.
+#[derive(Debug, Serialize, Clone)]
+#[serde(untagged)]
+pub enum DiffTarget {
+    Request(Box<RequestProfile>),
+    /// A snapshot file written by `record`, e.g. `req2: { snapshot: snapshots/todo.yaml }`.
+    Snapshot {
+        snapshot: PathBuf,
+    },
 }
 
+/// A mapping whose only key is `snapshot` is a snapshot, anything else is a request,
+/// so that the errors of an invalid request are reported as they are.
+impl<'de> Deserialize<'de> for DiffTarget {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_yaml::Value::deserialize(deserializer)?;
+        let is_snapshot = value
+            .as_mapping()
+            .is_some_and(|m| m.len() == 1 && m.contains_key("snapshot"));
+        let target = match is_snapshot {
+            true => serde_yaml::from_value(value["snapshot"].clone())
+                .map(|snapshot| DiffTarget::Snapshot { snapshot }),
+            false => serde_yaml::from_value(value).map(|req| DiffTarget::Request(Box::new(req))),
+        };
+        target.map_err(de::Error::custom)
+    }
+}
+
+impl DiffTarget {
+    pub async fn send_with(
+        &self,
+        clients: &HttpClients,
+        args: &OverrideArgs,
+        captures: &mut Captures,
+    ) -> Result<ResponseExt> {
+        match self {
+            DiffTarget::Request(req) => req.send_with(clients, args, captures).await,
+            DiffTarget::Snapshot { snapshot } => load_snapshot(snapshot).await,
+        }
+    }
+
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        match self {
+            DiffTarget::Request(req) => req.resolve_paths(dir),
+            DiffTarget::Snapshot { snapshot } => resolve_path(snapshot, dir),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            DiffTarget::Request(req) => req.validate(),
+            DiffTarget::Snapshot { .. } => Ok(()),
+        }
+    }
+
+    /// The schema of the request, snapshots are only validated against the one of the profile.
+    pub fn schema(&self) -> Option<&SchemaFile> {
+        match self {
+            DiffTarget::Request(req) => req.schema.as_ref(),
+            DiffTarget::Snapshot { .. } => None,
+        }
+    }
+}
+
+impl From<RequestProfile> for DiffTarget {
+    fn from(req: RequestProfile) -> Self {
+        DiffTarget::Request(Box::new(req))
+    }
+}
+
+/// The name of req2 among the targets.
+pub const REQ2: &str = "req2";
+
 impl ValidateConfig for DiffProfile {
     fn validate(&self) -> Result<()> {
+        for (idx, pre) in self.pre.iter().enumerate() {
+            pre.validate()
+                .with_context(|| format!("pre-request {} failed to validate", idx))?;
+        }
         self.req1.validate().context("req1 failed to validate")?;
-        self.req2.validate().context("req2 failed to validate")?;
+        if self.req2.is_none() && self.targets.is_empty() {
+            return Err(anyhow!("either req2 or targets is required"));
+        }
+        if self.req2.is_some() && self.targets.contains_key(REQ2) {
+            return Err(anyhow!("a target must not be named req2 if req2 is given"));
+        }
+        for (name, target) in self.targets() {
+            target
+                .validate()
+                .with_context(|| format!("{} failed to validate", name))?;
+        }
+        self.res.validate().context("res failed to validate")?;
         Ok(())
     }
 }
 
 impl DiffProfile {
+    /// Make the paths of the files of the profile relative to the directory of its config.
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        for pre in &mut self.pre {
+            pre.resolve_paths(dir);
+        }
+        self.req1.resolve_paths(dir);
+        for target in self.req2.iter_mut().chain(self.targets.values_mut()) {
+            target.resolve_paths(dir);
+        }
+        self.res.resolve_paths(dir);
+    }
+
     pub fn new(req1: RequestProfile, req2: RequestProfile, res: ResponseProfile) -> Self {
-        Self { req1, req2, res }
+        Self {
+            pre: vec![],
+            req1,
+            req2: Some(req2.into()),
+            targets: IndexMap::new(),
+            res,
+            tags: vec![],
+        }
+    }
+
+    /// The targets which are diffed against req1, req2 first if it is given.
+    pub fn targets(&self) -> Vec<(&str, &DiffTarget)> {
+        self.req2
+            .iter()
+            .map(|req2| (REQ2, req2))
+            .chain(self.targets.iter().map(|(name, t)| (name.as_str(), t)))
+            .collect()
+    }
+
+    /// Diff req1 against every target, or only the named one if given.
+    ///
+    /// The pre-requests and req1 are sent once, every target gets its own copy of
+    /// the captured values. A failing target doesn't fail the others.
+    pub async fn diff(
+        &self,
+        clients: &HttpClients,
+        args: OverrideArgs,
+        only: Option<&str>,
+    ) -> Result<Vec<(String, Result<DiffResult>)>> {
+        let targets: Vec<_> = self
+            .targets()
+            .into_iter()
+            .filter(|(name, _)| only.is_none_or(|only| only == *name))
+            .collect();
+        if let (Some(only), true) = (only, targets.is_empty()) {
+            return Err(anyhow!("target {} not found", only));
+        }
+
+        let mut captures = self.send_pre(clients, &args).await?;
+        let res1 = self.req1.send_with(clients, &args, &mut captures).await?;
+        let mut results = vec![];
+        for (name, target) in targets {
+            let mut captures = captures.clone();
+            let result = async {
+                let res2 = target.send_with(clients, &args, &mut captures).await?;
+                self.compare(&res1, &res2, target.schema())
+            }
+            .await;
+            results.push((name.to_string(), result));
+        }
+        Ok(results)
+    }
+
+    /// Diff the live response of req1 against a recorded snapshot of it.
+    pub async fn verify(
+        &self,
+        clients: &HttpClients,
+        args: OverrideArgs,
+        snapshot: &ResponseExt,
+    ) -> Result<DiffResult> {
+        let res = self.send_req1(clients, &args).await?;
+        self.compare(snapshot, &res, self.req1.schema.as_ref())
     }
 
-    pub async fn diff(&self, args: OverrideArgs) -> Result<String> {
-        let res1 = self.req1.send(&args).await?;
-        let res2 = self.req2.send(&args).await?;
+    /// Send the pre-requests and req1, and resolve the named target with what they captured
+    /// so that it could be sent repeatedly, e.g. to bench it against the response of req1.
+    pub async fn prepare_target(
+        &self,
+        clients: &HttpClients,
+        args: &OverrideArgs,
+        name: &str,
+    ) -> Result<(ResponseExt, RequestProfile)> {
+        let target = match self.targets().into_iter().find(|(n, _)| *n == name) {
+            Some((_, DiffTarget::Request(req))) => req,
+            Some(_) => return Err(anyhow!("target {} is a snapshot, not a request", name)),
+            None => return Err(anyhow!("target {} not found", name)),
+        };
+        let mut captures = self.send_pre(clients, args).await?;
+        let res1 = self.req1.send_with(clients, args, &mut captures).await?;
+        let target = target.prepare(clients, args, &mut captures).await?;
+        Ok((res1, target))
+    }
+
+    /// Send the pre-requests and req1.
+    pub async fn send_req1(
+        &self,
+        clients: &HttpClients,
+        args: &OverrideArgs,
+    ) -> Result<ResponseExt> {
+        let mut captures = self.send_pre(clients, args).await?;
+        self.req1.send_with(clients, args, &mut captures).await
+    }
+
+    pub(crate) async fn send_pre(
+        &self,
+        clients: &HttpClients,
+        args: &OverrideArgs,
+    ) -> Result<Captures> {
+        let mut captures = Captures::new();
+        for pre in &self.pre {
+            pre.send_with(clients, args, &mut captures)
+                .await
+                .with_context(|| format!("pre-request to {} failed", pre.url))?;
+        }
+        Ok(captures)
+    }
+
+    /// Diff the responses, and validate them against the schema of the profile and
+    /// the schemas of their requests.
+    fn compare(
+        &self,
+        res1: &ResponseExt,
+        res2: &ResponseExt,
+        schema2: Option<&SchemaFile>,
+    ) -> Result<DiffResult> {
+        let shared = self.res.schema.as_ref();
+        let schemas1: Vec<&SchemaFile> = shared
+            .into_iter()
+            .chain(self.req1.schema.as_ref())
+            .collect();
+        let schemas2: Vec<&SchemaFile> = shared.into_iter().chain(schema2).collect();
+        Ok(DiffResult {
+            mode: self.res.diff_mode,
+            res1: res1.get_parts(&self.res)?,
+            res2: res2.get_parts(&self.res)?,
+            metrics: MetricsDiff::new(&self.res.threshold, res1, res2),
+            violations: Violations {
+                res1: validate_response(res1, &schemas1)?,
+                res2: validate_response(res2, &schemas2)?,
+            },
+        })
+    }
+}
+
+/// The filtered responses of a diff profile, ready to be rendered.
+#[derive(Debug, Clone, Default)]
+pub struct DiffResult {
+    pub mode: DiffMode,
+    pub res1: ResponseParts,
+    pub res2: ResponseParts,
+    pub metrics: MetricsDiff,
+    pub violations: Violations,
+}
+
+/// The parts of the responses of req1 and the target which violate their schemas.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Violations {
+    pub res1: Vec<Violation>,
+    pub res2: Vec<Violation>,
+}
+
+impl Violations {
+    pub fn is_empty(&self) -> bool {
+        self.res1.is_empty() && self.res2.is_empty()
+    }
+}
+
+impl DiffResult {
+    /// The responses are the same, the second one is within the thresholds and
+    /// both are valid against their schemas.
+    pub fn is_same(&self) -> bool {
+        self.res1 == self.res2 && self.failures().is_empty()
+    }
+
+    /// The thresholds exceeded and the schema violations, which fail the diff
+    /// even if the responses are the same.
+    pub fn failures(&self) -> Vec<String> {
+        let violations = |side: &str, violations: &[Violation]| {
+            violations
+                .iter()
+                .map(|v| format!("{} violates the schema at {}", side, v))
+                .collect::<Vec<_>>()
+        };
+        let mut failures = self.metrics.exceeded.clone();
+        failures.extend(violations("req1", &self.violations.res1));
+        failures.extend(violations("target", &self.violations.res2));
+        failures
+    }
+
+    /// The structural changes of the bodies, if both responses are json.
+    pub fn changes(&self) -> Option<Vec<JsonChange>> {
+        match (&self.res1.json, &self.res2.json) {
+            (Some(json1), Some(json2)) => Some(diff_json(json1, json2)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        let target: DiffTarget = serde_yaml::from_str("snapshot: snapshots/todo.yaml").unwrap();
+        assert!(
+            matches!(target, DiffTarget::Snapshot { snapshot } if snapshot == Path::new("snapshots/todo.yaml"))
+        );
 
-        let text1 = res1.get_text(&self.res).await?;
-        let text2 = res2.get_text(&self.res).await?;
+        let target: DiffTarget = serde_yaml::from_str("url: http://localhost/todos").unwrap();
+        assert!(matches!(target, DiffTarget::Request(req) if req.url == "http://localhost/todos"));
 
-        diff_text(&text1, &text2)
+        // the error of the request is kept rather than "did not match any variant"
+        let err = serde_yaml::from_str::<DiffTarget>("{ url: http://localhost, method: [get] }")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("expected valid method name"), "{}", err);
     }
 }
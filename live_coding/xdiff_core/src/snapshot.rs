@@ -0,0 +1,147 @@
+use crate::profile::res::ResponseExt;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use tokio::fs;
+
+/// A directory of recorded responses, stored as one yaml file per profile.
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The snapshot file of the profile. A `.json` file is used if it exists, otherwise `.yaml`.
+    pub fn path(&self, name: &str) -> Result<PathBuf> {
+        validate_name(name)?;
+        let json = self.dir.join(format!("{}.json", name));
+        if json.exists() {
+            Ok(json)
+        } else {
+            Ok(self.dir.join(format!("{}.yaml", name)))
+        }
+    }
+
+    pub async fn save(&self, name: &str, res: &ResponseExt) -> Result<PathBuf> {
+        fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("failed to create snapshot dir {}", self.dir.display()))?;
+        let path = self.path(name)?;
+        save_snapshot(&path, res).await?;
+        Ok(path)
+    }
+
+    pub async fn load(&self, name: &str) -> Result<ResponseExt> {
+        load_snapshot(&self.path(name)?).await
+    }
+}
+
+/// The name of a profile becomes a file in the store, so it must not point outside of it.
+fn validate_name(name: &str) -> Result<()> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !name.contains(['/', '\\']) => Ok(()),
+        _ => Err(anyhow!("invalid snapshot name {:?}", name)),
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+/// Save the response to a snapshot file, as json if the file ends with `.json` or yaml otherwise.
+pub async fn save_snapshot(path: &Path, res: &ResponseExt) -> Result<()> {
+    let content = if is_json(path) {
+        serde_json::to_string_pretty(res)?
+    } else {
+        serde_yaml::to_string(res)?
+    };
+    fs::write(path, content)
+        .await
+        .with_context(|| format!("failed to write snapshot {}", path.display()))
+}
+
+/// Load a response from a json or yaml snapshot file.
+pub async fn load_snapshot(path: &Path) -> Result<ResponseExt> {
+    let content = fs::read_to_string(path)
+        .await
+        .with_context(|| format!("failed to read snapshot {}", path.display()))?;
+    let res = if is_json(path) {
+        serde_json::from_str(&content)?
+    } else {
+        serde_yaml::from_str(&content)?
+    };
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::res::Metrics;
+    use reqwest::{header::HeaderMap, StatusCode, Version};
+
+    fn response(body: &str) -> ResponseExt {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().unwrap());
+        ResponseExt {
+            version: Version::HTTP_11,
+            status: StatusCode::CREATED,
+            headers,
+            body: body.into(),
+            binary: false,
+            metrics: Metrics {
+                total_ms: 12.5,
+                size: body.len(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn assert_same(a: &ResponseExt, b: &ResponseExt) {
+        assert_eq!(a.version, b.version);
+        assert_eq!(a.status, b.status);
+        assert_eq!(a.headers, b.headers);
+        assert_eq!(a.body, b.body);
+        assert_eq!(a.binary, b.binary);
+        assert_eq!(a.metrics, b.metrics);
+    }
+
+    #[tokio::test]
+    async fn test_record_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().join("snapshots"));
+
+        let res = response(r#"{"id":1}"#);
+        let path = store.save("todo", &res).await.unwrap();
+        assert_eq!(path, dir.path().join("snapshots/todo.yaml"));
+        assert_same(&store.load("todo").await.unwrap(), &res);
+
+        // an existing json snapshot is preferred and kept as json
+        let json = dir.path().join("snapshots/user.json");
+        save_snapshot(&json, &res).await.unwrap();
+        serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&json).await.unwrap())
+            .unwrap();
+        let res = response(r#"{"id":2}"#);
+        assert_eq!(store.save("user", &res).await.unwrap(), json);
+        assert_same(&store.load("user").await.unwrap(), &res);
+
+        assert!(store.load("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new(dir.path().join("snapshots"));
+        let res = response("{}");
+        for name in ["", ".", "..", "../x", "a/b", "a\\b", "/etc/passwd"] {
+            assert!(store.save(name, &res).await.is_err(), "{}", name);
+            assert!(store.load(name).await.is_err(), "{}", name);
+        }
+        assert!(!dir.path().join("x.yaml").exists());
+        assert!(store.save("todo.v2", &res).await.is_ok());
+    }
+}
@@ -0,0 +1,608 @@
+use crate::{
+    cli::OverrideArgs,
+    config::xdiff::DiffConfig,
+    flaky::add_skips,
+    profile::xdiff::DiffResult,
+    utils::{json_diff::JsonChange, render::DiffOutcome},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::Result;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+use similar::{ChangeTag, TextDiff};
+use tokio::fs;
+
+const HELP: &str =
+    "enter run  tab focus  ↑↓ move  n/p hunk  t target  v view  s skip  w save  q quit";
+
+/// Which parts of the responses are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum View {
+    #[default]
+    All,
+    Headers,
+    Body,
+}
+
+/// A skip rule which could be added from a line of a diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipRule {
+    Header(String),
+    Body(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Removed,
+    Added,
+    /// A json value which is changed, shown as `old -> new`.
+    Changed,
+    /// The unchanged lines left out between two hunks.
+    Separator,
+}
+
+/// A line of the diff pane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: LineKind,
+    pub text: String,
+    /// The rule skipping the header or the json path of the line, if it has any.
+    pub rule: Option<SkipRule>,
+    /// Whether the line starts a hunk, for jumping between them.
+    pub hunk: bool,
+}
+
+impl DiffLine {
+    fn new(kind: LineKind, text: impl Into<String>, rule: Option<SkipRule>) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+            rule,
+            hunk: false,
+        }
+    }
+}
+
+/// The lines of the diff of the headers, the body or both. Json bodies are listed by
+/// the changed json paths, other bodies as a line diff.
+pub fn diff_lines(result: &DiffResult, view: View) -> Vec<DiffLine> {
+    let mut lines = vec![];
+    if view != View::Body {
+        let diff = TextDiff::from_lines(&result.res1.headers, &result.res2.headers);
+        for change in diff.iter_all_changes() {
+            let text = change.value().trim_end_matches('\n');
+            if text.is_empty() {
+                continue;
+            }
+            let kind = match change.tag() {
+                ChangeTag::Equal => LineKind::Context,
+                ChangeTag::Delete => LineKind::Removed,
+                ChangeTag::Insert => LineKind::Added,
+            };
+            let rule = text
+                .split_once(": ")
+                .filter(|(name, _)| !name.contains(' '))
+                .map(|(name, _)| SkipRule::Header(name.to_string()));
+            lines.push(DiffLine::new(kind, text, rule));
+        }
+    }
+
+    if view != View::Headers {
+        match result.changes() {
+            Some(changes) => {
+                for change in changes {
+                    let rule = Some(SkipRule::Body(change.path().to_string()));
+                    let line = match change {
+                        JsonChange::Added { path, value } => {
+                            DiffLine::new(LineKind::Added, format!("{}: {}", path, value), rule)
+                        }
+                        JsonChange::Removed { path, value } => {
+                            DiffLine::new(LineKind::Removed, format!("{}: {}", path, value), rule)
+                        }
+                        JsonChange::Changed { path, old, new } => DiffLine::new(
+                            LineKind::Changed,
+                            format!("{}: {} -> {}", path, old, new),
+                            rule,
+                        ),
+                    };
+                    lines.push(DiffLine { hunk: true, ..line });
+                }
+            }
+            None => {
+                let diff = TextDiff::from_lines(&result.res1.body, &result.res2.body);
+                for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
+                    if idx > 0 {
+                        lines.push(DiffLine::new(LineKind::Separator, "⋯", None));
+                    }
+                    for op in group {
+                        for change in diff.iter_changes(op) {
+                            let kind = match change.tag() {
+                                ChangeTag::Equal => LineKind::Context,
+                                ChangeTag::Delete => LineKind::Removed,
+                                ChangeTag::Insert => LineKind::Added,
+                            };
+                            let text = change.value().trim_end_matches('\n');
+                            lines.push(DiffLine::new(kind, text, None));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if view == View::All {
+        for failure in result.failures() {
+            lines.push(DiffLine::new(LineKind::Changed, failure, None));
+        }
+    }
+
+    // a hunk starts at every change which follows an unchanged line
+    for idx in 0..lines.len() {
+        let changed =
+            |line: &DiffLine| !matches!(line.kind, LineKind::Context | LineKind::Separator);
+        if changed(&lines[idx]) && (idx == 0 || !changed(&lines[idx - 1])) {
+            lines[idx].hunk = true;
+        }
+    }
+    lines
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Profiles,
+    Diff,
+}
+
+/// What the event loop should do after a key is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    None,
+    Run,
+    Save,
+    Quit,
+}
+
+/// The state of the terminal UI.
+pub struct App {
+    config_file: String,
+    config: DiffConfig,
+    profiles: Vec<String>,
+    selected: usize,
+    outcomes: HashMap<String, Vec<DiffOutcome>>,
+    /// The outcome of the selected profile which is shown, for profiles with targets.
+    target: usize,
+    view: View,
+    focus: Focus,
+    cursor: usize,
+    /// The skip rules added since the config was last saved, by profile.
+    pending: BTreeMap<String, Vec<SkipRule>>,
+    message: String,
+}
+
+impl App {
+    pub fn new(config_file: impl Into<String>, config: DiffConfig) -> Self {
+        let mut profiles: Vec<String> = config.profiles.keys().cloned().collect();
+        profiles.sort();
+        Self {
+            config_file: config_file.into(),
+            config,
+            profiles,
+            selected: 0,
+            outcomes: HashMap::new(),
+            target: 0,
+            view: View::default(),
+            focus: Focus::Profiles,
+            cursor: 0,
+            pending: BTreeMap::new(),
+            message: HELP.to_string(),
+        }
+    }
+
+    pub fn selected_profile(&self) -> Option<&str> {
+        self.profiles.get(self.selected).map(String::as_str)
+    }
+
+    fn outcome(&self) -> Option<&DiffOutcome> {
+        self.outcomes
+            .get(self.selected_profile()?)?
+            .get(self.target)
+    }
+
+    pub fn lines(&self) -> Vec<DiffLine> {
+        match self.outcome().map(|outcome| &outcome.result) {
+            Some(Ok(result)) => diff_lines(result, self.view),
+            _ => vec![],
+        }
+    }
+
+    pub fn set_outcomes(&mut self, profile: &str, outcomes: Vec<DiffOutcome>) {
+        self.outcomes.insert(profile.to_string(), outcomes);
+        self.target = 0;
+        self.cursor = 0;
+    }
+
+    pub fn handle_key(&mut self, key: KeyCode) -> Command {
+        let lines = self.lines();
+        match key {
+            KeyCode::Char('q') => return Command::Quit,
+            KeyCode::Esc if self.focus == Focus::Profiles => return Command::Quit,
+            KeyCode::Esc => self.focus = Focus::Profiles,
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Focus::Profiles => Focus::Diff,
+                    Focus::Diff => Focus::Profiles,
+                }
+            }
+            KeyCode::Enter | KeyCode::Char('r') => return Command::Run,
+            KeyCode::Char('w') => return Command::Save,
+            KeyCode::Char('v') => {
+                self.view = match self.view {
+                    View::All => View::Headers,
+                    View::Headers => View::Body,
+                    View::Body => View::All,
+                };
+                self.cursor = 0;
+            }
+            KeyCode::Char('t') => {
+                let count = self.selected_profile().and_then(|p| self.outcomes.get(p));
+                if let Some(count) = count.map(Vec::len).filter(|&n| n > 0) {
+                    self.target = (self.target + 1) % count;
+                    self.cursor = 0;
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1, &lines),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1, &lines),
+            KeyCode::PageUp => self.move_by(-10, &lines),
+            KeyCode::PageDown => self.move_by(10, &lines),
+            KeyCode::Char('n') => {
+                if let Some(idx) = (self.cursor + 1..lines.len()).find(|&i| lines[i].hunk) {
+                    self.cursor = idx;
+                }
+                self.focus = Focus::Diff;
+            }
+            KeyCode::Char('p') => {
+                if let Some(idx) = (0..self.cursor).rev().find(|&i| lines[i].hunk) {
+                    self.cursor = idx;
+                }
+                self.focus = Focus::Diff;
+            }
+            KeyCode::Char('s') if self.focus == Focus::Diff => match lines.get(self.cursor) {
+                Some(DiffLine {
+                    rule: Some(rule), ..
+                }) => self.add_rule(rule.clone()),
+                _ => self.message = "only headers and json paths could be skipped".into(),
+            },
+            _ => {}
+        }
+        Command::None
+    }
+
+    fn move_by(&mut self, delta: isize, lines: &[DiffLine]) {
+        match self.focus {
+            Focus::Profiles => {
+                let last = self.profiles.len().saturating_sub(1);
+                self.selected = self.selected.saturating_add_signed(delta).min(last);
+                self.target = 0;
+                self.cursor = 0;
+            }
+            Focus::Diff => {
+                let last = lines.len().saturating_sub(1);
+                self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+            }
+        }
+    }
+
+    /// Add the rule to the profile, so that it applies to the next run, and to the rules to save.
+    fn add_rule(&mut self, rule: SkipRule) {
+        let Some(name) = self.selected_profile().map(str::to_string) else {
+            return;
+        };
+        let Some(profile) = self.config.profiles.get_mut(&name) else {
+            return;
+        };
+        let (skips, value, what) = match rule {
+            SkipRule::Header(ref header) => (&mut profile.res.skip_headers, header, "header"),
+            SkipRule::Body(ref path) => (&mut profile.res.skip_body, path, "body path"),
+        };
+        if skips.contains(value) {
+            self.message = format!("{} {} is skipped already", what, value);
+            return;
+        }
+        skips.push(value.clone());
+        self.message = format!("skip {} {} added, r to run again, w to save", what, value);
+        self.pending.entry(name).or_default().push(rule);
+    }
+
+    /// Write the added skip rules to the files the profiles are defined in.
+    pub async fn save(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            self.message = "no skip rules to save".into();
+            return Ok(());
+        }
+        let mut files: BTreeMap<PathBuf, Vec<(&String, &Vec<SkipRule>)>> = BTreeMap::new();
+        for (profile, rules) in &self.pending {
+            let file = self
+                .config
+                .sources
+                .file(profile)
+                .map_or_else(|| PathBuf::from(&self.config_file), Path::to_path_buf);
+            files.entry(file).or_default().push((profile, rules));
+        }
+
+        for (file, profiles) in &files {
+            let mut content = fs::read_to_string(file).await?;
+            for (profile, rules) in profiles {
+                let (mut headers, mut body) = (vec![], vec![]);
+                for rule in rules.iter() {
+                    match rule {
+                        SkipRule::Header(header) => headers.push(header.clone()),
+                        SkipRule::Body(path) => body.push(path.clone()),
+                    }
+                }
+                content = add_skips(&content, profile, &headers, &body)?;
+            }
+            fs::write(file, content).await?;
+        }
+        let files: Vec<String> = files.keys().map(|f| f.display().to_string()).collect();
+        self.message = format!("saved the skip rules to {}", files.join(", "));
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Run the selected profile against all its targets.
+    pub async fn run(&mut self, args: &OverrideArgs) {
+        let Some(name) = self.selected_profile().map(str::to_string) else {
+            return;
+        };
+        let Some(profile) = self.config.get_profile(&name) else {
+            return;
+        };
+        let start = Instant::now();
+        let result = profile
+            .diff(&self.config.clients(), args.clone(), None)
+            .await;
+        let outcomes = DiffOutcome::from_targets(&name, result, start.elapsed());
+        self.message = format!("ran {} in {}ms", name, start.elapsed().as_millis());
+        self.set_outcomes(&name, outcomes);
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(25), Constraint::Percentage(75)])
+                .areas(main);
+
+        let items: Vec<ListItem> = self
+            .profiles
+            .iter()
+            .map(|name| {
+                let (mark, color) = match self.outcomes.get(name) {
+                    None => (" ", Color::Reset),
+                    Some(outcomes) if outcomes.iter().any(|o| o.result.is_err()) => {
+                        ("!", Color::Red)
+                    }
+                    Some(outcomes)
+                        if outcomes
+                            .iter()
+                            .all(|o| o.result.as_ref().is_ok_and(|result| result.is_same())) =>
+                    {
+                        ("=", Color::Green)
+                    }
+                    Some(_) => ("≠", Color::Yellow),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(mark, Style::new().fg(color)),
+                    Span::raw(format!(" {}", name)),
+                ]))
+            })
+            .collect();
+        let border = |focus| match self.focus == focus {
+            true => Style::new().fg(Color::Cyan),
+            false => Style::new(),
+        };
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(" Profiles ")
+                    .border_style(border(Focus::Profiles)),
+            )
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, left, &mut state);
+
+        let (title, text) = match self.outcome() {
+            None => (" Diff ".to_string(), vec![Line::raw("press enter to run")]),
+            Some(outcome) => {
+                let view = match self.view {
+                    View::All => "all",
+                    View::Headers => "headers",
+                    View::Body => "body",
+                };
+                let title = format!(" {} [{}] ", outcome.name, view);
+                let text = match &outcome.result {
+                    Err(e) => vec![Line::styled(
+                        format!("error: {:#}", e),
+                        Style::new().fg(Color::Red),
+                    )],
+                    Ok(result) if result.is_same() => vec![Line::raw("the responses are the same")],
+                    Ok(_) => self.render_lines(),
+                };
+                (title, text)
+            }
+        };
+        // keep the cursor in view
+        let height = right.height.saturating_sub(2) as usize;
+        let offset = (self.cursor + 1).saturating_sub(height);
+        let diff = Paragraph::new(text)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .border_style(border(Focus::Diff)),
+            )
+            .scroll((offset as u16, 0));
+        frame.render_widget(diff, right);
+
+        frame.render_widget(Paragraph::new(self.message.as_str()), status);
+    }
+
+    fn render_lines(&self) -> Vec<Line<'static>> {
+        self.lines()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                let (sign, style) = match line.kind {
+                    LineKind::Context => (" ", Style::new().fg(Color::DarkGray)),
+                    LineKind::Removed => ("-", Style::new().fg(Color::Red)),
+                    LineKind::Added => ("+", Style::new().fg(Color::Green)),
+                    LineKind::Changed => ("~", Style::new().fg(Color::Yellow)),
+                    LineKind::Separator => (" ", Style::new().fg(Color::DarkGray)),
+                };
+                let style = match self.focus == Focus::Diff && idx == self.cursor {
+                    true => style.add_modifier(Modifier::REVERSED),
+                    false => style,
+                };
+                Line::styled(format!("{} {}", sign, line.text), style)
+            })
+            .collect()
+    }
+}
+
+/// Run the terminal UI until it is quit, restoring the terminal afterwards.
+pub async fn run_tui(app: &mut App, args: &OverrideArgs) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, app, args).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    args: &OverrideArgs,
+) -> Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match app.handle_key(key.code) {
+            Command::None => {}
+            Command::Quit => return Ok(()),
+            Command::Run => {
+                if let Some(name) = app.selected_profile() {
+                    app.message = format!("running {}...", name);
+                }
+                terminal.draw(|frame| app.draw(frame))?;
+                app.run(args).await;
+            }
+            Command::Save => {
+                if let Err(e) = app.save().await {
+                    app.message = format!("failed to save: {:#}", e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        profile::res::{DiffMode, ResponseParts},
+        LoadConfig,
+    };
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn result() -> DiffResult {
+        let parts = |id: &str, ts: u32| ResponseParts {
+            headers: format!(
+                "\nHTTP/1.1 200 OK\ncontent-type: \"application/json\"\nx-id: \"{}\"\n",
+                id
+            ),
+            body: String::new(),
+            json: Some(json!({"ok": true, "ts": ts})),
+        };
+        DiffResult {
+            mode: DiffMode::Json,
+            res1: parts("a", 1),
+            res2: parts("b", 2),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let lines = diff_lines(&result(), View::All);
+        let texts: Vec<(LineKind, &str, bool)> = lines
+            .iter()
+            .map(|line| (line.kind, line.text.as_str(), line.hunk))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                (LineKind::Context, "HTTP/1.1 200 OK", false),
+                (
+                    LineKind::Context,
+                    "content-type: \"application/json\"",
+                    false
+                ),
+                (LineKind::Removed, "x-id: \"a\"", true),
+                (LineKind::Added, "x-id: \"b\"", false),
+                (LineKind::Changed, "$.ts: 1 -> 2", true),
+            ]
+        );
+        assert_eq!(lines[0].rule, None);
+        assert_eq!(lines[2].rule, Some(SkipRule::Header("x-id".into())));
+        assert_eq!(lines[4].rule, Some(SkipRule::Body("$.ts".into())));
+        assert_eq!(diff_lines(&result(), View::Body).len(), 1);
+    }
+
+    #[test]
+    fn test_add_skip_rules() {
+        let config = DiffConfig::from_yaml(
+            r#"
+todo:
+  req1: { url: "https://example.com/todos/1" }
+  req2: { url: "https://example.com/todos/2" }
+"#,
+        )
+        .unwrap();
+        let mut app = App::new("todo.yaml", config);
+        let outcome = DiffOutcome::new("todo", Ok(result()), Duration::ZERO);
+        app.set_outcomes("todo", vec![outcome]);
+
+        assert_eq!(app.handle_key(KeyCode::Char('n')), Command::None);
+        assert_eq!(app.cursor, 2);
+        app.handle_key(KeyCode::Char('s'));
+        app.handle_key(KeyCode::Char('n'));
+        app.handle_key(KeyCode::Char('s'));
+        app.handle_key(KeyCode::Char('s'));
+        assert_eq!(
+            app.pending["todo"],
+            vec![
+                SkipRule::Header("x-id".into()),
+                SkipRule::Body("$.ts".into())
+            ]
+        );
+        let res = &app.config.profiles["todo"].res;
+        assert_eq!(res.skip_headers, vec!["x-id"]);
+        assert_eq!(res.skip_body, vec!["$.ts"]);
+        assert_eq!(app.handle_key(KeyCode::Char('w')), Command::Save);
+    }
+}
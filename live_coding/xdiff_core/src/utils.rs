@@ -1,5 +1,14 @@
+pub mod json_diff;
+pub mod json_path;
+pub mod render;
+pub mod template;
+
 use anyhow::Result;
-use std::fmt::{self, Write};
+use std::{
+    fmt::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
 
 use console::{style, Style};
 use similar::{ChangeTag, TextDiff};
@@ -77,3 +86,56 @@ pub fn highlight_text(text: &str, extension: &str) -> Result<String> {
 
     Ok(output)
 }
+
+/// Make a relative path given in a config relative to the directory of the config file,
+/// as the `include:` files are.
+pub(crate) fn resolve_path(path: &mut PathBuf, dir: &Path) {
+    if path.is_relative() {
+        *path = dir.join(&*path);
+    }
+}
+
+/// A value loaded from a file of a config once, e.g. when the config is validated, and
+/// shared by the clones of the profile it belongs to. It is left out of the comparisons
+/// of the profiles, which compare the file instead.
+pub struct Cached<T>(Arc<OnceLock<T>>);
+
+impl<T> Cached<T> {
+    pub fn get_or_load(&self, load: impl FnOnce() -> Result<T>) -> Result<&T> {
+        if let Some(value) = self.0.get() {
+            return Ok(value);
+        }
+        let value = load()?;
+        Ok(self.0.get_or_init(|| value))
+    }
+}
+
+impl<T> Default for Cached<T> {
+    fn default() -> Self {
+        Self(Arc::default())
+    }
+}
+
+impl<T> Clone for Cached<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> fmt::Debug for Cached<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match self.0.get() {
+            Some(_) => "loaded",
+            None => "not loaded",
+        };
+        write!(f, "Cached({})", state)
+    }
+}
+
+impl<T> PartialEq for Cached<T> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for Cached<T> {}
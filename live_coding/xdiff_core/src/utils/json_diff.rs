@@ -0,0 +1,196 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use console::Style;
+use serde::Serialize;
+use serde_json::Value;
+
+/// A single difference between two json values, located by its json path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JsonChange {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl JsonChange {
+    pub fn path(&self) -> &str {
+        match self {
+            JsonChange::Added { path, .. }
+            | JsonChange::Removed { path, .. }
+            | JsonChange::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Compare two json values structurally and collect the changes by json path,
+/// e.g. `$.items[3].price`.
+pub fn diff_json(old: &Value, new: &Value) -> Vec<JsonChange> {
+    let mut changes = vec![];
+    diff_value("$", old, new, &mut changes);
+    changes
+}
+
+fn diff_value(path: &str, old: &Value, new: &Value, changes: &mut Vec<JsonChange>) {
+    match (old, new) {
+        (Value::Object(o), Value::Object(n)) => {
+            let keys: BTreeSet<&String> = o.keys().chain(n.keys()).collect();
+            for k in keys {
+                let path = join_key(path, k);
+                match (o.get(k), n.get(k)) {
+                    (Some(v1), Some(v2)) => diff_value(&path, v1, v2, changes),
+                    (Some(v), None) => changes.push(JsonChange::Removed {
+                        path,
+                        value: v.clone(),
+                    }),
+                    (None, Some(v)) => changes.push(JsonChange::Added {
+                        path,
+                        value: v.clone(),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        (Value::Array(o), Value::Array(n)) => {
+            for idx in 0..o.len().max(n.len()) {
+                let path = format!("{}[{}]", path, idx);
+                match (o.get(idx), n.get(idx)) {
+                    (Some(v1), Some(v2)) => diff_value(&path, v1, v2, changes),
+                    (Some(v), None) => changes.push(JsonChange::Removed {
+                        path,
+                        value: v.clone(),
+                    }),
+                    (None, Some(v)) => changes.push(JsonChange::Added {
+                        path,
+                        value: v.clone(),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        _ if old != new => changes.push(JsonChange::Changed {
+            path: path.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Append an object key to a json path, quoting it when it is not a plain identifier.
+pub fn join_key(path: &str, key: &str) -> String {
+    let is_ident = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_ident {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{}]", path, Value::String(key.to_string()))
+    }
+}
+
+pub fn render_json_diff(changes: &[JsonChange]) -> Result<String> {
+    let mut output = String::new();
+    let (red, green, yellow) = (
+        Style::new().red(),
+        Style::new().green(),
+        Style::new().yellow(),
+    );
+
+    for change in changes {
+        match change {
+            JsonChange::Added { path, value } => {
+                writeln!(
+                    &mut output,
+                    "{} {}: {}",
+                    green.apply_to("+").bold(),
+                    green.apply_to(path),
+                    green.apply_to(value)
+                )?;
+            }
+            JsonChange::Removed { path, value } => {
+                writeln!(
+                    &mut output,
+                    "{} {}: {}",
+                    red.apply_to("-").bold(),
+                    red.apply_to(path),
+                    red.apply_to(value)
+                )?;
+            }
+            JsonChange::Changed { path, old, new } => {
+                writeln!(
+                    &mut output,
+                    "{} {}: {} -> {}",
+                    yellow.apply_to("~").bold(),
+                    yellow.apply_to(path),
+                    red.apply_to(old),
+                    green.apply_to(new)
+                )?;
+            }
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_json() {
+        let old = json!({"id": 1, "items": [{"price": 10}, {"price": 20}], "a b": true});
+        let new = json!({"items": [{"price": 10}, {"price": 25}, {"price": 30}], "a b": true, "ok": null});
+        let changes = diff_json(&old, &new);
+
+        assert_eq!(
+            changes,
+            vec![
+                JsonChange::Removed {
+                    path: "$.id".into(),
+                    value: json!(1)
+                },
+                JsonChange::Changed {
+                    path: "$.items[1].price".into(),
+                    old: json!(20),
+                    new: json!(25)
+                },
+                JsonChange::Added {
+                    path: "$.items[2]".into(),
+                    value: json!({"price": 30})
+                },
+                JsonChange::Added {
+                    path: "$.ok".into(),
+                    value: json!(null)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_json_ignores_key_order() {
+        let old: Value = serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap();
+        let new: Value = serde_json::from_str(r#"{"b": 2, "a": 1}"#).unwrap();
+        assert!(diff_json(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_join_key() {
+        assert_eq!(join_key("$", "name"), "$.name");
+        assert_eq!(join_key("$", "content-type"), "$[\"content-type\"]");
+    }
+}
@@ -0,0 +1,412 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+use super::json_diff::join_key;
+use serde_json::Value;
+
+/// A small subset of JSONPath used to locate nodes in a json value.
+///
+/// Supported syntax: `$` root (optional), `.key`, `['key']`, `["key"]`, `[0]`,
+/// `.*` / `[*]` wildcards and `..key` recursive descent. A bare `key` is
+/// treated as `$.key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+    recursive: bool,
+    selector: Selector,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Selector {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let err = |msg: &str| anyhow!("Invalid json path {:?}: {}", s, msg);
+        let chars: Vec<char> = s.trim().chars().collect();
+        let mut steps = vec![];
+        let mut pos = 0;
+
+        match chars.first() {
+            None => return Err(err("empty path")),
+            Some('$') => pos = 1,
+            Some('.') => return Err(err("missing key before '.'")),
+            Some('[') => {}
+            Some(_) => {
+                let key = read_key(&chars, &mut pos);
+                steps.push(Step {
+                    recursive: false,
+                    selector: Selector::Key(key),
+                });
+            }
+        }
+
+        while pos < chars.len() {
+            let mut recursive = false;
+            match chars[pos] {
+                '.' => {
+                    pos += 1;
+                    if chars.get(pos) == Some(&'.') {
+                        recursive = true;
+                        pos += 1;
+                    }
+                    let selector = match chars.get(pos) {
+                        Some('*') => {
+                            pos += 1;
+                            Selector::Wildcard
+                        }
+                        Some('[') if recursive => read_bracket(&chars, &mut pos).map_err(err)?,
+                        _ => {
+                            let key = read_key(&chars, &mut pos);
+                            if key.is_empty() {
+                                return Err(err("missing key after '.'"));
+                            }
+                            Selector::Key(key)
+                        }
+                    };
+                    steps.push(Step {
+                        recursive,
+                        selector,
+                    });
+                }
+                '[' => {
+                    let selector = read_bracket(&chars, &mut pos).map_err(err)?;
+                    steps.push(Step {
+                        recursive,
+                        selector,
+                    });
+                }
+                c => return Err(err(&format!("unexpected character {:?}", c))),
+            }
+        }
+
+        if steps.is_empty() {
+            return Err(err("path selects the root"));
+        }
+        Ok(Self { steps })
+    }
+}
+
+fn read_key(chars: &[char], pos: &mut usize) -> String {
+    let start = *pos;
+    while *pos < chars.len() && !matches!(chars[*pos], '.' | '[' | ']') {
+        *pos += 1;
+    }
+    chars[start..*pos].iter().collect()
+}
+
+fn read_bracket(chars: &[char], pos: &mut usize) -> Result<Selector, &'static str> {
+    // skip '['
+    *pos += 1;
+    let selector = match chars.get(*pos) {
+        Some('*') => {
+            *pos += 1;
+            Selector::Wildcard
+        }
+        Some(&quote @ ('\'' | '"')) => {
+            *pos += 1;
+            let mut key = String::new();
+            loop {
+                match chars.get(*pos) {
+                    None => return Err("unterminated quoted key"),
+                    Some('\\') => {
+                        key.extend(chars.get(*pos + 1));
+                        *pos += 2;
+                    }
+                    Some(&c) if c == quote => {
+                        *pos += 1;
+                        break;
+                    }
+                    Some(&c) => {
+                        key.push(c);
+                        *pos += 1;
+                    }
+                }
+            }
+            Selector::Key(key)
+        }
+        _ => {
+            let start = *pos;
+            while chars.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+                *pos += 1;
+            }
+            let index: String = chars[start..*pos].iter().collect();
+            Selector::Index(index.parse().map_err(|_| "invalid array index")?)
+        }
+    };
+    if chars.get(*pos) != Some(&']') {
+        return Err("missing ']'");
+    }
+    *pos += 1;
+    Ok(selector)
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "$")?;
+        for step in &self.steps {
+            if step.recursive {
+                write!(f, "..")?;
+            }
+            match &step.selector {
+                Selector::Key(k) => {
+                    let key = join_key("", k);
+                    if step.recursive {
+                        write!(f, "{}", key.trim_start_matches('.'))?
+                    } else {
+                        write!(f, "{}", key)?
+                    }
+                }
+                Selector::Index(idx) => write!(f, "[{}]", idx)?,
+                Selector::Wildcard if step.recursive => write!(f, "*")?,
+                Selector::Wildcard => write!(f, "[*]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl JsonPath {
+    /// Remove every node matched by the path from the value.
+    pub fn remove(&self, value: &mut Value) {
+        remove_steps(value, &self.steps);
+    }
+
+    /// Collect every node matched by the path.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut found = vec![];
+        select_steps(value, &self.steps, &mut found);
+        found
+    }
+
+    /// Update every node matched by the path in place.
+    pub fn update(&self, value: &mut Value, f: &mut impl FnMut(&mut Value)) {
+        update_steps(value, &self.steps, f);
+    }
+
+    fn is_single(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| !step.recursive && step.selector != Selector::Wildcard)
+    }
+
+    /// The path as a jq filter, if it matches a single node.
+    pub fn to_jq(&self) -> Option<String> {
+        if !self.is_single() {
+            return None;
+        }
+        let path = self.to_string();
+        match path[1..].starts_with('[') {
+            true => Some(format!(".{}", &path[1..])),
+            false => Some(path[1..].to_string()),
+        }
+    }
+
+    /// The path as a JSON pointer, if it matches a single node.
+    pub fn to_pointer(&self) -> Option<String> {
+        if !self.is_single() {
+            return None;
+        }
+        let mut pointer = String::new();
+        for step in &self.steps {
+            match &step.selector {
+                Selector::Key(k) => {
+                    pointer.push('/');
+                    pointer.push_str(&k.replace('~', "~0").replace('/', "~1"));
+                }
+                Selector::Index(idx) => write!(pointer, "/{}", idx).unwrap(),
+                Selector::Wildcard => unreachable!("not a single node"),
+            }
+        }
+        Some(pointer)
+    }
+}
+
+fn remove_steps(value: &mut Value, steps: &[Step]) {
+    let Some((step, rest)) = steps.split_first() else {
+        return;
+    };
+
+    if step.recursive {
+        for child in children_mut(value) {
+            remove_steps(child, steps);
+        }
+    }
+
+    if rest.is_empty() {
+        match (&step.selector, value) {
+            (Selector::Key(k), Value::Object(obj)) => {
+                obj.remove(k);
+            }
+            (Selector::Index(idx), Value::Array(arr)) if *idx < arr.len() => {
+                arr.remove(*idx);
+            }
+            (Selector::Wildcard, Value::Object(obj)) => obj.clear(),
+            (Selector::Wildcard, Value::Array(arr)) => arr.clear(),
+            _ => {}
+        }
+    } else {
+        for child in matches_mut(value, &step.selector) {
+            remove_steps(child, rest);
+        }
+    }
+}
+
+fn update_steps(value: &mut Value, steps: &[Step], f: &mut impl FnMut(&mut Value)) {
+    let Some((step, rest)) = steps.split_first() else {
+        f(value);
+        return;
+    };
+
+    if step.recursive {
+        for child in children_mut(value) {
+            update_steps(child, steps, f);
+        }
+    }
+    for child in matches_mut(value, &step.selector) {
+        update_steps(child, rest, f);
+    }
+}
+
+fn select_steps<'a>(value: &'a Value, steps: &[Step], found: &mut Vec<&'a Value>) {
+    let Some((step, rest)) = steps.split_first() else {
+        found.push(value);
+        return;
+    };
+
+    let matched: Vec<&Value> = match (&step.selector, value) {
+        (Selector::Key(k), Value::Object(obj)) => obj.get(k).into_iter().collect(),
+        (Selector::Index(idx), Value::Array(arr)) => arr.get(*idx).into_iter().collect(),
+        (Selector::Wildcard, Value::Object(obj)) => obj.values().collect(),
+        (Selector::Wildcard, Value::Array(arr)) => arr.iter().collect(),
+        _ => vec![],
+    };
+    for child in matched {
+        select_steps(child, rest, found);
+    }
+
+    if step.recursive {
+        let children: Vec<&Value> = match value {
+            Value::Object(obj) => obj.values().collect(),
+            Value::Array(arr) => arr.iter().collect(),
+            _ => vec![],
+        };
+        for child in children {
+            select_steps(child, steps, found);
+        }
+    }
+}
+
+fn children_mut(value: &mut Value) -> Vec<&mut Value> {
+    match value {
+        Value::Object(obj) => obj.values_mut().collect(),
+        Value::Array(arr) => arr.iter_mut().collect(),
+        _ => vec![],
+    }
+}
+
+fn matches_mut<'a>(value: &'a mut Value, selector: &Selector) -> Vec<&'a mut Value> {
+    match (selector, value) {
+        (Selector::Key(k), Value::Object(obj)) => obj.get_mut(k).into_iter().collect(),
+        (Selector::Index(idx), Value::Array(arr)) => arr.get_mut(*idx).into_iter().collect(),
+        (Selector::Wildcard, value) => children_mut(value),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn remove(path: &str, mut value: Value) -> Value {
+        path.parse::<JsonPath>().unwrap().remove(&mut value);
+        value
+    }
+
+    #[test]
+    fn test_parse_json_path() {
+        for (input, expected) in [
+            ("id", "$.id"),
+            ("data.meta.request_id", "$.data.meta.request_id"),
+            ("items[*].updated_at", "$.items[*].updated_at"),
+            ("$.items[3]['a b']", "$.items[3][\"a b\"]"),
+            ("$..updated_at", "$..updated_at"),
+            ("$..['a b']", "$..[\"a b\"]"),
+        ] {
+            let path: JsonPath = input.parse().unwrap();
+            assert_eq!(path.to_string(), expected);
+        }
+
+        for input in ["", "$", "$.", "a[x]", "a[0", "$.a]"] {
+            assert!(input.parse::<JsonPath>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_single_node_paths() {
+        for (input, jq, pointer) in [
+            ("token", ".token", "/token"),
+            (
+                "$.data.items[2].id",
+                ".data.items[2].id",
+                "/data/items/2/id",
+            ),
+            ("$['a/b']", ".[\"a/b\"]", "/a~1b"),
+        ] {
+            let path: JsonPath = input.parse().unwrap();
+            assert_eq!(path.to_jq().unwrap(), jq);
+            assert_eq!(path.to_pointer().unwrap(), pointer);
+        }
+
+        for input in ["$.items[*].id", "$..id"] {
+            let path: JsonPath = input.parse().unwrap();
+            assert_eq!((path.to_jq(), path.to_pointer()), (None, None));
+        }
+    }
+
+    #[test]
+    fn test_remove_nested_and_wildcard() {
+        let value = json!({
+            "data": {"meta": {"request_id": "x", "page": 1}},
+            "items": [{"id": 1, "updated_at": "t1"}, {"id": 2, "updated_at": "t2"}],
+        });
+
+        assert_eq!(
+            remove("items[*].updated_at", remove("data.meta.request_id", value)),
+            json!({
+                "data": {"meta": {"page": 1}},
+                "items": [{"id": 1}, {"id": 2}],
+            })
+        );
+    }
+
+    #[test]
+    fn test_remove_recursive() {
+        let value = json!({"ts": 1, "a": {"ts": 2, "b": [{"ts": 3, "c": 4}]}});
+        assert_eq!(remove("$..ts", value), json!({"a": {"b": [{"c": 4}]}}));
+    }
+
+    #[test]
+    fn test_select() {
+        let value = json!({"token": "t", "items": [{"id": 1}, {"id": 2, "sub": {"id": 3}}]});
+        let select = |path: &str| path.parse::<JsonPath>().unwrap().select(&value);
+
+        assert_eq!(select("token"), vec![&json!("t")]);
+        assert_eq!(select("$.items[*].id"), vec![&json!(1), &json!(2)]);
+        assert_eq!(select("$..id"), vec![&json!(1), &json!(2), &json!(3)]);
+        assert!(select("$.items[5]").is_empty());
+    }
+}
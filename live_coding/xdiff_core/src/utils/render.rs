@@ -0,0 +1,1047 @@
+use super::{
+    diff_text,
+    json_diff::{render_json_diff, JsonChange},
+};
+use crate::profile::{
+    res::{change, DiffMode, Metrics},
+    schema::Violation,
+    xdiff::{DiffResult, REQ2},
+};
+use std::{fmt::Write, time::Duration};
+
+use anyhow::Result;
+use console::{measure_text_width, pad_str, style, truncate_str, Alignment, Style};
+use serde::Serialize;
+use similar::{ChangeTag, DiffOp, DiffTag, TextDiff};
+
+/// The outcome of diffing a single profile, or one of its targets.
+#[derive(Debug)]
+pub struct DiffOutcome {
+    /// The name of the profile, followed by the target as `profile/target` if it has any.
+    pub name: String,
+    /// The named target of the profile, `None` for a profile with only req2.
+    pub target: Option<String>,
+    pub result: Result<DiffResult>,
+    pub elapsed: Duration,
+}
+
+impl DiffOutcome {
+    pub fn new(name: impl Into<String>, result: Result<DiffResult>, elapsed: Duration) -> Self {
+        Self {
+            name: name.into(),
+            target: None,
+            result,
+            elapsed,
+        }
+    }
+
+    /// The outcomes of the targets of a profile, or its failure before any target is diffed.
+    pub fn from_targets(
+        profile: &str,
+        result: Result<Vec<(String, Result<DiffResult>)>>,
+        elapsed: Duration,
+    ) -> Vec<Self> {
+        match result {
+            Ok(results) if results.len() == 1 && results[0].0 == REQ2 => results
+                .into_iter()
+                .map(|(_, result)| Self::new(profile, result, elapsed))
+                .collect(),
+            Ok(results) => results
+                .into_iter()
+                .map(|(target, result)| Self {
+                    name: format!("{}/{}", profile, target),
+                    target: Some(target),
+                    result,
+                    elapsed,
+                })
+                .collect(),
+            Err(e) => vec![Self::new(profile, Err(e), elapsed)],
+        }
+    }
+
+    /// The name of the target req1 is diffed against.
+    pub fn target_name(&self) -> &str {
+        self.target.as_deref().unwrap_or(REQ2)
+    }
+
+    /// The name of the profile without the target.
+    pub fn profile(&self) -> &str {
+        match self.target {
+            Some(ref target) => self
+                .name
+                .strip_suffix(target.as_str())
+                .and_then(|name| name.strip_suffix('/'))
+                .unwrap_or(&self.name),
+            None => &self.name,
+        }
+    }
+}
+
+/// The process exit code for the outcomes: 0 if all responses are the same,
+/// 1 if any responses differ and 2 if any profile failed.
+pub fn exit_code(outcomes: &[DiffOutcome]) -> i32 {
+    outcomes
+        .iter()
+        .fold(0, |code, outcome| match &outcome.result {
+            Ok(result) if result.is_same() => code,
+            Ok(_) => code.max(1),
+            Err(_) => 2,
+        })
+}
+
+/// A summary table with the status, the number of changed lines and the time of every profile.
+pub fn summary_table(outcomes: &[DiffOutcome]) -> Result<String> {
+    let width = outcomes
+        .iter()
+        .map(|o| o.name.len())
+        .chain(Some("PROFILE".len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut output = String::new();
+    writeln!(
+        &mut output,
+        "{:<width$}  {:<9}  {:>7}  {:>8}",
+        "PROFILE",
+        "STATUS",
+        "CHANGES",
+        "TIME",
+        width = width
+    )?;
+    for outcome in outcomes {
+        let (status, changes) = match &outcome.result {
+            Ok(result) if result.is_same() => (style("same").green(), 0),
+            Ok(result) => (style("different").yellow(), changed_lines(result)),
+            Err(_) => (style("error").red(), 0),
+        };
+        writeln!(
+            &mut output,
+            "{:<width$}  {:<9}  {:>7}  {:>6}ms",
+            outcome.name,
+            status.for_stderr(),
+            changes,
+            outcome.elapsed.as_millis(),
+            width = width
+        )?;
+    }
+    Ok(output)
+}
+
+/// The timings and sizes of the two responses next to each other, empty if neither has any.
+pub fn metrics_table(result: &DiffResult, target: &str) -> Result<String> {
+    let (m1, m2) = (&result.metrics.res1, &result.metrics.res2);
+    let mut output = String::new();
+    if *m1 == Metrics::default() && *m2 == Metrics::default() {
+        return Ok(output);
+    }
+
+    let ms = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.1}ms", v));
+    let measured = |v: Option<f64>| v.map_or_else(|| "n/a".to_string(), |v| format!("{:.1}ms", v));
+    let (size1, size2) = (m1.size as f64, m2.size as f64);
+    let rows = [
+        ("dns", ms(m1.dns_ms), ms(m2.dns_ms), None),
+        (
+            "connect",
+            measured(m1.connect_ms),
+            measured(m2.connect_ms),
+            None,
+        ),
+        (
+            "ttfb",
+            ms(Some(m1.ttfb_ms)),
+            ms(Some(m2.ttfb_ms)),
+            Some(change(m1.ttfb_ms, m2.ttfb_ms)),
+        ),
+        (
+            "total",
+            ms(Some(m1.total_ms)),
+            ms(Some(m2.total_ms)),
+            Some(change(m1.total_ms, m2.total_ms)),
+        ),
+        (
+            "size",
+            format!("{}B", m1.size),
+            format!("{}B", m2.size),
+            Some(change(size1, size2)),
+        ),
+    ];
+    writeln!(&mut output, "{:<7}  {:>10}  {:>10}", "", "req1", target)?;
+    for (name, v1, v2, change) in rows {
+        write!(&mut output, "{:<7}  {:>10}  {:>10}", name, v1, v2)?;
+        if let Some(change) = change {
+            write!(&mut output, "  {:>6}", change)?;
+        }
+        writeln!(&mut output)?;
+    }
+    Ok(output)
+}
+
+fn changed_lines(result: &DiffResult) -> usize {
+    let (text1, text2) = (result.res1.text(), result.res2.text());
+    TextDiff::from_lines(&text1, &text2)
+        .iter_all_changes()
+        .filter(|change| change.tag() != ChangeTag::Equal)
+        .count()
+}
+
+/// Render the outcomes of one or more diff profiles into a printable report.
+pub trait DiffRenderer {
+    fn render(&self, outcomes: &[DiffOutcome]) -> Result<String>;
+}
+
+/// The colored inline view for terminals.
+pub struct ColorRenderer;
+
+/// A plain unified patch without any escape sequences.
+pub struct UnifiedRenderer;
+
+/// A json report with the hunks and the changed json paths of every profile.
+pub struct JsonRenderer;
+
+/// A JUnit xml report, every profile is a test case which fails if the responses differ.
+pub struct JunitRenderer;
+
+/// The two responses next to each other, fitting the width of the terminal.
+pub struct SideBySideRenderer {
+    pub width: usize,
+}
+
+/// A self-contained html report with a section per profile, unchanged regions are collapsed.
+pub struct HtmlRenderer;
+
+/// A matrix of the profiles and their targets, followed by the changed paths of every target
+/// which differs from req1.
+pub struct MatrixRenderer;
+
+impl DiffRenderer for ColorRenderer {
+    fn render(&self, outcomes: &[DiffOutcome]) -> Result<String> {
+        let mut output = String::new();
+        for outcome in outcomes {
+            if outcomes.len() > 1 {
+                writeln!(
+                    &mut output,
+                    "{}",
+                    style(format!("== {}", outcome.name)).bold()
+                )?;
+            }
+            match &outcome.result {
+                Ok(result) => {
+                    match (result.mode, result.changes()) {
+                        (DiffMode::Json, Some(changes)) => {
+                            output
+                                .push_str(&diff_text(&result.res1.headers, &result.res2.headers)?);
+                            output.push_str(&render_json_diff(&changes)?);
+                        }
+                        _ => {
+                            output.push_str(&diff_text(&result.res1.text(), &result.res2.text())?);
+                        }
+                    }
+                    render_metrics(&mut output, outcome, result)?;
+                }
+                Err(e) => writeln!(&mut output, "{} {:#}", style("error:").red().bold(), e)?,
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// The metrics table dimmed, followed by the thresholds the target exceeds.
+fn render_metrics(output: &mut String, outcome: &DiffOutcome, result: &DiffResult) -> Result<()> {
+    let table = metrics_table(result, outcome.target_name())?;
+    if !table.is_empty() {
+        write!(output, "\n{}", style(table).dim())?;
+    }
+    for failure in result.failures() {
+        writeln!(output, "{} {}", style("failed:").red().bold(), failure)?;
+    }
+    Ok(())
+}
+
+impl DiffRenderer for UnifiedRenderer {
+    fn render(&self, outcomes: &[DiffOutcome]) -> Result<String> {
+        let mut output = String::new();
+        for outcome in outcomes {
+            match &outcome.result {
+                Ok(result) => {
+                    write!(&mut output, "{}", unified_diff(outcome, result))?;
+                    for failure in result.failures() {
+                        writeln!(&mut output, "# {}: {}", outcome.name, failure)?;
+                    }
+                }
+                Err(e) => writeln!(&mut output, "# {}: error: {:#}", outcome.name, e)?,
+            }
+        }
+        Ok(output)
+    }
+}
+
+fn unified_diff(outcome: &DiffOutcome, result: &DiffResult) -> String {
+    let (text1, text2) = (result.res1.text(), result.res2.text());
+    let profile = outcome.profile();
+    TextDiff::from_lines(&text1, &text2)
+        .unified_diff()
+        .context_radius(3)
+        .header(
+            &format!("{}/req1", profile),
+            &format!("{}/{}", profile, outcome.target_name()),
+        )
+        .to_string()
+}
+
+/// A line of a side-by-side view, with the number and text of the line on each side.
+struct Row<'a> {
+    tag: DiffTag,
+    old: Option<(usize, &'a str)>,
+    new: Option<(usize, &'a str)>,
+}
+
+/// The rows of a diff op, pairing the lines of a replaced region.
+fn rows<'a>(old: &[&'a str], new: &[&'a str], op: &DiffOp) -> Vec<Row<'a>> {
+    let (tag, old_range, new_range) = op.as_tag_tuple();
+    let line = |lines: &[&'a str], idx: usize| (idx + 1, lines[idx].trim_end_matches('\n'));
+    (0..old_range.len().max(new_range.len()))
+        .map(|i| Row {
+            tag,
+            old: (i < old_range.len()).then(|| line(old, old_range.start + i)),
+            new: (i < new_range.len()).then(|| line(new, new_range.start + i)),
+        })
+        .collect()
+}
+
+impl SideBySideRenderer {
+    fn render_diff(&self, output: &mut String, text1: &str, text2: &str) -> Result<()> {
+        // line number, sign and text on each side, and a separator in the middle
+        let column = self.width.saturating_sub(15).max(40) / 2;
+        let diff = TextDiff::from_lines(text1, text2);
+        let (old, new) = (diff.old_slices(), diff.new_slices());
+
+        for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
+            if idx > 0 {
+                writeln!(output, "{}", style("-".repeat(column * 2 + 15)).dim())?;
+            }
+            for op in group {
+                for row in rows(old, new, op) {
+                    let (sign1, style1, sign2, style2) = match row.tag {
+                        DiffTag::Equal => (" ", Style::new().dim(), " ", Style::new().dim()),
+                        _ => ("-", Style::new().red(), "+", Style::new().green()),
+                    };
+                    let side = |line: Option<(usize, &str)>, sign: &str, s: &Style| match line {
+                        Some((n, text)) => format!(
+                            "{} {}{}",
+                            style(format!("{:>4}", n)).dim(),
+                            s.apply_to(sign).bold(),
+                            s.apply_to(fit(text, column))
+                        ),
+                        None => " ".repeat(column + 6),
+                    };
+                    writeln!(
+                        output,
+                        "{} {} {}",
+                        side(row.old, sign1, &style1),
+                        style("│").dim(),
+                        side(row.new, sign2, &style2).trim_end()
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Truncate or pad the text to the width of a column, with tabs expanded.
+fn fit(text: &str, width: usize) -> String {
+    let text = text.replace('\t', "    ");
+    let text = match measure_text_width(&text) > width {
+        true => truncate_str(&text, width, "…").into_owned(),
+        false => text,
+    };
+    pad_str(&text, width, Alignment::Left, None).into_owned()
+}
+
+impl DiffRenderer for SideBySideRenderer {
+    fn render(&self, outcomes: &[DiffOutcome]) -> Result<String> {
+        let mut output = String::new();
+        for outcome in outcomes {
+            if outcomes.len() > 1 {
+                writeln!(
+                    &mut output,
+                    "{}",
+                    style(format!("== {}", outcome.name)).bold()
+                )?;
+            }
+            match &outcome.result {
+                Ok(result) => {
+                    self.render_diff(&mut output, &result.res1.text(), &result.res2.text())?;
+                    render_metrics(&mut output, outcome, result)?;
+                }
+                Err(e) => writeln!(&mut output, "{} {:#}", style("error:").red().bold(), e)?,
+            }
+        }
+        Ok(output)
+    }
+}
+
+const HTML_STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em; color: #24292f; }
+table.summary { border-collapse: collapse; margin-bottom: 2em; }
+table.summary th, table.summary td { padding: 4px 12px; border-bottom: 1px solid #d0d7de; text-align: left; }
+.same { color: #1a7f37; } .different { color: #9a6700; } .error { color: #cf222e; }
+details.profile { border: 1px solid #d0d7de; border-radius: 6px; margin-bottom: 1em; }
+details.profile > summary { padding: 8px 12px; background: #f6f8fa; cursor: pointer; font-weight: 600; }
+.diff { font-family: ui-monospace, SFMono-Regular, Menlo, monospace; font-size: 12px; }
+.row { display: grid; grid-template-columns: 4em 1fr 4em 1fr; }
+.row > span { white-space: pre-wrap; word-break: break-all; padding: 0 6px; }
+.num { color: #6e7781; text-align: right; user-select: none; }
+.del { background: #ffebe9; } .ins { background: #dafbe1; }
+details.unchanged > summary { padding: 2px 12px; background: #ddf4ff; color: #57606a; cursor: pointer; }
+pre.error { padding: 8px 12px; margin: 0; }
+pre.metrics { padding: 8px 12px; margin: 0; border-top: 1px solid #d0d7de; }
+"#;
+
+/// Unchanged regions longer than this are collapsed, keeping the lines next to the changes.
+const HTML_CONTEXT: usize = 3;
+
+impl HtmlRenderer {
+    fn render_diff(&self, output: &mut String, text1: &str, text2: &str) -> Result<()> {
+        let diff = TextDiff::from_lines(text1, text2);
+        let (old, new) = (diff.old_slices(), diff.new_slices());
+        let ops = diff.ops();
+
+        writeln!(output, r#"<div class="diff">"#)?;
+        for (idx, op) in ops.iter().enumerate() {
+            let rows = rows(old, new, op);
+            if op.tag() != DiffTag::Equal {
+                for row in &rows {
+                    write_html_row(output, row)?;
+                }
+                continue;
+            }
+            // keep the context after the previous change and before the next one
+            let head = if idx > 0 { HTML_CONTEXT } else { 0 };
+            let tail = if idx + 1 < ops.len() { HTML_CONTEXT } else { 0 };
+            if rows.len() <= head + tail + 1 {
+                for row in &rows {
+                    write_html_row(output, row)?;
+                }
+                continue;
+            }
+            for row in &rows[..head] {
+                write_html_row(output, row)?;
+            }
+            let hidden = &rows[head..rows.len() - tail];
+            writeln!(
+                output,
+                r#"<details class="unchanged"><summary>{} unchanged lines</summary>"#,
+                hidden.len()
+            )?;
+            for row in hidden {
+                write_html_row(output, row)?;
+            }
+            writeln!(output, "</details>")?;
+            for row in &rows[rows.len() - tail..] {
+                write_html_row(output, row)?;
+            }
+        }
+        writeln!(output, "</div>")?;
+        Ok(())
+    }
+}
+
+fn write_html_row(output: &mut String, row: &Row) -> Result<()> {
+    let changed = row.tag != DiffTag::Equal;
+    let side = |line: Option<(usize, &str)>, class: &str| match line {
+        Some((n, text)) => format!(
+            r#"<span class="num">{}</span><span class="{}">{}</span>"#,
+            n,
+            if changed { class } else { "" },
+            escape_xml(text)
+        ),
+        None => r#"<span class="num"></span><span></span>"#.to_string(),
+    };
+    writeln!(
+        output,
+        r#"<div class="row">{}{}</div>"#,
+        side(row.old, "del"),
+        side(row.new, "ins")
+    )?;
+    Ok(())
+}
+
+impl DiffRenderer for HtmlRenderer {
+    fn render(&self, outcomes: &[DiffOutcome]) -> Result<String> {
+        let mut output = String::new();
+        writeln!(&mut output, "<!DOCTYPE html>")?;
+        writeln!(
+            &mut output,
+            r#"<html><head><meta charset="utf-8"><title>xdiff report</title>"#
+        )?;
+        writeln!(&mut output, "<style>{}</style></head><body>", HTML_STYLE)?;
+        writeln!(&mut output, "<h1>xdiff report</h1>")?;
+
+        writeln!(&mut output, r#"<table class="summary">"#)?;
+        writeln!(
+            &mut output,
+            "<tr><th>Profile</th><th>Status</th><th>Changes</th><th>Time</th></tr>"
+        )?;
+        for (idx, outcome) in outcomes.iter().enumerate() {
+            let (status, changes) = html_status(outcome);
+            writeln!(
+                &mut output,
+                r##"<tr><td><a href="#profile-{}">{}</a></td><td class="{}">{}</td><td>{}</td><td>{}ms</td></tr>"##,
+                idx,
+                escape_xml(&outcome.name),
+                status,
+                status,
+                changes,
+                outcome.elapsed.as_millis()
+            )?;
+        }
+        writeln!(&mut output, "</table>")?;
+
+        for (idx, outcome) in outcomes.iter().enumerate() {
+            let (status, _) = html_status(outcome);
+            writeln!(
+                &mut output,
+                r#"<details class="profile" id="profile-{}"{}><summary>{} <span class="{}">{}</span></summary>"#,
+                idx,
+                if status == "same" { "" } else { " open" },
+                escape_xml(&outcome.name),
+                status,
+                status
+            )?;
+            match &outcome.result {
+                Ok(result) => {
+                    self.render_diff(&mut output, &result.res1.text(), &result.res2.text())?;
+                    let mut metrics = metrics_table(result, outcome.target_name())?;
+                    for failure in result.failures() {
+                        writeln!(&mut metrics, "{}", failure)?;
+                    }
+                    if !metrics.is_empty() {
+                        writeln!(
+                            &mut output,
+                            r#"<pre class="metrics">{}</pre>"#,
+                            escape_xml(&metrics)
+                        )?;
+                    }
+                }
+                Err(e) => writeln!(
+                    &mut output,
+                    r#"<pre class="error">{}</pre>"#,
+                    escape_xml(&format!("{:#}", e))
+                )?,
+            }
+            writeln!(&mut output, "</details>")?;
+        }
+        writeln!(&mut output, "</body></html>")?;
+        Ok(output)
+    }
+}
+
+impl DiffRenderer for MatrixRenderer {
+    fn render(&self, outcomes: &[DiffOutcome]) -> Result<String> {
+        let mut profiles: Vec<&str> = vec![];
+        let mut targets: Vec<&str> = vec![];
+        for outcome in outcomes {
+            if !profiles.contains(&outcome.profile()) {
+                profiles.push(outcome.profile());
+            }
+            // a profile which failed before any target is diffed has no column of its own
+            let target = outcome.target_name();
+            let failed = outcome.target.is_none() && outcome.result.is_err();
+            if !failed && !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        let cell = |profile: &str, target: &str| {
+            outcomes
+                .iter()
+                .find(|o| o.profile() == profile && o.target_name() == target)
+        };
+
+        let width = profiles
+            .iter()
+            .map(|p| p.len())
+            .chain(Some("PROFILE".len()))
+            .max()
+            .unwrap_or_default();
+        let column = targets
+            .iter()
+            .map(|t| t.len())
+            .chain(Some("different".len()))
+            .max()
+            .unwrap_or_default();
+
+        let mut output = String::new();
+        write!(&mut output, "{:<width$}", "PROFILE", width = width)?;
+        for target in &targets {
+            write!(&mut output, "  {:<column$}", target, column = column)?;
+        }
+        writeln!(&mut output)?;
+        for profile in &profiles {
+            write!(&mut output, "{:<width$}", profile, width = width)?;
+            let failed = outcomes
+                .iter()
+                .find(|o| o.profile() == *profile && o.target.is_none() && o.result.is_err());
+            for target in &targets {
+                let status = match failed.or_else(|| cell(profile, target)) {
+                    Some(outcome) => match &outcome.result {
+                        Ok(result) if result.is_same() => style(pad("same", column)).green(),
+                        Ok(_) => style(pad("different", column)).yellow(),
+                        Err(_) => style(pad("error", column)).red(),
+                    },
+                    None => style(pad("-", column)).dim(),
+                };
+                write!(&mut output, "  {}", status)?;
+            }
+            writeln!(&mut output)?;
+        }
+
+        for outcome in outcomes {
+            match &outcome.result {
+                Ok(result) if result.is_same() => {}
+                Ok(result) => {
+                    writeln!(&mut output, "\n{}:", style(&outcome.name).bold())?;
+                    match result.changes() {
+                        Some(changes) => {
+                            let (text1, text2) = (&result.res1.headers, &result.res2.headers);
+                            if text1 != text2 {
+                                writeln!(&mut output, "  headers")?;
+                            }
+                            for change in changes {
+                                writeln!(&mut output, "  {}", change.path())?;
+                            }
+                        }
+                        None if result.res1 != result.res2 => {
+                            writeln!(&mut output, "  {} changed lines", changed_lines(result))?
+                        }
+                        None => {}
+                    }
+                    for failure in result.failures() {
+                        writeln!(&mut output, "  {}", failure)?;
+                    }
+                }
+                Err(e) => writeln!(
+                    &mut output,
+                    "\n{}: {} {:#}",
+                    style(&outcome.name).bold(),
+                    style("error:").red().bold(),
+                    e
+                )?,
+            }
+        }
+        Ok(output)
+    }
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!("{:<width$}", text, width = width)
+}
+
+fn html_status(outcome: &DiffOutcome) -> (&'static str, usize) {
+    match &outcome.result {
+        Ok(result) if result.is_same() => ("same", 0),
+        Ok(result) => ("different", changed_lines(result)),
+        Err(_) => ("error", 0),
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReport<'a> {
+    profiles: Vec<JsonProfileReport<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonProfileReport<'a> {
+    name: &'a str,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hunks: Vec<Hunk>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    changed_paths: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    changes: Vec<JsonChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<MetricsReport<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    exceeded: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    violations: Option<ViolationsReport<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ViolationsReport<'a> {
+    req1: &'a [Violation],
+    target: &'a [Violation],
+}
+
+#[derive(Debug, Serialize)]
+struct MetricsReport<'a> {
+    req1: &'a Metrics,
+    target: &'a Metrics,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Same,
+    Different,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+struct Hunk {
+    old_start: usize,
+    old_lines: usize,
+    new_start: usize,
+    new_lines: usize,
+    lines: Vec<String>,
+}
+
+fn hunks(text1: &str, text2: &str) -> Vec<Hunk> {
+    let diff = TextDiff::from_lines(text1, text2);
+    diff.grouped_ops(3)
+        .iter()
+        .map(|group| {
+            let (first, last) = (&group[0], &group[group.len() - 1]);
+            let lines = group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| format!("{}{}", change.tag(), change.value().trim_end_matches('\n')))
+                .collect();
+            Hunk {
+                old_start: first.old_range().start + 1,
+                old_lines: last.old_range().end - first.old_range().start,
+                new_start: first.new_range().start + 1,
+                new_lines: last.new_range().end - first.new_range().start,
+                lines,
+            }
+        })
+        .collect()
+}
+
+impl DiffRenderer for JsonRenderer {
+    fn render(&self, outcomes: &[DiffOutcome]) -> Result<String> {
+        let profiles = outcomes
+            .iter()
+            .map(|outcome| match &outcome.result {
+                Ok(result) => {
+                    let changes = result.changes().unwrap_or_default();
+                    JsonProfileReport {
+                        name: &outcome.name,
+                        status: if result.is_same() {
+                            Status::Same
+                        } else {
+                            Status::Different
+                        },
+                        error: None,
+                        hunks: hunks(&result.res1.text(), &result.res2.text()),
+                        changed_paths: changes.iter().map(|c| c.path().to_string()).collect(),
+                        changes,
+                        metrics: Some(MetricsReport {
+                            req1: &result.metrics.res1,
+                            target: &result.metrics.res2,
+                        }),
+                        exceeded: result.metrics.exceeded.clone(),
+                        violations: (!result.violations.is_empty()).then(|| ViolationsReport {
+                            req1: &result.violations.res1,
+                            target: &result.violations.res2,
+                        }),
+                    }
+                }
+                Err(e) => JsonProfileReport {
+                    name: &outcome.name,
+                    status: Status::Error,
+                    error: Some(format!("{:#}", e)),
+                    hunks: vec![],
+                    changed_paths: vec![],
+                    changes: vec![],
+                    metrics: None,
+                    exceeded: vec![],
+                    violations: None,
+                },
+            })
+            .collect();
+
+        let mut output = serde_json::to_string_pretty(&JsonReport { profiles })?;
+        output.push('\n');
+        Ok(output)
+    }
+}
+
+impl DiffRenderer for JunitRenderer {
+    fn render(&self, outcomes: &[DiffOutcome]) -> Result<String> {
+        let failures = outcomes
+            .iter()
+            .filter(|o| matches!(&o.result, Ok(result) if !result.is_same()))
+            .count();
+        let errors = outcomes.iter().filter(|o| o.result.is_err()).count();
+
+        let mut output = String::new();
+        writeln!(&mut output, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            &mut output,
+            r#"<testsuite name="xdiff" tests="{}" failures="{}" errors="{}">"#,
+            outcomes.len(),
+            failures,
+            errors
+        )?;
+        for outcome in outcomes {
+            let name = escape_xml(&outcome.name);
+            let time = outcome.elapsed.as_secs_f64();
+            match &outcome.result {
+                Ok(result) if result.is_same() => {
+                    writeln!(
+                        &mut output,
+                        r#"  <testcase name="{}" classname="xdiff" time="{:.3}"/>"#,
+                        name, time
+                    )?;
+                }
+                Ok(result) => {
+                    writeln!(
+                        &mut output,
+                        r#"  <testcase name="{}" classname="xdiff" time="{:.3}">"#,
+                        name, time
+                    )?;
+                    let mut failure = unified_diff(outcome, result);
+                    for line in result.failures() {
+                        writeln!(&mut failure, "{}", line)?;
+                    }
+                    writeln!(
+                        &mut output,
+                        r#"    <failure message="responses differ">{}</failure>"#,
+                        escape_xml(&failure)
+                    )?;
+                    writeln!(&mut output, "  </testcase>")?;
+                }
+                Err(e) => {
+                    writeln!(
+                        &mut output,
+                        r#"  <testcase name="{}" classname="xdiff" time="{:.3}">"#,
+                        name, time
+                    )?;
+                    writeln!(
+                        &mut output,
+                        r#"    <error message="{}"/>"#,
+                        escape_xml(&format!("{:#}", e))
+                    )?;
+                    writeln!(&mut output, "  </testcase>")?;
+                }
+            }
+        }
+        writeln!(&mut output, "</testsuite>")?;
+        Ok(output)
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut output = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::res::ResponseParts;
+    use anyhow::anyhow;
+    use serde_json::json;
+
+    fn outcomes() -> Vec<DiffOutcome> {
+        let parts = |id: u32| ResponseParts {
+            headers: "\nHTTP/1.1 200 OK\n".into(),
+            body: format!("{{\n  \"id\": {}\n}}", id),
+            json: Some(json!({ "id": id })),
+        };
+        let result = |id1, id2| DiffResult {
+            mode: DiffMode::Json,
+            res1: parts(id1),
+            res2: parts(id2),
+            ..Default::default()
+        };
+        vec![
+            DiffOutcome::new("same", Ok(result(1, 1)), Duration::ZERO),
+            DiffOutcome::new("diff", Ok(result(1, 2)), Duration::ZERO),
+            DiffOutcome::new(
+                "broken",
+                Err(anyhow!("connection <refused>")),
+                Duration::ZERO,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_json_renderer() {
+        let output = JsonRenderer.render(&outcomes()).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let profiles = &report["profiles"];
+
+        assert_eq!(profiles[0]["status"], "same");
+        assert_eq!(profiles[1]["status"], "different");
+        assert_eq!(profiles[1]["changed_paths"], json!(["$.id"]));
+        assert_eq!(
+            profiles[1]["hunks"][0]["lines"],
+            json!([
+                " ",
+                " HTTP/1.1 200 OK",
+                " {",
+                "-  \"id\": 1",
+                "+  \"id\": 2",
+                " }"
+            ])
+        );
+        assert_eq!(profiles[2]["status"], "error");
+    }
+
+    #[test]
+    fn test_junit_renderer() {
+        let output = JunitRenderer.render(&outcomes()).unwrap();
+
+        assert!(output.contains(r#"tests="3" failures="1" errors="1""#));
+        assert!(output.contains(r#"<testcase name="same" classname="xdiff" time="0.000"/>"#));
+        assert!(output.contains("+  &quot;id&quot;: 2"));
+        assert!(output.contains(r#"<error message="connection &lt;refused&gt;"/>"#));
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let mut outcomes = outcomes();
+        assert_eq!(exit_code(&outcomes), 2);
+        outcomes.pop();
+        assert_eq!(exit_code(&outcomes), 1);
+        outcomes.pop();
+        assert_eq!(exit_code(&outcomes), 0);
+    }
+
+    #[test]
+    fn test_side_by_side_renderer() {
+        let renderer = SideBySideRenderer { width: 60 };
+        let output = renderer.render(&outcomes()[1..2]).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines
+            .iter()
+            .any(|l| l.contains("-  \"id\": 1") && l.contains("+  \"id\": 2")));
+        assert!(lines.iter().all(|l| measure_text_width(l) <= 60));
+    }
+
+    #[test]
+    fn test_html_renderer() {
+        let mut outcomes = outcomes();
+        let body = (1..20).map(|i| format!("line {}\n", i)).collect::<String>();
+        let parts = |last: &str| ResponseParts {
+            body: format!("{}{}", body, last),
+            ..Default::default()
+        };
+        outcomes.push(DiffOutcome::new(
+            "long",
+            Ok(DiffResult {
+                mode: DiffMode::Text,
+                res1: parts("<a>"),
+                res2: parts("<b>"),
+                ..Default::default()
+            }),
+            Duration::ZERO,
+        ));
+        let output = HtmlRenderer.render(&outcomes).unwrap();
+
+        assert_eq!(output.matches(r#"<details class="profile""#).count(), 4);
+        assert!(output.contains(r#"<details class="profile" id="profile-0"><summary>"#));
+        assert!(output.contains("<summary>16 unchanged lines</summary>"));
+        assert!(output.contains(r#"<span class="del">&lt;a&gt;</span>"#));
+        assert!(output.contains(r#"<pre class="error">connection &lt;refused&gt;</pre>"#));
+    }
+
+    #[test]
+    fn test_matrix_renderer() {
+        let mut outcomes = outcomes();
+        outcomes.pop();
+        let result = |id| {
+            let mut result = outcomes[1].result.as_ref().unwrap().clone();
+            result.res2.json = Some(json!({ "id": id }));
+            result.res2.body = format!("{{\n  \"id\": {}\n}}", id);
+            result
+        };
+        outcomes.extend(DiffOutcome::from_targets(
+            "rollout",
+            Ok(vec![
+                ("eu".into(), Ok(result(1))),
+                ("us".into(), Ok(result(3))),
+                ("canary".into(), Err(anyhow!("timeout"))),
+            ]),
+            Duration::ZERO,
+        ));
+        assert_eq!(outcomes[3].name, "rollout/us");
+        assert_eq!(outcomes[3].profile(), "rollout");
+
+        let output = MatrixRenderer.render(&outcomes).unwrap();
+        let lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+        assert_eq!(
+            lines[..4],
+            [
+                "PROFILE  req2       eu         us         canary",
+                "same     same       -          -          -",
+                "diff     different  -          -          -",
+                "rollout  -          same       different  error",
+            ]
+        );
+        assert!(output.contains("\nrollout/us:\n  $.id\n"));
+        assert!(output.contains("rollout/canary: error: timeout"));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut outcomes = outcomes();
+        let result = outcomes[0].result.as_mut().unwrap();
+        result.metrics.res1 = Metrics {
+            dns_ms: Some(1.25),
+            ttfb_ms: 10.0,
+            total_ms: 12.0,
+            size: 100,
+            ..Default::default()
+        };
+        result.metrics.res2 = Metrics {
+            ttfb_ms: 14.0,
+            total_ms: 18.0,
+            size: 100,
+            ..Default::default()
+        };
+        result.metrics.exceeded =
+            vec!["total time 12.0ms -> 18.0ms (+50%), over the 20% threshold".into()];
+        assert!(!result.is_same());
+
+        assert_eq!(
+            metrics_table(result, REQ2).unwrap(),
+            "               req1        req2\n\
+             dns           1.2ms           -\n\
+             connect         n/a         n/a\n\
+             ttfb         10.0ms      14.0ms    +40%\n\
+             total        12.0ms      18.0ms    +50%\n\
+             size           100B        100B     +0%\n"
+        );
+        let output = UnifiedRenderer.render(&outcomes[..1]).unwrap();
+        assert_eq!(
+            output,
+            "# same: total time 12.0ms -> 18.0ms (+50%), over the 20% threshold\n"
+        );
+        assert_eq!(exit_code(&outcomes[..1]), 1);
+    }
+
+    #[test]
+    fn test_unified_renderer_has_no_escapes() {
+        let output = UnifiedRenderer.render(&outcomes()).unwrap();
+
+        assert!(output.contains("--- diff/req1\n+++ diff/req2\n"));
+        assert!(!output.contains('\x1b'));
+    }
+}
@@ -0,0 +1,54 @@
+/// Replace every `{{ name }}` placeholder in the text with the value from `lookup`.
+///
+/// Unresolved placeholders are kept as is, and their names are returned along with the output.
+pub fn render_template(
+    text: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(text.len());
+    let mut missing = vec![];
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 2];
+        let name = placeholder[2..placeholder.len() - 2].trim();
+
+        output.push_str(&rest[..start]);
+        match lookup(name) {
+            Some(value) => output.push_str(&value),
+            None => {
+                missing.push(name.to_string());
+                output.push_str(placeholder);
+            }
+        }
+        rest = &rest[start + len + 2..];
+    }
+    output.push_str(rest);
+
+    (output, missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let lookup = |name: &str| (name == "host").then(|| "example.com".to_string());
+
+        assert_eq!(
+            render_template("https://{{ host }}/{{host}}?a={{ id }}{{", lookup),
+            (
+                "https://example.com/example.com?a={{ id }}{{".to_string(),
+                vec!["id".to_string()]
+            )
+        );
+        assert_eq!(
+            render_template("plain", lookup),
+            ("plain".to_string(), vec![])
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use crate::utils::{diff_text, render::DiffOutcome};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use console::style;
+use tokio::{fs, time::sleep};
+
+/// How often the config files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What started an iteration of a watch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Start,
+    Interval,
+    ConfigChanged,
+}
+
+/// The compared parts of a target in one iteration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TargetState {
+    Same(String),
+    Different { res1: String, res2: String },
+    Error(String),
+}
+
+impl TargetState {
+    fn new(outcome: &DiffOutcome) -> Self {
+        match &outcome.result {
+            Ok(result) if result.is_same() => TargetState::Same(result.res1.text()),
+            Ok(result) => TargetState::Different {
+                res1: result.res1.text(),
+                res2: result.res2.text(),
+            },
+            Err(e) => TargetState::Error(format!("{:#}", e)),
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match self {
+            TargetState::Same(_) => "same",
+            TargetState::Different { .. } => "different",
+            TargetState::Error(_) => "error",
+        }
+    }
+
+    /// The responses of req1 and the target, which are the same if the target matches req1.
+    fn responses(&self) -> Option<(&str, &str)> {
+        match self {
+            TargetState::Same(res) => Some((res, res)),
+            TargetState::Different { res1, res2 } => Some((res1, res2)),
+            TargetState::Error(_) => None,
+        }
+    }
+}
+
+/// A line of the history of a watch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IterationSummary {
+    pub number: usize,
+    pub trigger: Trigger,
+    pub elapsed: Duration,
+    /// The number of the targets which are the same, different and failed.
+    pub counts: [usize; 3],
+    /// The targets whose status or responses changed since the previous iteration.
+    pub changed: Vec<String>,
+}
+
+/// The results of the iterations of a watch, to report what changed since the previous one.
+#[derive(Debug)]
+pub struct Watch {
+    capacity: usize,
+    last: Option<BTreeMap<String, TargetState>>,
+    history: VecDeque<IterationSummary>,
+}
+
+impl Watch {
+    /// Keep the summaries of at most `capacity` iterations.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            last: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn history(&self) -> &VecDeque<IterationSummary> {
+        &self.history
+    }
+
+    /// Record the outcomes of an iteration, and render what changed since the previous one.
+    /// The first iteration renders nothing as there is nothing to compare it to.
+    pub fn update(
+        &mut self,
+        outcomes: &[DiffOutcome],
+        trigger: Trigger,
+        elapsed: Duration,
+    ) -> Result<String> {
+        let states: BTreeMap<String, TargetState> = outcomes
+            .iter()
+            .map(|outcome| (outcome.name.clone(), TargetState::new(outcome)))
+            .collect();
+
+        let mut output = String::new();
+        let mut changed = vec![];
+        if let Some(last) = self.last.as_ref() {
+            for (name, state) in &states {
+                match last.get(name) {
+                    Some(prev) if prev == state => continue,
+                    Some(prev) => render_change(&mut output, name, prev, state)?,
+                    None => writeln!(output, "{}: new target, {}", name, state.status())?,
+                }
+                changed.push(name.clone());
+            }
+            for name in last.keys().filter(|name| !states.contains_key(*name)) {
+                writeln!(output, "{}: removed", name)?;
+                changed.push(name.clone());
+            }
+        }
+
+        let mut counts = [0; 3];
+        for state in states.values() {
+            match state {
+                TargetState::Same(_) => counts[0] += 1,
+                TargetState::Different { .. } => counts[1] += 1,
+                TargetState::Error(_) => counts[2] += 1,
+            }
+        }
+        self.history.push_back(IterationSummary {
+            number: self.history.back().map_or(1, |s| s.number + 1),
+            trigger,
+            elapsed,
+            counts,
+            changed,
+        });
+        while self.history.len() > self.capacity.max(1) {
+            self.history.pop_front();
+        }
+        self.last = Some(states);
+        Ok(output)
+    }
+
+    /// The summaries of the recent iterations, the latest last.
+    pub fn render_history(&self) -> String {
+        self.history.iter().map(|s| format!("{}\n", s)).collect()
+    }
+}
+
+impl fmt::Display for IterationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let trigger = match self.trigger {
+            Trigger::Start => "start",
+            Trigger::Interval => "interval",
+            Trigger::ConfigChanged => "config",
+        };
+        let [same, different, errors] = self.counts;
+        write!(
+            f,
+            "#{:<4} {:>6}s  {:<8}  {} same, {} different, {} errors",
+            self.number,
+            self.elapsed.as_secs(),
+            trigger,
+            same,
+            different,
+            errors
+        )?;
+        match self.changed.as_slice() {
+            [] if self.number > 1 => write!(f, ", no changes"),
+            [] => Ok(()),
+            changed => write!(f, ", changed: {}", changed.join(", ")),
+        }
+    }
+}
+
+fn render_change(
+    output: &mut String,
+    name: &str,
+    prev: &TargetState,
+    state: &TargetState,
+) -> Result<()> {
+    let status = match prev.status() == state.status() {
+        true => state.status().to_string(),
+        false => format!("{} -> {}", prev.status(), state.status()),
+    };
+    writeln!(
+        output,
+        "{}",
+        style(format!("== {}: {}", name, status)).bold()
+    )?;
+    match (prev.responses(), state.responses()) {
+        (Some((old1, old2)), Some((new1, new2))) => {
+            if old1 != new1 {
+                writeln!(output, "{}", style("req1 changed:").dim())?;
+                output.push_str(&diff_text(old1, new1)?);
+            }
+            if old2 != new2 && (old1 != old2 || new1 != new2) {
+                writeln!(output, "{}", style("target changed:").dim())?;
+                output.push_str(&diff_text(old2, new2)?);
+            }
+        }
+        (_, None) => {
+            if let TargetState::Error(e) = state {
+                writeln!(output, "{} {}", style("error:").red().bold(), e)?;
+            }
+        }
+        (None, Some(_)) => {}
+    }
+    Ok(())
+}
+
+/// Wait for the interval to pass, if any, or for one of the config files to be modified,
+/// i.e. the config file or a file it includes.
+pub async fn wait_for_change(files: &[PathBuf], interval: Option<Duration>) -> Trigger {
+    let modified = modified_times(files).await;
+    let mut waited = Duration::ZERO;
+    loop {
+        sleep(POLL_INTERVAL).await;
+        waited += POLL_INTERVAL;
+        if modified_times(files).await != modified {
+            return Trigger::ConfigChanged;
+        }
+        if interval.is_some_and(|interval| waited >= interval) {
+            return Trigger::Interval;
+        }
+    }
+}
+
+async fn modified_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    let mut times = vec![];
+    for file in files {
+        times.push(modified_time(file).await);
+    }
+    times
+}
+
+async fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).await.ok()?.modified().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::{
+        res::{DiffMode, ResponseParts},
+        xdiff::DiffResult,
+    };
+    use anyhow::anyhow;
+
+    fn outcome(name: &str, body1: &str, body2: &str) -> DiffOutcome {
+        let parts = |body: &str| ResponseParts {
+            headers: "\nHTTP/1.1 200 OK\n".into(),
+            body: body.into(),
+            json: None,
+        };
+        let result = DiffResult {
+            mode: DiffMode::Text,
+            res1: parts(body1),
+            res2: parts(body2),
+            ..Default::default()
+        };
+        DiffOutcome::new(name, Ok(result), Duration::ZERO)
+    }
+
+    #[test]
+    fn test_watch() {
+        let mut watch = Watch::new(2);
+        let first = [outcome("a", "1", "1"), outcome("b", "1", "2")];
+        assert_eq!(
+            watch
+                .update(&first, Trigger::Start, Duration::ZERO)
+                .unwrap(),
+            ""
+        );
+
+        let same = watch
+            .update(&first, Trigger::Interval, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(same, "");
+
+        let second = [
+            outcome("a", "1", "3"),
+            DiffOutcome::new("b", Err(anyhow!("timeout")), Duration::ZERO),
+        ];
+        let output = watch
+            .update(&second, Trigger::ConfigChanged, Duration::from_secs(7))
+            .unwrap();
+        let output = console::strip_ansi_codes(&output);
+        assert!(output.contains("== a: same -> different\ntarget changed:\n"));
+        assert!(output.contains("-1"));
+        assert!(output.contains("+3"));
+        assert!(output.contains("== b: different -> error\nerror: timeout\n"));
+
+        assert_eq!(
+            watch.render_history(),
+            "#2         5s  interval  1 same, 1 different, 0 errors, no changes\n\
+             #3         7s  config    0 same, 1 different, 1 errors, changed: a, b\n"
+        );
+    }
+}